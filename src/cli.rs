@@ -20,4 +20,7 @@ pub struct Args {
     pub log_dir: Option<PathBuf>,
     #[arg(long)]
     pub verbose: bool,
+    /// Retry notifications that have permanently failed before
+    #[arg(long)]
+    pub replay_dead_letters: bool,
 }
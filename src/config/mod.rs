@@ -1,14 +1,24 @@
 mod overridable;
 mod secret;
 mod validator;
+mod watcher;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
-use std::{collections::HashMap, time::Duration};
-
-use anyhow::anyhow;
+use anyhow::{anyhow, ensure};
 pub use overridable::*;
 pub use secret::*;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use spdlog::prelude::*;
 pub use validator::*;
+pub use watcher::*;
 
 use crate::{
     helper, notify,
@@ -20,6 +30,12 @@ use crate::{
 pub struct Config {
     #[serde(with = "humantime_serde")]
     pub interval: Duration,
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
+    #[serde(default, with = "humantime_serde")]
+    pub shutdown_timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_backoff: Option<Duration>,
     reporter: Accessor<Option<ConfigReporterRaw>>,
     #[serde(default)]
     platform: Accessor<PlatformGlobal>,
@@ -28,26 +44,23 @@ pub struct Config {
     subscription: HashMap<String, Vec<SubscriptionRaw>>,
 }
 
-#[cfg(not(test))]
-static CONFIG: once_cell::sync::OnceCell<Config> = once_cell::sync::OnceCell::new();
-#[cfg(test)]
-static CONFIG: parking_lot::RwLock<Option<std::sync::Arc<Config>>> = parking_lot::RwLock::new(None);
+static CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+thread_local! {
+    // Validators may access other parts of the config via `Config::global()`, so
+    // while validating a config that has not yet been published, redirect it here
+    static VALIDATING: RefCell<Option<Arc<Config>>> = const { RefCell::new(None) };
+}
 
 impl Config {
-    pub async fn init(input: impl AsRef<str>) -> anyhow::Result<&'static Self> {
-        let config = toml::from_str::<Self>(input.as_ref())?;
-
-        #[cfg(not(test))]
-        CONFIG
-            .set(config)
-            .map_err(|_| anyhow!("config was initialized before"))?;
-        #[cfg(test)]
-        drop(config); // Suppress the warning of unused variable
-
-        let config = Self::global();
-        config
-            .validate()
-            .map_err(|err| anyhow!("invalid configuration: {err}"))?;
+    pub async fn init(input: impl AsRef<str>) -> anyhow::Result<Arc<Self>> {
+        let config = Self::parse_then_validate(input)?;
+        {
+            let mut global = CONFIG.write().unwrap();
+            ensure!(global.is_none(), "config was initialized before");
+            *global = Some(Arc::clone(&config));
+        }
+
         if let Some(reporter) = &*config.reporter {
             reporter
                 .init(&config.notify_map)
@@ -56,31 +69,61 @@ impl Config {
         Ok(config)
     }
 
+    // Replaces the global config with a new one if it's valid, returns the old and
+    // the new config. The old config is dropped once no one holds it anymore.
+    pub fn reload(input: impl AsRef<str>) -> anyhow::Result<(Arc<Self>, Arc<Self>)> {
+        let new = Self::parse_then_validate(input)?;
+        let old = CONFIG
+            .write()
+            .unwrap()
+            .replace(Arc::clone(&new))
+            .expect("config was not initialized");
+
+        if old.reporter.as_ref().map(|r| &r.log) != new.reporter.as_ref().map(|r| &r.log) {
+            warn!("changes to the log reporter will not take effect until restart");
+        }
+        if old.state_dir != new.state_dir {
+            warn!("changes to 'state_dir' will not take effect until restart");
+        }
+        Ok((old, new))
+    }
+
+    fn parse_then_validate(input: impl AsRef<str>) -> anyhow::Result<Arc<Self>> {
+        let config = Arc::new(toml::from_str::<Self>(input.as_ref())?);
+
+        VALIDATING.set(Some(Arc::clone(&config)));
+        let result = config.validate();
+        VALIDATING.set(None);
+
+        result
+            .map(|_| config)
+            .map_err(|err| anyhow!("invalid configuration: {err}"))
+    }
+
     #[cfg(test)]
     fn parse_for_test(input: impl AsRef<str>, cb: impl FnOnce(anyhow::Result<&Config>)) {
         let config = toml::from_str::<Self>(input.as_ref()).map_err(anyhow::Error::from);
         match config {
             Ok(config) => {
-                let mut write_guard = CONFIG.write();
-                *write_guard = Some(std::sync::Arc::new(config));
-                let read_guard = parking_lot::RwLockWriteGuard::downgrade(write_guard);
-                cb(read_guard
-                    .as_ref()
-                    .unwrap()
+                // Thread local, so tests running in parallel don't interfere with each other
+                let config = Arc::new(config);
+                VALIDATING.set(Some(Arc::clone(&config)));
+                cb(config
                     .validate()
                     .map_err(|err| anyhow!("invalid configuration: {err}"))
-                    .map(|_| &**read_guard.as_ref().unwrap()))
+                    .map(|_| &*config));
+                VALIDATING.set(None);
             }
             Err(err) => cb(Err(err)),
         }
     }
 
-    pub fn global() -> &'static Self {
-        #[cfg(not(test))]
-        let ret = &CONFIG.get().expect("config was not initialized");
-        #[cfg(test)]
-        let ret = Box::leak(Box::new(CONFIG.read().clone().unwrap()));
-        ret
+    // Returns a snapshot, it's not affected by reloading afterwards
+    pub fn global() -> Arc<Self> {
+        VALIDATING
+            .with_borrow(|validating| validating.clone())
+            .or_else(|| CONFIG.read().unwrap().clone())
+            .expect("config was not initialized")
     }
 
     pub fn platform(&self) -> &Accessor<PlatformGlobal> {
@@ -102,7 +145,10 @@ impl Config {
                         notify: subscription
                             .notify_ref
                             .iter()
-                            .map(|notify_ref| self.notify_map.get_by_ref(notify_ref).unwrap())
+                            .map(|notify_ref| NotifyResolved {
+                                key: notify_ref.key(),
+                                params: self.notify_map.get_by_ref(notify_ref).unwrap(),
+                            })
                             .collect(),
                     },
                 )
@@ -154,6 +200,10 @@ pub struct PlatformGlobal {
     pub telegram: Accessor<Option<notify::platform::telegram::ConfigGlobal>>,
     #[serde(rename = "Twitter")]
     pub twitter: Accessor<Option<source::platform::twitter::ConfigGlobal>>,
+    #[serde(rename = "Twitch")]
+    pub twitch: Accessor<Option<source::platform::twitch::ConfigGlobal>>,
+    #[serde(rename = "Bluesky")]
+    pub bluesky: Accessor<Option<source::platform::bluesky::ConfigGlobal>>,
     #[serde(rename = "bilibili")]
     pub bilibili: Accessor<Option<source::platform::bilibili::ConfigGlobal>>,
 }
@@ -163,11 +213,40 @@ impl Validator for PlatformGlobal {
         self.qq.validate()?;
         self.telegram.validate()?;
         self.twitter.validate()?;
+        self.twitch.validate()?;
+        self.bluesky.validate()?;
         self.bilibili.validate()?;
         Ok(())
     }
 }
 
+impl PlatformGlobal {
+    // Keeps only the sections used by the given source and notifiers, so that a
+    // change to other sections doesn't restart the task on reloading
+    pub fn used_by<'a>(
+        &self,
+        source: &source::platform::Config,
+        notify: impl IntoIterator<Item = &'a NotifyResolved>,
+    ) -> Self {
+        let mut used = Self::default();
+        match source {
+            source::platform::Config::BilibiliPlayback(_) => used.bilibili = self.bilibili.clone(),
+            source::platform::Config::Twitter(_) => used.twitter = self.twitter.clone(),
+            source::platform::Config::Twitch(_) => used.twitch = self.twitch.clone(),
+            source::platform::Config::Bluesky(_) => used.bluesky = self.bluesky.clone(),
+            _ => {}
+        }
+        for notify in notify {
+            match &*notify.params {
+                notify::platform::Config::Qq(_) => used.qq = self.qq.clone(),
+                notify::platform::Config::Telegram(_) => used.telegram = self.telegram.clone(),
+                _ => {}
+            }
+        }
+        used
+    }
+}
+
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct SubscriptionRaw {
     pub platform: Accessor<source::platform::Config>,
@@ -181,7 +260,15 @@ pub struct SubscriptionRaw {
 pub struct SubscriptionRef<'a> {
     pub platform: &'a Accessor<source::platform::Config>,
     pub interval: Option<Duration>,
-    pub notify: Vec<Accessor<notify::platform::Config>>,
+    pub notify: Vec<NotifyResolved>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotifyResolved {
+    // Identifies the reference within a subscription, and is kept across restarts as
+    // long as the reference is unchanged
+    pub key: String,
+    pub params: Accessor<notify::platform::Config>,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
@@ -191,9 +278,15 @@ pub struct Notifications {
     pub live_online: bool,
     #[serde(default = "helper::refl_bool::<false>")]
     pub live_title: bool,
+    #[serde(default = "helper::refl_bool::<false>")]
+    pub live_area: bool,
     #[serde(default = "helper::refl_bool::<true>")]
     pub post: bool,
     #[serde(default = "helper::refl_bool::<true>")]
+    pub post_edited: bool,
+    #[serde(default = "helper::refl_bool::<true>")]
+    pub post_deleted: bool,
+    #[serde(default = "helper::refl_bool::<true>")]
     pub log: bool,
     #[serde(default = "helper::refl_bool::<true>")]
     pub playback: bool,
@@ -217,7 +310,10 @@ impl Overridable for Notifications {
         Self {
             live_online: new.live_online.unwrap_or(self.live_online),
             live_title: new.live_title.unwrap_or(self.live_title),
+            live_area: new.live_area.unwrap_or(self.live_area),
             post: new.post.unwrap_or(self.post),
+            post_edited: new.post_edited.unwrap_or(self.post_edited),
+            post_deleted: new.post_deleted.unwrap_or(self.post_deleted),
             log: new.log.unwrap_or(self.log),
             playback: new.playback.unwrap_or(self.playback),
             document: new.document.unwrap_or(self.document),
@@ -230,7 +326,10 @@ impl Overridable for Notifications {
 pub struct NotificationsOverride {
     pub live_online: Option<bool>,
     pub live_title: Option<bool>,
+    pub live_area: Option<bool>,
     pub post: Option<bool>,
+    pub post_edited: Option<bool>,
+    pub post_deleted: Option<bool>,
     pub log: Option<bool>,
     pub playback: Option<bool>,
     pub document: Option<bool>,
@@ -256,6 +355,18 @@ impl NotifyRef {
             NotifyRef::Override { name, .. } => name,
         }
     }
+
+    // The name, followed by a hash of the overrides if any, so that overriding the
+    // same notify differently gets different keys
+    fn key(&self) -> String {
+        match self {
+            NotifyRef::Direct(name) => name.clone(),
+            NotifyRef::Override { name, new } => {
+                let hash = Sha256::digest(new.to_string());
+                format!("{name}.{}", hex::encode(&hash[..4]))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Deserialize)]
@@ -300,17 +411,27 @@ mod tests {
         Config::parse_for_test(
             r#"
 interval = '1min'
+state_dir = "/var/lib/closely/"
+shutdown_timeout = '10s'
+max_backoff = '1h'
 reporter = { log = { notify = ["meow"] }, heartbeat = { type = "HttpGet", url = "https://example.com/", interval = '1min' } } 
 
 [platform.QQ.account.MyQQ]
 lagrange = { remote_http = { host = "localhost", port = 8000 } }
 
+[platform.QQ.account.MyNapCat]
+lagrange = { listen_ws = { host = "127.0.0.1", port = 8001 }, access_token = "qqq" }
+
 [platform.Telegram]
 token = "ttt"
 
 [platform.Twitter]
 auth = { cookies = "a=b;c=d;ct0=blah" }
 
+[platform.Twitch]
+client_id = "iii"
+client_secret = "sss"
+
 [platform.bilibili]
 playback = { bililive_recorder = { listen_webhook = { host = "127.0.0.1", port = 8888 }, working_directory = "/brec/" } }
 
@@ -334,6 +455,9 @@ notify = ["meow", "woof", { ref = "woof", id = 123 }]
             |c| {
                 assert_eq!(c.unwrap(), &Config {
                     interval: Duration::from_secs(60), // 1min
+                    state_dir: Some("/var/lib/closely/".into()),
+                    shutdown_timeout: Some(Duration::from_secs(10)),
+                    max_backoff: Some(Duration::from_secs(60 * 60)),
                     reporter: Accessor::new(Some(ConfigReporterRaw {
                         log: Accessor::new(Some(ConfigReporterLog {
                             notify_ref: vec![NotifyRef::Direct("meow".into())],
@@ -350,12 +474,25 @@ notify = ["meow", "woof", { ref = "woof", id = 123 }]
                             account: HashMap::from_iter([
                                 ("MyQQ".into(), Accessor::new(notify::platform::qq::ConfigAccount {
                                     lagrange: notify::platform::qq::lagrange::ConfigLagrange {
-                                        remote_http: notify::platform::qq::lagrange::RemoteHttp {
-                                            host: "localhost".into(),
-                                            port: 8000,
-                                        },
+                                        transport: notify::platform::qq::lagrange::ConfigTransport::RemoteHttp(
+                                            notify::platform::qq::lagrange::RemoteHttp {
+                                                host: "localhost".into(),
+                                                port: 8000,
+                                            }
+                                        ),
                                         access_token: None,
                                     }
+                                })),
+                                ("MyNapCat".into(), Accessor::new(notify::platform::qq::ConfigAccount {
+                                    lagrange: notify::platform::qq::lagrange::ConfigLagrange {
+                                        transport: notify::platform::qq::lagrange::ConfigTransport::ListenWs(
+                                            notify::platform::qq::lagrange::ListenWs {
+                                                host: "127.0.0.1".into(),
+                                                port: 8001,
+                                            }
+                                        ),
+                                        access_token: Some(notify::platform::qq::lagrange::ConfigAccessToken::with_raw("qqq")),
+                                    }
                                 }))
                             ])
                         })),
@@ -367,6 +504,11 @@ notify = ["meow", "woof", { ref = "woof", id = 123 }]
                         twitter: Accessor::new(Some(source::platform::twitter::ConfigGlobal {
                             auth: source::platform::twitter::ConfigCookies::with_raw("a=b;c=d;ct0=blah")
                         })),
+                        twitch: Accessor::new(Some(source::platform::twitch::ConfigGlobal {
+                            client_id: "iii".into(),
+                            client_secret: source::platform::twitch::ConfigClientSecret::with_raw("sss")
+                        })),
+                        bluesky: Accessor::new(None),
                         bilibili: Accessor::new(Some(source::platform::bilibili::ConfigGlobal {
                             playback: Accessor::new(Some(source::platform::bilibili::playback::ConfigGlobal {
                                 bililive_recorder: Accessor::new(source::platform::bilibili::playback::bililive_recorder::ConfigBililiveRecorder {
@@ -395,7 +537,10 @@ notify = ["meow", "woof", { ref = "woof", id = 123 }]
                                 notifications: Notifications {
                                     live_online: true,
                                     live_title: false,
+                                    live_area: false,
                                     post: false,
+                                    post_edited: true,
+                                    post_deleted: true,
                                     log: true,
                                     playback: true,
                                     document: true,
@@ -412,7 +557,7 @@ notify = ["meow", "woof", { ref = "woof", id = 123 }]
                         vec![
                             SubscriptionRaw {
                                 platform: Accessor::new(source::platform::Config::BilibiliLive(
-                                    Accessor::new(source::platform::bilibili::live::ConfigParams { user_id: 123456 })
+                                    Accessor::new(source::platform::bilibili::live::ConfigParams { user_id: 123456, realtime: false })
                                 )),
                                 interval: Some(Duration::from_secs(30)),
                                 notify_ref: vec![NotifyRef::Direct("meow".into())],
@@ -580,50 +725,60 @@ notify = ["meow", { ref = "woof", thread_id = 114 }, { ref = "woof", notificatio
                         SubscriptionRef {
                             platform: &Accessor::new(source::platform::Config::BilibiliLive(
                                 Accessor::new(source::platform::bilibili::live::ConfigParams {
-                                    user_id: 123456
+                                    user_id: 123456,
+                                    realtime: false,
                                 })
                             )),
                             interval: None,
                             notify: vec![
-                                Accessor::new(notify::platform::Config::Telegram(Accessor::new(
-                                    notify::platform::telegram::ConfigParams {
-                                        notifications: Notifications::default(),
-                                        chat: notify::platform::telegram::ConfigChat::Id(1234),
-                                        thread_id: Some(123),
-                                        token: Some(
-                                            notify::platform::telegram::ConfigToken::with_raw(
-                                                "xxx"
-                                            )
-                                        ),
-                                    }
-                                ))),
-                                Accessor::new(notify::platform::Config::Telegram(Accessor::new(
-                                    notify::platform::telegram::ConfigParams {
-                                        notifications: Notifications::default(),
-                                        chat: notify::platform::telegram::ConfigChat::Id(5678),
-                                        thread_id: Some(114),
-                                        token: Some(
-                                            notify::platform::telegram::ConfigToken::with_raw(
-                                                "yyy"
-                                            )
-                                        ),
-                                    }
-                                ))),
-                                Accessor::new(notify::platform::Config::Telegram(Accessor::new(
-                                    notify::platform::telegram::ConfigParams {
-                                        notifications: Notifications {
-                                            post: false,
-                                            ..Default::default()
-                                        },
-                                        chat: notify::platform::telegram::ConfigChat::Id(5678),
-                                        thread_id: Some(456),
-                                        token: Some(
-                                            notify::platform::telegram::ConfigToken::with_raw(
-                                                "yyy"
-                                            )
-                                        ),
-                                    }
-                                )))
+                                NotifyResolved {
+                                    key: "meow".into(),
+                                    params: Accessor::new(notify::platform::Config::Telegram(
+                                        Accessor::new(notify::platform::telegram::ConfigParams {
+                                            notifications: Notifications::default(),
+                                            chat: notify::platform::telegram::ConfigChat::Id(1234),
+                                            thread_id: Some(123),
+                                            token: Some(
+                                                notify::platform::telegram::ConfigToken::with_raw(
+                                                    "xxx"
+                                                )
+                                            ),
+                                        })
+                                    )),
+                                },
+                                NotifyResolved {
+                                    key: "woof.e1d48648".into(),
+                                    params: Accessor::new(notify::platform::Config::Telegram(
+                                        Accessor::new(notify::platform::telegram::ConfigParams {
+                                            notifications: Notifications::default(),
+                                            chat: notify::platform::telegram::ConfigChat::Id(5678),
+                                            thread_id: Some(114),
+                                            token: Some(
+                                                notify::platform::telegram::ConfigToken::with_raw(
+                                                    "yyy"
+                                                )
+                                            ),
+                                        })
+                                    )),
+                                },
+                                NotifyResolved {
+                                    key: "woof.92080017".into(),
+                                    params: Accessor::new(notify::platform::Config::Telegram(
+                                        Accessor::new(notify::platform::telegram::ConfigParams {
+                                            notifications: Notifications {
+                                                post: false,
+                                                ..Default::default()
+                                            },
+                                            chat: notify::platform::telegram::ConfigChat::Id(5678),
+                                            thread_id: Some(456),
+                                            token: Some(
+                                                notify::platform::telegram::ConfigToken::with_raw(
+                                                    "yyy"
+                                                )
+                                            ),
+                                        })
+                                    )),
+                                }
                             ],
                         }
                     ),]
@@ -0,0 +1,64 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use spdlog::prelude::*;
+use tokio::{fs, sync::mpsc, time::MissedTickBehavior};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Yields whenever the config file is modified or SIGHUP is received
+pub fn watch(path: PathBuf) -> mpsc::Receiver<()> {
+    // Triggers are coalesced, it doesn't matter how many times it changed since the
+    // last reload
+    let (sender, receiver) = mpsc::channel(1);
+
+    tokio::spawn(poll_modified(path, sender.clone()));
+    #[cfg(unix)]
+    tokio::spawn(listen_sighup(sender));
+
+    receiver
+}
+
+async fn poll_modified(path: PathBuf, sender: mpsc::Sender<()>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut last_modified = modified_time(&path).await;
+    loop {
+        interval.tick().await;
+
+        let current = modified_time(&path).await;
+        if current.is_some() && current != last_modified {
+            info!("config file modification detected");
+            last_modified = current;
+            _ = sender.try_send(());
+        }
+    }
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .inspect_err(|err| warn!("failed to get modified time of config file: {err}"))
+        .ok()
+}
+
+#[cfg(unix)]
+async fn listen_sighup(sender: mpsc::Sender<()>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(err) => {
+            error!("failed to listen SIGHUP: {err}");
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        info!("SIGHUP received");
+        _ = sender.try_send(());
+    }
+}
@@ -66,6 +66,50 @@ pub fn format_duration_in_min(dur: Duration) -> String {
     humantime::format_duration(Duration::from_secs(mins * 60)).to_string()
 }
 
+// `base * 2^exponent`, but no more than `max`
+pub fn exponential_backoff(base: Duration, exponent: u32, max: Duration) -> Duration {
+    base.saturating_mul(2_u32.saturating_pow(exponent)).min(max)
+}
+
+// Truncates to at most `limit` chars, ending with `…` if truncated
+pub fn truncate_chars(mut text: String, limit: usize) -> String {
+    if let Some((index, _)) = text.char_indices().nth(limit) {
+        text.truncate(index);
+        text.pop();
+        text.push('…');
+    }
+    text
+}
+
+// Truncates to at most `limit` bytes, ending with `…` if truncated
+pub fn truncate_bytes(mut text: String, limit: usize) -> String {
+    if text.len() > limit {
+        let mut index = limit - '…'.len_utf8();
+        while !text.is_char_boundary(index) {
+            index -= 1;
+        }
+        text.truncate(index);
+        text.push('…');
+    }
+    text
+}
+
+// Escapes text for HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub async fn ffmpeg_copy(from: &Path, to: &Path) -> anyhow::Result<()> {
     let mut cmd = Command::new("ffmpeg");
     cmd.arg("-i").arg(from).arg("-c").arg("copy").arg(to);
@@ -157,4 +201,32 @@ mod tests {
             "1h 1m"
         );
     }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let max = Duration::from_secs(600);
+        assert_eq!(
+            exponential_backoff(Duration::from_secs(30), 0, max),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            exponential_backoff(Duration::from_secs(30), 2, max),
+            Duration::from_secs(120)
+        );
+        assert_eq!(exponential_backoff(Duration::from_secs(30), 5, max), max);
+        assert_eq!(exponential_backoff(Duration::from_secs(30), 100, max), max);
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("meow".into(), 4), "meow");
+        assert_eq!(truncate_chars("meow meow".into(), 4), "meo…");
+        assert_eq!(truncate_chars("喵喵喵喵喵".into(), 3), "喵喵…");
+    }
+
+    #[test]
+    fn test_truncate_bytes() {
+        assert_eq!(truncate_bytes("meow".into(), 4), "meow");
+        assert_eq!(truncate_bytes("喵喵喵".into(), 7), "喵…");
+    }
 }
@@ -6,13 +6,17 @@ mod platform;
 pub mod prop;
 mod reporter;
 mod source;
+mod state;
 mod task;
 
+use std::{sync::Arc, time::Duration};
+
 use anyhow::anyhow;
 use once_cell::sync::OnceCell;
-use task::{Task, TaskReporter, TaskSubscription};
+use spdlog::prelude::*;
+use task::{Subscriber, Task, TaskReporter, TaskSubscription};
 
-use crate::config::Config;
+use crate::{config::Config, reporter::ReporterParams};
 
 static CLI_ARGS: OnceCell<cli::Args> = OnceCell::new();
 
@@ -32,20 +36,173 @@ pub async fn run(args: cli::Args) -> anyhow::Result<()> {
     )
     .await?;
 
-    let subscription_tasks = config.subscriptions().map(|(name, subscription)| {
-        Box::new(TaskSubscription::new(
-            name,
-            subscription.interval.unwrap_or(config.interval),
-            subscription.notify,
-            subscription.platform,
-        )) as Box<dyn Task>
-    });
-    let reporter_task = config
-        .reporter()
-        .map(|params| Box::new(TaskReporter::new(params)) as Box<dyn Task>);
-    let tasks = reporter_task.into_iter().chain(subscription_tasks);
+    state::init(config.state_dir.clone())
+        .map_err(|err| anyhow!("failed to initialize state store: {err}"))?;
 
-    task::run_tasks(tasks).await?.join_all().await;
+    let mut runner = task::Runner::new();
+    runner
+        .reconcile(
+            task_keys(&config),
+            |key| make_task(key, cli_args().replay_dead_letters),
+            shutdown_timeout(),
+        )
+        .await;
+    info!("{} tasks are running", runner.len());
+
+    let mut config_changed = config::watch(cli_args().config.clone());
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+    loop {
+        tokio::select! {
+            Some(()) = config_changed.recv() => match reload().await {
+                Ok(new) => {
+                    let stats = runner
+                        .reconcile(task_keys(&new), |key| make_task(key, false), shutdown_timeout())
+                        .await;
+                    info!(
+                        "configuration reloaded, {} tasks stopped, {} started, {} kept",
+                        stats.stopped, stats.started, stats.kept
+                    );
+                }
+                Err(err) => error!("failed to reload configuration, keep using the old one: {err}"),
+            },
+            result = &mut shutdown => {
+                result?;
+                break;
+            }
+        }
+    }
+
+    let timeout = shutdown_timeout();
+    info!("shutting down, waiting for tasks to finish in {timeout:?}");
+    runner.shutdown(timeout).await;
+    info!("all tasks stopped");
 
     Ok(())
 }
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+// Also applies to the tasks stopped on reloading
+fn shutdown_timeout() -> Duration {
+    Config::global()
+        .shutdown_timeout
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+}
+
+async fn shutdown_signal() -> anyhow::Result<()> {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .map_err(|err| anyhow!("failed to listen Ctrl-C: {err}"))
+    };
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())
+            .map_err(|err| anyhow!("failed to listen SIGTERM: {err}"))?;
+        tokio::select! {
+            result = ctrl_c => result.inspect(|_| info!("SIGINT received")),
+            _ = sigterm.recv() => {
+                info!("SIGTERM received");
+                Ok(())
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    ctrl_c.await.inspect(|_| info!("Ctrl-C received"))
+}
+
+async fn reload() -> anyhow::Result<Arc<Config>> {
+    let input = tokio::fs::read_to_string(&cli_args().config)
+        .await
+        .map_err(|err| anyhow!("failed to read config file: {err}"))?;
+    let (_old, new) = Config::reload(input)?;
+    Ok(new)
+}
+
+// Identifies a task by everything it depends on, a task is restarted on
+// reloading only if any of them changed
+#[derive(PartialEq)]
+enum TaskKey {
+    Reporter(ReporterParams),
+    Subscription {
+        interval: Duration,
+        max_backoff: Duration,
+        subscribers: Vec<Subscriber>,
+        platform: config::Accessor<source::platform::Config>,
+        // The global platform sections read by the source and notifiers
+        platform_global: Box<config::PlatformGlobal>,
+    },
+}
+
+fn task_keys(config: &Config) -> Vec<TaskKey> {
+    // Sorted so that the order of subscribers is stable across reloads
+    let mut subscriptions = config.subscriptions().collect::<Vec<_>>();
+    subscriptions.sort_by_key(|(name, _)| name.clone());
+
+    // Subscriptions to the same source are grouped into one task, which fetches at
+    // the shortest interval of them
+    let mut groups: Vec<(
+        &config::Accessor<source::platform::Config>,
+        Duration,
+        Vec<Subscriber>,
+    )> = vec![];
+    for (name, subscription) in subscriptions {
+        let interval = subscription.interval.unwrap_or(config.interval);
+        let subscriber = Subscriber {
+            name,
+            notify: subscription.notify,
+        };
+        match groups
+            .iter_mut()
+            .find(|(platform, _, _)| *platform == subscription.platform)
+        {
+            Some((_, shortest, subscribers)) => {
+                *shortest = (*shortest).min(interval);
+                subscribers.push(subscriber);
+            }
+            None => groups.push((subscription.platform, interval, vec![subscriber])),
+        }
+    }
+
+    let subscriptions =
+        groups
+            .into_iter()
+            .map(|(platform, interval, subscribers)| TaskKey::Subscription {
+                interval,
+                max_backoff: config.max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF),
+                platform_global: Box::new(config.platform().used_by(
+                    platform,
+                    subscribers.iter().flat_map(|subscriber| &subscriber.notify),
+                )),
+                subscribers,
+                platform: platform.clone(),
+            });
+    config
+        .reporter()
+        .map(TaskKey::Reporter)
+        .into_iter()
+        .chain(subscriptions)
+        .collect()
+}
+
+fn make_task(key: &TaskKey, replay_dead_letters: bool) -> Box<dyn Task> {
+    match key {
+        TaskKey::Reporter(params) => Box::new(TaskReporter::new(params.clone())),
+        TaskKey::Subscription {
+            interval,
+            max_backoff,
+            subscribers,
+            platform,
+            platform_global: _,
+        } => Box::new(TaskSubscription::new(
+            *interval,
+            *max_backoff,
+            subscribers.clone(),
+            platform,
+            replay_dead_letters,
+        )),
+    }
+}
@@ -29,10 +29,12 @@ async fn main() {
 
     if let Err(err) = run(args).await {
         error!("exit with error: {err}");
+        spdlog::default_logger().flush();
         exit(1);
     }
 
     info!("exit normally");
+    spdlog::default_logger().flush();
 }
 
 fn setup_logger(verbose: bool, log_dir: Option<&Path>) -> anyhow::Result<()> {
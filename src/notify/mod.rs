@@ -1,7 +1,12 @@
+mod outbox;
 pub mod platform;
+mod push;
+mod robot;
 
 use std::{future::Future, pin::Pin};
 
+pub use outbox::Outbox;
+use serde_json as json;
 use spdlog::prelude::*;
 
 use crate::{config, platform::PlatformTrait, source::Notification};
@@ -11,12 +16,42 @@ pub trait NotifierTrait: PlatformTrait {
         &'a self,
         notification: &'a Notification<'_>,
     ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+    // Sends out notifications held back by the notifier (e.g. digests). Only the
+    // due ones are sent unless `force`, which is used when stopping.
+    fn flush(&self, _force: bool) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    // Notifications held back by the notifier, persisted by the outbox so that they
+    // survive restarts. `None` if nothing is held.
+    fn held(&self) -> Pin<Box<dyn Future<Output = Option<json::Value>> + Send + '_>> {
+        Box::pin(async { None })
+    }
+
+    // Restores what `held` returned before the restart
+    fn restore_held(
+        &self,
+        _held: json::Value,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
 }
 
 pub fn notifier(params: config::Accessor<platform::Config>) -> Box<dyn NotifierTrait> {
     match params.into_inner() {
+        platform::Config::DingTalk(p) => Box::new(platform::dingtalk::Notifier::new(p)),
+        platform::Config::Discord(p) => Box::new(platform::discord::Notifier::new(p)),
+        platform::Config::Email(p) => Box::new(platform::email::Notifier::new(p)),
+        platform::Config::Feishu(p) => Box::new(platform::feishu::Notifier::new(p)),
+        platform::Config::Gotify(p) => Box::new(platform::gotify::Notifier::new(p)),
+        platform::Config::Matrix(p) => Box::new(platform::matrix::Notifier::new(p)),
+        platform::Config::Ntfy(p) => Box::new(platform::ntfy::Notifier::new(p)),
         platform::Config::Qq(p) => Box::new(platform::qq::Notifier::new(p)),
+        platform::Config::Slack(p) => Box::new(platform::slack::Notifier::new(p)),
         platform::Config::Telegram(p) => Box::new(platform::telegram::Notifier::new(p)),
+        platform::Config::Webhook(p) => Box::new(platform::webhook::Notifier::new(p)),
+        platform::Config::WeCom(p) => Box::new(platform::wecom::Notifier::new(p)),
     }
 }
 
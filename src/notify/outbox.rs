@@ -0,0 +1,262 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use spdlog::prelude::*;
+
+use super::NotifierTrait;
+use crate::{
+    config, helper,
+    source::{Notification, NotificationKind, NotificationOwned, PostsRef},
+    state,
+};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+const MAX_ATTEMPTS: u32 = 10;
+
+// Sits between a subscription and a notifier. Failed deliveries are kept (and
+// persisted if the state store is enabled) and retried with exponential
+// backoff, then moved to the dead letters if they keep failing.
+pub struct Outbox {
+    key: String,
+    notifier: Box<dyn NotifierTrait>,
+    state: OutboxState,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxState {
+    pending: Vec<Delivery>,
+    dead_letters: Vec<Delivery>,
+    // Delivered to the notifier but held back by it, see `NotifierTrait::held`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    held: Option<json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Delivery {
+    notification: NotificationOwned,
+    attempts: u32,
+    next_attempt: SystemTime,
+    last_error: String,
+}
+
+impl Outbox {
+    pub async fn new(
+        subscription: impl fmt::Display,
+        notify: config::NotifyResolved,
+        replay_dead_letters: bool,
+    ) -> Self {
+        let key = format!("outbox.{subscription}.{}", notify.key);
+        let mut state = load_state(&key).await;
+
+        if !state.dead_letters.is_empty() {
+            if replay_dead_letters {
+                info!(
+                    "replaying {} dead letters of '{key}'",
+                    state.dead_letters.len()
+                );
+                let now = SystemTime::now();
+                state
+                    .pending
+                    .extend(state.dead_letters.drain(..).map(|delivery| Delivery {
+                        attempts: 0,
+                        next_attempt: now,
+                        ..delivery
+                    }));
+                save_state(&key, &state).await;
+            } else {
+                warn!(
+                    "'{key}' has {} dead letters, run with '--replay-dead-letters' to retry them",
+                    state.dead_letters.len()
+                );
+            }
+        }
+
+        let notifier = super::notifier(notify.params);
+        if let Some(held) = state.held.clone() {
+            if let Err(err) = notifier.restore_held(held).await {
+                error!("failed to restore notifications held for '{key}': {err}");
+            }
+        }
+
+        Self {
+            key,
+            notifier,
+            state,
+        }
+    }
+
+    pub async fn deliver(&mut self, notification: &Notification<'_>) {
+        // Posts are delivered one by one, so that retrying a failed one doesn't re-send
+        // the others
+        if let NotificationKind::Posts(posts) = &notification.kind {
+            if posts.0.len() > 1 {
+                for &post in &posts.0 {
+                    let notification = Notification {
+                        kind: NotificationKind::Posts(PostsRef(vec![post])),
+                        source: notification.source,
+                    };
+                    self.deliver_one(&notification).await;
+                }
+                return;
+            }
+        }
+        self.deliver_one(notification).await
+    }
+
+    async fn deliver_one(&mut self, notification: &Notification<'_>) {
+        info!("notifying notification '{notification}'");
+        let Err(err) = self.notifier.notify(notification).await else {
+            self.save_held().await;
+            return;
+        };
+        error!(
+            "failed to notify to {}: {err}",
+            self.notifier.metadata().display_name
+        );
+
+        match NotificationOwned::new(notification) {
+            Ok(notification) => {
+                let delivery = Delivery {
+                    notification,
+                    attempts: 1,
+                    next_attempt: SystemTime::now() + backoff(1),
+                    last_error: err.to_string(),
+                };
+                self.state.pending.push(delivery);
+                save_state(&self.key, &self.state).await;
+            }
+            Err(err) => error!("notification '{notification}' cannot be retried: {err}"),
+        }
+    }
+
+    // Retries pending deliveries that are due, and flushes what the notifier held
+    // back if it is due
+    pub async fn retry_due(&mut self) {
+        self.flush(false).await;
+
+        if self.state.pending.is_empty() {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let mut changed = false;
+        let mut pending = Vec::with_capacity(self.state.pending.len());
+
+        for mut delivery in std::mem::take(&mut self.state.pending) {
+            if delivery.next_attempt > now {
+                pending.push(delivery);
+                continue;
+            }
+            changed = true;
+
+            let result = match delivery.notification.to_notification().await {
+                Ok(notification) => self.notifier.notify(&notification).await,
+                Err(err) => Err(err),
+            };
+            let Err(err) = result else {
+                info!(
+                    "retried notification '{}' to '{}' successfully",
+                    delivery.notification, self.key
+                );
+                continue;
+            };
+
+            delivery.attempts += 1;
+            delivery.last_error = err.to_string();
+            if delivery.attempts >= MAX_ATTEMPTS {
+                error!(
+                    "failed to notify '{}' to '{}' after {} attempts, moved to dead letters: {err}",
+                    delivery.notification, self.key, delivery.attempts
+                );
+                self.state.dead_letters.push(delivery);
+            } else {
+                let delay = backoff(delivery.attempts);
+                warn!(
+                    "failed to notify '{}' to '{}' (attempt {}), retry in {delay:?}: {err}",
+                    delivery.notification, self.key, delivery.attempts
+                );
+                delivery.next_attempt = now + delay;
+                pending.push(delivery);
+            }
+        }
+
+        self.state.pending = pending;
+        if changed {
+            save_state(&self.key, &self.state).await;
+        }
+    }
+
+    // Called when the subscription is stopping, so nothing held back by the
+    // notifier is lost
+    pub async fn close(&mut self) {
+        self.flush(true).await;
+    }
+
+    async fn flush(&mut self, force: bool) {
+        if let Err(err) = self.notifier.flush(force).await {
+            error!(
+                "failed to flush notifications held by {} for '{}': {err}",
+                self.notifier.metadata().display_name,
+                self.key
+            );
+        }
+        self.save_held().await;
+    }
+
+    async fn save_held(&mut self) {
+        let held = self.notifier.held().await;
+        if held != self.state.held {
+            self.state.held = held;
+            save_state(&self.key, &self.state).await;
+        }
+    }
+}
+
+fn backoff(attempts: u32) -> Duration {
+    helper::exponential_backoff(
+        RETRY_BASE_DELAY,
+        attempts.saturating_sub(1),
+        RETRY_MAX_DELAY,
+    )
+}
+
+async fn load_state(key: &str) -> OutboxState {
+    let Some(store) = state::store() else {
+        return OutboxState::default();
+    };
+    store
+        .load(key)
+        .await
+        .inspect_err(|err| error!("failed to load outbox '{key}': {err}"))
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+async fn save_state(key: &str, state: &OutboxState) {
+    let Some(store) = state::store() else {
+        return;
+    };
+    if let Err(err) = store.save(key, state).await {
+        error!("failed to save outbox '{key}': {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delay() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(3), Duration::from_secs(120));
+        assert_eq!(backoff(8), Duration::from_secs(60 * 60));
+        assert_eq!(backoff(100), Duration::from_secs(60 * 60));
+    }
+}
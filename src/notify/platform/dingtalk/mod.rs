@@ -0,0 +1,263 @@
+use std::{borrow::Cow, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::{
+        robot::{self, RobotCard},
+        NotifierTrait,
+    },
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::Notification,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    // e.g. "https://oapi.dingtalk.com/robot/send?access_token=xxx"
+    #[serde(flatten)]
+    pub webhook: ConfigWebhook,
+    // Required if the robot is secured by signing
+    #[serde(flatten)]
+    pub secret: Option<robot::ConfigSecret>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        self.webhook.validate()?;
+        let url = self
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for DingTalk: {err}"))?;
+        ensure!(url.starts_with("https://"), "invalid webhook URL");
+        self.secret.validate()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The webhook URL is the secret
+        write!(f, "dingtalk:webhook")
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            webhook: new.webhook.unwrap_or(self.webhook),
+            secret: new.secret.or(self.secret),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+    #[serde(flatten)]
+    pub secret: Option<robot::ConfigSecret>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigWebhook {
+        WebhookUrl(String),
+    }
+}
+
+// The limit is 20000 bytes for the whole body
+const TEXT_LIMIT: usize = 18000;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "DingTalk",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn webhook_url(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for DingTalk: {err}"))
+    }
+
+    fn secret(&self) -> anyhow::Result<Option<Cow<'_, str>>> {
+        self.params
+            .secret
+            .as_ref()
+            .map(|secret| {
+                secret
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read secret for DingTalk: {err}"))
+            })
+            .transpose()
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let mut errors = vec![];
+        for card in robot::make_cards(&self.params.notifications, notification).await {
+            if let Err(err) = self.send(&card).await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn send(&self, card: &RobotCard) -> anyhow::Result<()> {
+        let mut url = reqwest::Url::parse(&self.webhook_url()?)
+            .map_err(|err| anyhow!("invalid webhook URL: {err}"))?;
+        if let Some(secret) = self.secret()? {
+            let timestamp = robot::unix_time().as_millis();
+            url.query_pairs_mut()
+                .append_pair("timestamp", &timestamp.to_string())
+                .append_pair("sign", &sign(&secret, timestamp));
+        }
+
+        let resp = robot::post(url, &make_body(card))
+            .await
+            .map_err(|err| anyhow!("failed to send message to DingTalk: {err}"))?;
+        let errcode = resp["errcode"].as_i64().unwrap_or_default();
+        if errcode != 0 {
+            bail!(
+                "response contains error, errcode: {errcode}, errmsg: '{}'",
+                resp["errmsg"].as_str().unwrap_or("*no errmsg*")
+            );
+        }
+        Ok(())
+    }
+}
+
+// https://open.dingtalk.com/document/robots/customize-robot-security-settings
+fn sign(secret: &str, timestamp_ms: u128) -> String {
+    robot::hmac_sha256_base64(
+        secret.as_bytes(),
+        format!("{timestamp_ms}\n{secret}").as_bytes(),
+    )
+}
+
+// https://open.dingtalk.com/document/robots/custom-robot-access
+fn make_body(card: &RobotCard) -> json::Value {
+    let mut text = format!("#### {}\n\n", card.title);
+    if let Some(cover) = &card.cover {
+        text.push_str(&format!("![cover]({})\n\n", cover.image_url));
+    }
+    // Single newlines are ignored by the markdown of DingTalk
+    text.push_str(&card.markdown.replace('\n', "\n\n"));
+    for url in &card.image_urls {
+        text.push_str(&format!("\n\n![image]({url})"));
+    }
+    let text = helper::truncate_bytes(text, TEXT_LIMIT);
+
+    if card.buttons.is_empty() {
+        json!({
+            "msgtype": "markdown",
+            "markdown": { "title": card.title, "text": text },
+        })
+    } else {
+        json!({
+            "msgtype": "actionCard",
+            "actionCard": {
+                "title": card.title,
+                "text": text,
+                // Vertical
+                "btnOrientation": "0",
+                "btns": card.buttons.iter().map(|button| json!({
+                    "title": button.text,
+                    "actionURL": button.url,
+                })).collect::<Vec<_>>(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::robot::RobotButton;
+
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign("SECmeow", 1700000000000),
+            "/oQ9WhQgqE0UdtsBaJ1S9JHvIE8dvcEpk6J+jjlRFQ8="
+        );
+    }
+
+    #[test]
+    fn body() {
+        let mut card = RobotCard {
+            title: "[test] user".into(),
+            markdown: "meow\n> quote".into(),
+            cover: None,
+            image_urls: vec!["https://example.com/0.jpg".into()],
+            buttons: vec![],
+        };
+        assert_eq!(
+            make_body(&card),
+            json!({
+                "msgtype": "markdown",
+                "markdown": {
+                    "title": "[test] user",
+                    "text": "#### [test] user\n\nmeow\n\n> quote\n\n![image](https://example.com/0.jpg)",
+                },
+            })
+        );
+
+        card.buttons.push(RobotButton {
+            text: "View".into(),
+            url: "https://example.com/post".into(),
+        });
+        let body = make_body(&card);
+        assert_eq!(body["msgtype"], "actionCard");
+        assert_eq!(
+            body["actionCard"]["btns"],
+            json!([{ "title": "View", "actionURL": "https://example.com/post" }])
+        );
+    }
+}
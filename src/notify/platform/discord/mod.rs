@@ -0,0 +1,600 @@
+mod request;
+
+use std::{borrow::Cow, fmt, future::Future, pin::Pin, time::SystemTime};
+
+use anyhow::{anyhow, ensure};
+use chrono::{DateTime, Utc};
+use request::*;
+use serde::Deserialize;
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::NotifierTrait,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        DocumentRef, FileRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        PlaybackFormat, PlaybackRef, Post, PostAttachment, PostContent, PostContentPart, PostUrl,
+        PostsRef, RepostFrom, StatusSource,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    #[serde(flatten)]
+    pub webhook: ConfigWebhook,
+    pub thread_id: Option<u64>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        self.webhook.validate()?;
+        let url = self
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for Discord: {err}"))?;
+        ensure!(
+            url.starts_with("https://") && webhook_id(&url).is_some(),
+            "invalid webhook URL"
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the ID, the rest of the URL is the secret token
+        let url = self.webhook.as_secret_ref().get_str().ok();
+        write!(
+            f,
+            "discord:{}",
+            url.as_deref().and_then(webhook_id).unwrap_or("*")
+        )?;
+        if let Some(thread_id) = self.thread_id {
+            write!(f, ":({thread_id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            webhook: new.webhook.unwrap_or(self.webhook),
+            thread_id: new.thread_id.or(self.thread_id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+    pub thread_id: Option<u64>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigWebhook {
+        WebhookUrl(String),
+    }
+}
+
+// https://discord.com/api/webhooks/{id}/{token}
+fn webhook_id(url: &str) -> Option<&str> {
+    let mut segments = url.split('/').skip_while(|segment| *segment != "webhooks");
+    segments.next()?;
+    let id = segments.next()?;
+    segments.next()?;
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then_some(id)
+}
+
+// Files larger than this are rejected by Discord, unless the server is boosted
+const FILE_SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+
+const COLOR_ONLINE: u32 = 0x57f287;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Discord",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn webhook_url(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for Discord: {err}"))
+    }
+
+    async fn execute(&self, message: Message, file: Option<File<'_>>) -> anyhow::Result<()> {
+        let url = self.webhook_url()?;
+        Webhook::new(&url, self.params.thread_id)
+            .execute(&message, file)
+            .await
+            .map_err(|err| anyhow!("failed to execute Discord webhook: {err}"))
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        match &notification.kind {
+            NotificationKind::LiveOnline(live_status) => {
+                self.notify_live(live_status, notification.source).await
+            }
+            NotificationKind::LiveTitle(live_status, _old_title) => {
+                self.notify_live_title(live_status, notification.source)
+                    .await
+            }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.notify_live_area(live_status, old_area, notification.source)
+                    .await
+            }
+            NotificationKind::Posts(posts) => self.notify_posts(posts, notification.source).await,
+            NotificationKind::PostEdited(post, _old) => {
+                if !self.params.notifications.post_edited {
+                    info!("post_edited notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post(post, notification.source, "✏️").await
+            }
+            NotificationKind::PostDeleted(post) => {
+                if !self.params.notifications.post_deleted {
+                    info!("post_deleted notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post(post, notification.source, "🗑️").await
+            }
+            NotificationKind::Log(message) => self.notify_log(message).await,
+            NotificationKind::Playback(playback) => {
+                self.notify_playback(playback, notification.source).await
+            }
+            NotificationKind::Document(document) => {
+                self.notify_document(document, notification.source).await
+            }
+        }
+    }
+
+    async fn notify_live(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_online {
+            info!("live_online notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let LiveStatusKind::Online { start_time } = live_status.kind else {
+            return Ok(());
+        };
+
+        let embed = Embed {
+            title: Some(helper::truncate_chars(
+                format!("🟢 {}", live_status.title),
+                EMBED_TITLE_LIMIT,
+            )),
+            description: live_status.area.as_ref().map(|area| area.to_string()),
+            url: Some(live_status.live_url.clone()),
+            timestamp: Some(
+                DateTime::<Utc>::from(start_time.unwrap_or_else(SystemTime::now)).to_rfc3339(),
+            ),
+            color: Some(COLOR_ONLINE),
+            image: Some(EmbedImage {
+                url: live_status.cover_image_url.clone(),
+            }),
+            ..self.live_embed_base(live_status, source)
+        };
+
+        self.execute(
+            Message {
+                embeds: vec![embed],
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+    }
+
+    async fn notify_live_title(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_title {
+            info!("live_title notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let embed = Embed {
+            title: Some(helper::truncate_chars(
+                format!("✏️ {}", live_status.title),
+                EMBED_TITLE_LIMIT,
+            )),
+            url: Some(live_status.live_url.clone()),
+            ..self.live_embed_base(live_status, source)
+        };
+        self.execute(
+            Message {
+                embeds: vec![embed],
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+    }
+
+    async fn notify_live_area(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let Some(area) = &live_status.area else {
+            return Ok(());
+        };
+
+        let embed = Embed {
+            title: Some(helper::truncate_chars(
+                format!("🏷️ {old_area} ➡️ {area}"),
+                EMBED_TITLE_LIMIT,
+            )),
+            url: Some(live_status.live_url.clone()),
+            ..self.live_embed_base(live_status, source)
+        };
+        self.execute(
+            Message {
+                embeds: vec![embed],
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+    }
+
+    fn live_embed_base(&self, live_status: &LiveStatus, source: &StatusSource) -> Embed {
+        Embed {
+            author: self.params.notifications.author_name.then(|| EmbedAuthor {
+                name: live_status.streamer_name.clone(),
+                url: source.user.as_ref().map(|user| user.profile_url.clone()),
+                icon_url: None,
+            }),
+            footer: Some(EmbedFooter {
+                text: source.platform.display_name.into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn notify_posts(
+        &self,
+        posts: &PostsRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.post {
+            info!("post notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let mut errors = vec![];
+        for post in &posts.0 {
+            if let Err(err) = self.notify_post(post, source, "").await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn notify_post(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let message = Message {
+            content: (!annotation.is_empty()).then(|| annotation.into()),
+            embeds: make_post_embeds(self.params.notifications.author_name, post, source),
+            ..Default::default()
+        };
+        self.execute(message, None).await
+    }
+
+    async fn notify_log(&self, message: &str) -> anyhow::Result<()> {
+        if !self.params.notifications.log {
+            info!("log notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.execute(Message::content(message), None).await
+    }
+
+    async fn notify_playback(
+        &self,
+        playback: &PlaybackRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.playback {
+            info!("playback notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let playback = playback.get(PlaybackFormat::Mp4).await?;
+        self.notify_file(&playback.file, source, "🎥").await
+    }
+
+    async fn notify_document(
+        &self,
+        document: &DocumentRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.document {
+            info!("document notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.notify_file(&document.file, source, "📊").await
+    }
+
+    async fn notify_file(
+        &self,
+        file: &FileRef<'_>,
+        source: &StatusSource,
+        emoji: &str,
+    ) -> anyhow::Result<()> {
+        let text = format!(
+            "[{}] {emoji} {} ({})",
+            source.platform.display_name,
+            file.name,
+            humansize::format_size(file.size, humansize::BINARY)
+        );
+
+        // Retrying wouldn't help, so let the users know instead of failing
+        if file.size > FILE_SIZE_LIMIT {
+            warn!("file {file} exceeds the size limit of Discord, skip uploading");
+            return self
+                .execute(
+                    Message::content(format!("{text}, too large to upload to Discord")),
+                    None,
+                )
+                .await;
+        }
+
+        trace!("uploading file to Discord '{file}'");
+        self.execute(
+            Message::content(text),
+            Some(File {
+                name: &file.name,
+                data: file.data.clone(),
+            }),
+        )
+        .await
+    }
+}
+
+fn make_post_embeds(author_name: bool, post: &Post, source: &StatusSource) -> Vec<Embed> {
+    let urls = post.urls_recursive();
+    let major_url = match urls.major() {
+        PostUrl::Clickable(url) => Some(url.url.clone()),
+        PostUrl::Identity(_) => None,
+    };
+
+    let mut description = String::new();
+    match &post.repost_from {
+        Some(RepostFrom::Recursion(repost_from)) => {
+            if !post.content.is_empty() {
+                description.push_str("💬 ");
+                push_content(&mut description, &post.content);
+                description.push_str("\n\n");
+            }
+            // Quotes all the following lines
+            description.push_str(">>> 🔁 ");
+            match repost_from.urls_recursive().major() {
+                PostUrl::Clickable(url) => description.push_str(&format!(
+                    "[{}]({})",
+                    escape_markdown(&repost_from.user.nickname),
+                    url.url
+                )),
+                PostUrl::Identity(_) => {
+                    description.push_str(&escape_markdown(&repost_from.user.nickname))
+                }
+            }
+            description.push_str(": ");
+            push_content(&mut description, &repost_from.content);
+        }
+        None => push_content(&mut description, &post.content),
+    }
+
+    let mut videos = post
+        .attachments_recursive(true)
+        .into_iter()
+        .filter_map(|attachment| match attachment {
+            PostAttachment::Video(video) => Some(video.media_url.as_str()),
+            PostAttachment::Image(_) => None,
+        })
+        .peekable();
+    if videos.peek().is_some() {
+        description.push_str("\n\n");
+        description.push_str(
+            &videos
+                .enumerate()
+                .map(|(i, url)| format!("[🎬 {}]({url})", i + 1))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+
+    // Jump links, webhooks are not allowed to send buttons
+    let links = urls
+        .iter()
+        .filter_map(|url| url.as_clickable())
+        .map(|url| format!("[{}]({})", escape_markdown(&url.display), url.url))
+        .collect::<Vec<_>>();
+    if !links.is_empty() {
+        description.push_str("\n\n");
+        description.push_str(&links.join(" | "));
+    }
+
+    let mut images = post
+        .attachments_recursive(true)
+        .into_iter()
+        .filter_map(|attachment| match attachment {
+            PostAttachment::Image(image) => Some(image.media_url.clone()),
+            PostAttachment::Video(_) => None,
+        })
+        .take(GALLERY_LIMIT);
+
+    let mut embeds = vec![Embed {
+        description: (!description.is_empty())
+            .then(|| helper::truncate_chars(description, EMBED_DESCRIPTION_LIMIT)),
+        url: major_url.clone(),
+        timestamp: Some(post.time.to_rfc3339()),
+        author: author_name.then(|| EmbedAuthor {
+            name: post.user.nickname.clone(),
+            url: Some(post.user.profile_url.clone()),
+            icon_url: post.user.avatar_url.clone(),
+        }),
+        image: images.next().map(|url| EmbedImage { url }),
+        footer: Some(EmbedFooter {
+            text: source.platform.display_name.into(),
+        }),
+        ..Default::default()
+    }];
+    // Displayed in the same gallery only if the embeds have the same URL
+    if major_url.is_some() {
+        embeds.extend(images.map(|url| Embed {
+            url: major_url.clone(),
+            image: Some(EmbedImage { url }),
+            ..Default::default()
+        }));
+    }
+    embeds
+}
+
+fn push_content(markdown: &mut String, content: &PostContent) {
+    content.parts().for_each(|part| match part {
+        PostContentPart::Plain(text) => markdown.push_str(&escape_markdown(text)),
+        PostContentPart::Link { display, url } => {
+            markdown.push_str(&format!("[{}]({url})", escape_markdown(display)))
+        }
+        PostContentPart::InlineAttachment(_) => {
+            // Ignore, we handle it in post.attachments
+        }
+    });
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::PostAttachmentImage;
+
+    #[test]
+    fn webhook_ids() {
+        assert_eq!(
+            webhook_id("https://discord.com/api/webhooks/1234567890/abc-DEF_123"),
+            Some("1234567890")
+        );
+        assert_eq!(
+            webhook_id("https://discord.com/api/v10/webhooks/1234567890/abc"),
+            Some("1234567890")
+        );
+        assert_eq!(
+            webhook_id("https://discord.com/api/webhooks/1234567890"),
+            None
+        );
+        assert_eq!(webhook_id("https://discord.com/api/webhooks/abc/def"), None);
+        assert_eq!(webhook_id("https://example.com/"), None);
+    }
+
+    #[test]
+    fn post_embeds() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = Post::mock(
+            PostContent::plain("*meow* ").with_link("link", "https://example.com/link"),
+            (0..5)
+                .map(|i| {
+                    PostAttachment::Image(PostAttachmentImage {
+                        media_url: format!("https://example.com/{i}.jpg"),
+                        has_spoiler: false,
+                    })
+                })
+                .collect(),
+        );
+
+        let embeds = make_post_embeds(true, &post, &source);
+        assert_eq!(embeds.len(), GALLERY_LIMIT);
+        assert_eq!(
+            embeds[0].description.as_deref(),
+            Some("\\*meow\\* [link](https://example.com/link)\n\n[View](https://example.com/post)")
+        );
+        assert_eq!(embeds[0].author.as_ref().unwrap().name, "user");
+        assert_eq!(
+            embeds[0].image.as_ref().unwrap().url,
+            "https://example.com/0.jpg"
+        );
+        assert!(embeds
+            .iter()
+            .all(|embed| embed.url.as_deref() == Some("https://example.com/post")));
+
+        let embeds = make_post_embeds(false, &post, &source);
+        assert!(embeds[0].author.is_none());
+    }
+}
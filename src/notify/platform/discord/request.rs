@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use reqwest::{
+    multipart::{Form, Part},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use spdlog::prelude::*;
+
+use crate::helper;
+
+// Limits of the message payload
+// https://discord.com/developers/docs/resources/message#embed-object-embed-limits
+pub const CONTENT_LIMIT: usize = 2000;
+pub const EMBED_TITLE_LIMIT: usize = 256;
+pub const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+// Embeds sharing the same URL are displayed as a gallery of up to 4 images
+pub const GALLERY_LIMIT: usize = 4;
+
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+pub struct Webhook<'a> {
+    url: &'a str,
+    thread_id: Option<u64>,
+}
+
+impl<'a> Webhook<'a> {
+    pub fn new(url: &'a str, thread_id: Option<u64>) -> Self {
+        Self { url, thread_id }
+    }
+
+    pub async fn execute(&self, message: &Message, file: Option<File<'_>>) -> anyhow::Result<()> {
+        let payload = json::to_string(message)
+            .map_err(|err| anyhow!("failed to serialize message: {err}"))?;
+
+        let mut retries = 0;
+        loop {
+            let mut request = helper::reqwest_client()?
+                .post(self.url)
+                .query(&[("wait", "true")]);
+            if let Some(thread_id) = self.thread_id {
+                request = request.query(&[("thread_id", thread_id)]);
+            }
+            // The form cannot be reused, so build it for each attempt
+            request = match &file {
+                Some(file) => {
+                    request.multipart(Form::new().text("payload_json", payload.clone()).part(
+                        "files[0]",
+                        Part::stream(file.data.clone()).file_name(file.name.to_owned()),
+                    ))
+                }
+                None => request
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(payload.clone()),
+            };
+
+            let resp = request
+                .send()
+                .await
+                .map_err(|err| anyhow!("failed to send request: {err}"))?;
+            let status = resp.status();
+            if status.is_success() {
+                return Ok(());
+            }
+
+            let text = resp
+                .text()
+                .await
+                .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+            if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RATE_LIMITED_RETRIES {
+                let retry_after = json::from_str::<RateLimited>(&text)
+                    .map(|resp| Duration::from_secs_f64(resp.retry_after))
+                    .unwrap_or(Duration::from_secs(1));
+                warn!("rate limited by Discord, retry after {retry_after:?}");
+                tokio::time::sleep(retry_after).await;
+                retries += 1;
+                continue;
+            }
+            bail!("response status is not success: {status}, response '{text}'");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+}
+
+pub struct File<'a> {
+    pub name: &'a str,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Message {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<Embed>,
+    pub allowed_mentions: AllowedMentions,
+}
+
+impl Message {
+    pub fn content(content: impl Into<String>) -> Self {
+        Self {
+            content: Some(helper::truncate_chars(content.into(), CONTENT_LIMIT)),
+            ..Default::default()
+        }
+    }
+}
+
+// Never ping anyone with the content from sources
+#[derive(Debug, Default, Serialize)]
+pub struct AllowedMentions {
+    parse: [&'static str; 0],
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Embed {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>, // ISO 8601
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<EmbedAuthor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<EmbedImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<EmbedFooter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbedAuthor {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbedImage {
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbedFooter {
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_message() {
+        let message = Message {
+            embeds: vec![Embed {
+                title: Some("title".into()),
+                image: Some(EmbedImage {
+                    url: "https://example.com/image.jpg".into(),
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            json::to_value(&message).unwrap(),
+            json::json!({
+                "embeds": [{
+                    "title": "title",
+                    "image": { "url": "https://example.com/image.jpg" }
+                }],
+                "allowed_mentions": { "parse": [] }
+            })
+        );
+    }
+}
@@ -0,0 +1,535 @@
+mod render;
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, ensure};
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use render::{Entry, Mail};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use spdlog::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{self, AsSecretRef},
+    notify::NotifierTrait,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        DocumentRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        PlaybackFormat, PostsRef, StatusSource,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: ConfigTls,
+    pub username: Option<String>,
+    #[serde(flatten)]
+    pub password: Option<ConfigPassword>,
+    pub from: String,
+    pub to: Vec<String>,
+    // Group the notifications within the window into one mail
+    #[serde(default, with = "humantime_serde")]
+    pub digest: Option<Duration>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.host.is_empty(), "SMTP host is empty");
+        ensure!(
+            self.username.is_some() == self.password.is_some(),
+            "username and password must be specified together"
+        );
+        self.password.validate()?;
+        self.from
+            .parse::<Mailbox>()
+            .map_err(|err| anyhow!("invalid sender address '{}': {err}", self.from))?;
+        ensure!(!self.to.is_empty(), "no recipient specified");
+        for to in &self.to {
+            to.parse::<Mailbox>()
+                .map_err(|err| anyhow!("invalid recipient address '{to}': {err}"))?;
+        }
+        if let Some(digest) = self.digest {
+            ensure!(!digest.is_zero(), "digest window must not be zero");
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "email:{}", self.to.join(","))
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            host: new.host.unwrap_or(self.host),
+            port: new.port.or(self.port),
+            tls: new.tls.unwrap_or(self.tls),
+            username: new.username.or(self.username),
+            password: new.password.or(self.password),
+            from: new.from.unwrap_or(self.from),
+            to: new.to.unwrap_or(self.to),
+            digest: new.digest.or(self.digest),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub tls: Option<ConfigTls>,
+    pub username: Option<String>,
+    #[serde(flatten)]
+    pub password: Option<ConfigPassword>,
+    pub from: Option<String>,
+    pub to: Option<Vec<String>>,
+    #[serde(default, with = "humantime_serde")]
+    pub digest: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigTls {
+    // Plaintext, port 25 by default
+    None,
+    // Port 587 by default
+    StartTls,
+    // Implicit TLS, port 465 by default
+    #[default]
+    Tls,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigPassword {
+        Password(String),
+    }
+}
+
+// Attachments larger than this are usually rejected by mail servers
+const ATTACHMENT_SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+    digest: Mutex<Digest>,
+}
+
+// Entries waiting for the digest window to close, persisted by the outbox
+#[derive(Default, Serialize, Deserialize)]
+struct Digest {
+    entries: Vec<Entry>,
+    deadline: Option<SystemTime>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Email",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+
+    fn flush(&self, force: bool) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(self.flush_digest(force))
+    }
+
+    fn held(&self) -> Pin<Box<dyn Future<Output = Option<json::Value>> + Send + '_>> {
+        Box::pin(async {
+            let digest = self.digest.lock().await;
+            if digest.entries.is_empty() {
+                return None;
+            }
+            json::to_value(&*digest)
+                .inspect_err(|err| {
+                    error!("failed to serialize digest for '{}': {err}", self.params)
+                })
+                .ok()
+        })
+    }
+
+    fn restore_held(
+        &self,
+        held: json::Value,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + '_>> {
+        Box::pin(async {
+            let held: Digest = json::from_value(held)
+                .map_err(|err| anyhow!("failed to deserialize digest: {err}"))?;
+            info!(
+                "restored a digest of {} entries for '{}'",
+                held.entries.len(),
+                self.params
+            );
+            *self.digest.lock().await = held;
+            Ok(())
+        })
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            digest: Mutex::new(Digest::default()),
+        }
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let source = notification.source;
+        let entry = match &notification.kind {
+            NotificationKind::LiveOnline(live_status) => self.make_live_entry(live_status, source),
+            NotificationKind::LiveTitle(live_status, old_title) => {
+                self.make_live_title_entry(live_status, old_title, source)
+            }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.make_live_area_entry(live_status, old_area, source)
+            }
+            NotificationKind::Posts(posts) => {
+                return self.notify_posts(posts, source).await;
+            }
+            NotificationKind::PostEdited(post, _old) => {
+                if !self.params.notifications.post_edited {
+                    info!("post_edited notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                Some(render::post(
+                    self.params.notifications.author_name,
+                    post,
+                    source,
+                    "✏️ ",
+                ))
+            }
+            NotificationKind::PostDeleted(post) => {
+                if !self.params.notifications.post_deleted {
+                    info!("post_deleted notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                Some(render::post(
+                    self.params.notifications.author_name,
+                    post,
+                    source,
+                    "🗑️ ",
+                ))
+            }
+            NotificationKind::Log(message) => {
+                if !self.params.notifications.log {
+                    info!("log notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                Some(render::log(message))
+            }
+            NotificationKind::Playback(playback) => {
+                if !self.params.notifications.playback {
+                    info!("playback notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                // Playbacks are far too large to be mailed, only let the users know
+                let playback = playback.get(PlaybackFormat::Mp4).await?;
+                Some(render::file(&playback.file, source, "🎥", false))
+            }
+            NotificationKind::Document(document) => self.make_document_entry(document, source),
+        };
+
+        match entry {
+            Some(entry) => self.send_or_digest(entry).await,
+            None => Ok(()),
+        }
+    }
+
+    fn make_live_entry(&self, live_status: &LiveStatus, source: &StatusSource) -> Option<Entry> {
+        if !self.params.notifications.live_online {
+            info!("live_online notification is disabled, skip notifying");
+            return None;
+        }
+        // Mails cannot be edited, so only the online is notified
+        matches!(live_status.kind, LiveStatusKind::Online { .. }).then(|| {
+            render::live_online(self.params.notifications.author_name, live_status, source)
+        })
+    }
+
+    fn make_live_title_entry(
+        &self,
+        live_status: &LiveStatus,
+        old_title: &str,
+        source: &StatusSource,
+    ) -> Option<Entry> {
+        if !self.params.notifications.live_title {
+            info!("live_title notification is disabled, skip notifying");
+            return None;
+        }
+        Some(render::live_title(
+            self.params.notifications.author_name,
+            live_status,
+            old_title,
+            source,
+        ))
+    }
+
+    fn make_live_area_entry(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> Option<Entry> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return None;
+        }
+        let area = live_status.area.as_ref()?;
+        Some(render::live_area(
+            self.params.notifications.author_name,
+            live_status,
+            area,
+            old_area,
+            source,
+        ))
+    }
+
+    fn make_document_entry(
+        &self,
+        document: &DocumentRef<'_>,
+        source: &StatusSource,
+    ) -> Option<Entry> {
+        if !self.params.notifications.document {
+            info!("document notification is disabled, skip notifying");
+            return None;
+        }
+        let attach = document.file.size <= ATTACHMENT_SIZE_LIMIT;
+        if !attach {
+            warn!(
+                "file {} exceeds the attachment size limit, skip attaching",
+                document.file
+            );
+        }
+        Some(render::file(&document.file, source, "📊", attach))
+    }
+
+    async fn notify_posts(
+        &self,
+        posts: &PostsRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.post {
+            info!("post notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let mut errors = vec![];
+        for post in &posts.0 {
+            if let Err(err) = self
+                .send_or_digest(render::post(
+                    self.params.notifications.author_name,
+                    post,
+                    source,
+                    "",
+                ))
+                .await
+            {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn send_or_digest(&self, entry: Entry) -> anyhow::Result<()> {
+        let Some(window) = self.params.digest else {
+            return send_mail(&self.params, Mail::single(entry)).await;
+        };
+
+        let mut digest = self.digest.lock().await;
+        if digest.deadline.is_none() {
+            info!(
+                "starting a digest window of {window:?} for '{}'",
+                self.params
+            );
+            digest.deadline = Some(SystemTime::now() + window);
+        }
+        digest.entries.push(entry);
+        Ok(())
+    }
+
+    async fn flush_digest(&self, force: bool) -> anyhow::Result<()> {
+        let mut digest = self.digest.lock().await;
+        let Some(deadline) = digest.deadline else {
+            return Ok(());
+        };
+        if !force && SystemTime::now() < deadline {
+            return Ok(());
+        }
+
+        info!(
+            "sending a digest of {} entries to '{}'",
+            digest.entries.len(),
+            self.params
+        );
+        if let Err(err) = send_mail(&self.params, Mail::digest(&digest.entries)).await {
+            // Keep the entries and try again with the next window
+            if let Some(window) = self.params.digest {
+                digest.deadline = Some(SystemTime::now() + window);
+            }
+            return Err(err);
+        }
+        *digest = Digest::default();
+        Ok(())
+    }
+}
+
+async fn send_mail(params: &ConfigParams, mail: Mail) -> anyhow::Result<()> {
+    let mut builder = Message::builder()
+        .from(
+            params
+                .from
+                .parse()
+                .map_err(|err| anyhow!("invalid sender address: {err}"))?,
+        )
+        .subject(mail.subject);
+    for to in &params.to {
+        builder = builder.to(to
+            .parse()
+            .map_err(|err| anyhow!("invalid recipient address: {err}"))?);
+    }
+
+    let body = MultiPart::alternative_plain_html(mail.plain, mail.html);
+    let message = if mail.attachments.is_empty() {
+        builder.multipart(body)
+    } else {
+        let mut mixed = MultiPart::mixed().multipart(body);
+        for attachment in mail.attachments {
+            mixed = mixed.singlepart(Attachment::new(attachment.name).body(
+                attachment.data.to_vec(),
+                ContentType::parse("application/octet-stream").unwrap(),
+            ));
+        }
+        builder.multipart(mixed)
+    }
+    .map_err(|err| anyhow!("failed to build mail: {err}"))?;
+
+    transport(params)?
+        .send(message)
+        .await
+        .map_err(|err| anyhow!("failed to send mail via SMTP: {err}"))?;
+    Ok(())
+}
+
+fn transport(params: &ConfigParams) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let mut builder = match params.tls {
+        ConfigTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&params.host),
+        ConfigTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&params.host)
+            .map_err(|err| anyhow!("failed to create SMTP transport: {err}"))?,
+        ConfigTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&params.host)
+            .map_err(|err| anyhow!("failed to create SMTP transport: {err}"))?,
+    };
+    if let Some(port) = params.port {
+        builder = builder.port(port);
+    }
+    if let (Some(username), Some(password)) = (&params.username, &params.password) {
+        let password = password
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read password for SMTP: {err}"))?;
+        builder = builder.credentials(Credentials::new(username.clone(), password.into_owned()));
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config() {
+        let params: ConfigParams = toml::from_str(
+            r#"
+host = "smtp.example.com"
+tls = "starttls"
+username = "bot"
+password = "meow"
+from = "closely <bot@example.com>"
+to = ["a@example.com", "b@example.com"]
+digest = "10min"
+            "#,
+        )
+        .unwrap();
+        config::Validator::validate(&params).unwrap();
+        assert_eq!(params.tls, ConfigTls::StartTls);
+        assert_eq!(params.digest, Some(Duration::from_secs(600)));
+        assert_eq!(params.to_string(), "email:a@example.com,b@example.com");
+
+        let params = ConfigParams {
+            password: None,
+            ..params
+        };
+        assert!(config::Validator::validate(&params).is_err());
+    }
+
+    #[tokio::test]
+    async fn held_digest() {
+        let params: ConfigParams = toml::from_str(
+            r#"
+host = "smtp.example.com"
+from = "bot@example.com"
+to = ["a@example.com"]
+digest = "10min"
+            "#,
+        )
+        .unwrap();
+        let notifier = Notifier::new(config::Accessor::new_then_validate(params.clone()).unwrap());
+        assert!(notifier.held().await.is_none());
+
+        let mut entry = render::log("meow");
+        entry.attachments.push(render::EntryAttachment {
+            name: "meow.txt".into(),
+            data: bytes::Bytes::from_static(b"meow"),
+        });
+        notifier.send_or_digest(entry).await.unwrap();
+        let held = notifier.held().await.unwrap();
+
+        let restored = Notifier::new(config::Accessor::new_then_validate(params).unwrap());
+        restored.restore_held(held.clone()).await.unwrap();
+        assert_eq!(restored.held().await, Some(held));
+        let digest = restored.digest.lock().await;
+        assert_eq!(digest.entries[0].attachments[0].data, "meow");
+        assert!(digest.deadline.is_some());
+    }
+}
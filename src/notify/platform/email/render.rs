@@ -0,0 +1,412 @@
+use std::borrow::Cow;
+
+use base64::prelude::*;
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    helper,
+    source::{
+        FileRef, LiveArea, LiveStatus, Post, PostAttachment, PostContent, PostContentPart, PostUrl,
+        RepostFrom, StatusSource,
+    },
+};
+
+// Length of the post content shown in the subject
+const SUBJECT_CONTENT_LIMIT: usize = 50;
+
+// A rendered notification, mailed alone or as a part of a digest
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub subject: String,
+    pub plain: String,
+    pub html: String,
+    pub attachments: Vec<EntryAttachment>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntryAttachment {
+    pub name: String,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub data: Bytes,
+}
+
+fn serialize_base64<S: Serializer>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64_STANDARD.encode(data))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    BASE64_STANDARD
+        .decode(encoded)
+        .map(Bytes::from)
+        .map_err(serde::de::Error::custom)
+}
+
+pub struct Mail {
+    pub subject: String,
+    pub plain: String,
+    pub html: String,
+    pub attachments: Vec<EntryAttachment>,
+}
+
+impl Mail {
+    pub fn single(entry: Entry) -> Self {
+        Self {
+            subject: entry.subject,
+            plain: entry.plain,
+            html: wrap_html(&entry.html),
+            attachments: entry.attachments,
+        }
+    }
+
+    pub fn digest(entries: &[Entry]) -> Self {
+        let subject = format!("[closely] {} notifications", entries.len());
+        let mut plain = vec![];
+        let mut html = vec![];
+        let mut attachments = vec![];
+        for entry in entries {
+            plain.push(format!("{}\n\n{}", entry.subject, entry.plain));
+            html.push(format!(
+                "<h3>{}</h3>\n{}",
+                helper::escape_html(&entry.subject),
+                entry.html
+            ));
+            attachments.extend(entry.attachments.iter().cloned());
+        }
+        Self {
+            subject,
+            plain: plain.join("\n\n---\n\n"),
+            html: wrap_html(&html.join("\n<hr>\n")),
+            attachments,
+        }
+    }
+}
+
+pub fn live_online(author_name: bool, live_status: &LiveStatus, source: &StatusSource) -> Entry {
+    let subject = format!(
+        "[{}] 🟢 {}{}",
+        source.platform.display_name,
+        author_prefix(author_name, live_status),
+        live_status.title
+    );
+
+    let mut plain = format!("{}\n{}", live_status.title, live_status.live_url);
+    let mut html = format!(
+        "<p>🟢 <a href=\"{}\">{}</a></p>\n",
+        helper::escape_html(&live_status.live_url),
+        helper::escape_html(&live_status.title)
+    );
+    if let Some(area) = &live_status.area {
+        plain.push_str(&format!("\n{area}"));
+        html.push_str(&format!(
+            "<p>{}</p>\n",
+            helper::escape_html(&area.to_string())
+        ));
+    }
+    html.push_str(&format!(
+        "<p><img src=\"{}\" style=\"max-width: 100%\"></p>",
+        helper::escape_html(&live_status.cover_image_url)
+    ));
+
+    Entry {
+        subject,
+        plain,
+        html,
+        attachments: vec![],
+    }
+}
+
+pub fn live_title(
+    author_name: bool,
+    live_status: &LiveStatus,
+    old_title: &str,
+    source: &StatusSource,
+) -> Entry {
+    Entry {
+        subject: format!(
+            "[{}] ✏️ {}{}",
+            source.platform.display_name,
+            author_prefix(author_name, live_status),
+            live_status.title
+        ),
+        plain: format!(
+            "{} ⬅️ {old_title}\n{}",
+            live_status.title, live_status.live_url
+        ),
+        html: format!(
+            "<p>✏️ <a href=\"{}\">{}</a> ⬅️ {}</p>",
+            helper::escape_html(&live_status.live_url),
+            helper::escape_html(&live_status.title),
+            helper::escape_html(old_title)
+        ),
+        attachments: vec![],
+    }
+}
+
+pub fn live_area(
+    author_name: bool,
+    live_status: &LiveStatus,
+    area: &LiveArea,
+    old_area: &LiveArea,
+    source: &StatusSource,
+) -> Entry {
+    let text = format!("{old_area} ➡️ {area}");
+    Entry {
+        subject: format!(
+            "[{}] 🏷️ {}{text}",
+            source.platform.display_name,
+            author_prefix(author_name, live_status)
+        ),
+        plain: format!("{text}\n{}", live_status.live_url),
+        html: format!(
+            "<p>🏷️ <a href=\"{}\">{}</a></p>",
+            helper::escape_html(&live_status.live_url),
+            helper::escape_html(&text)
+        ),
+        attachments: vec![],
+    }
+}
+
+pub fn post(author_name: bool, post: &Post, source: &StatusSource, annotation: &str) -> Entry {
+    let summary = match &post.repost_from {
+        Some(RepostFrom::Recursion(repost_from)) if post.content.is_empty() => {
+            format!("🔁 {}", repost_from.content.fallback())
+        }
+        _ => post.content.fallback(),
+    };
+    let subject = format!(
+        "[{}] {annotation}{}{}",
+        source.platform.display_name,
+        if author_name {
+            format!("{}: ", post.user.nickname)
+        } else {
+            String::new()
+        },
+        helper::truncate_chars(
+            summary.lines().next().unwrap_or_default().into(),
+            SUBJECT_CONTENT_LIMIT
+        )
+    );
+
+    let mut plain = String::new();
+    let mut html = if author_name {
+        format!(
+            "<p>{}<a href=\"{}\">{}</a></p>\n",
+            helper::escape_html(annotation),
+            helper::escape_html(&post.user.profile_url),
+            helper::escape_html(&post.user.nickname)
+        )
+    } else if !annotation.is_empty() {
+        format!("<p>{}</p>\n", helper::escape_html(annotation.trim_end()))
+    } else {
+        String::new()
+    };
+
+    if !post.content.is_empty() {
+        plain.push_str(&post.content.fallback());
+        html.push_str(&format!("<p>{}</p>\n", content_html(&post.content)));
+    }
+    if let Some(RepostFrom::Recursion(repost_from)) = &post.repost_from {
+        let nickname_html = match repost_from.urls_recursive().major() {
+            PostUrl::Clickable(url) => format!(
+                "<a href=\"{}\">{}</a>",
+                helper::escape_html(&url.url),
+                helper::escape_html(&repost_from.user.nickname)
+            ),
+            PostUrl::Identity(_) => helper::escape_html(&repost_from.user.nickname),
+        };
+        if !plain.is_empty() {
+            plain.push_str("\n\n");
+        }
+        plain.push_str(&format!(
+            "> 🔁 {}: {}",
+            repost_from.user.nickname,
+            repost_from.content.fallback().replace('\n', "\n> ")
+        ));
+        html.push_str(&format!(
+            "<blockquote>🔁 {nickname_html}: {}</blockquote>\n",
+            content_html(&repost_from.content)
+        ));
+    }
+
+    for attachment in post.attachments_recursive(true) {
+        match attachment {
+            PostAttachment::Image(image) => {
+                plain.push_str(&format!("\n🖼️ {}", image.media_url));
+                html.push_str(&format!(
+                    "<p><img src=\"{}\" style=\"max-width: 100%\"></p>\n",
+                    helper::escape_html(&image.media_url)
+                ));
+            }
+            PostAttachment::Video(video) => {
+                plain.push_str(&format!("\n🎬 {}", video.media_url));
+                html.push_str(&format!(
+                    "<p><a href=\"{}\">🎬 {}</a></p>\n",
+                    helper::escape_html(&video.media_url),
+                    helper::escape_html(&video.media_url)
+                ));
+            }
+        }
+    }
+
+    // Jump links
+    let links = post
+        .urls_recursive()
+        .into_iter()
+        .filter_map(|url| url.as_clickable())
+        .collect::<Vec<_>>();
+    if !links.is_empty() {
+        plain.push_str("\n\n");
+        plain.push_str(
+            &links
+                .iter()
+                .map(|url| format!("{}: {}", url.display, url.url))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        html.push_str(&format!(
+            "<p>{}</p>",
+            links
+                .iter()
+                .map(|url| format!(
+                    "<a href=\"{}\">{}</a>",
+                    helper::escape_html(&url.url),
+                    helper::escape_html(&url.display)
+                ))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+    }
+
+    Entry {
+        subject,
+        plain,
+        html,
+        attachments: vec![],
+    }
+}
+
+pub fn log(message: &str) -> Entry {
+    Entry {
+        subject: "[closely] Log".into(),
+        plain: message.into(),
+        html: format!("<pre>{}</pre>", helper::escape_html(message)),
+        attachments: vec![],
+    }
+}
+
+pub fn file(file: &FileRef<'_>, source: &StatusSource, emoji: &str, attach: bool) -> Entry {
+    let text = format!(
+        "{emoji} {} ({})",
+        file.name,
+        humansize::format_size(file.size, humansize::BINARY)
+    );
+    Entry {
+        subject: format!("[{}] {text}", source.platform.display_name),
+        plain: text.clone(),
+        html: format!("<p>{}</p>", helper::escape_html(&text)),
+        attachments: if attach {
+            vec![EntryAttachment {
+                name: file.name.clone(),
+                data: file.data.clone(),
+            }]
+        } else {
+            vec![]
+        },
+    }
+}
+
+fn author_prefix(author_name: bool, live_status: &LiveStatus) -> String {
+    if author_name {
+        format!("[{}] ", live_status.streamer_name)
+    } else {
+        String::new()
+    }
+}
+
+fn content_html(content: &PostContent) -> String {
+    content
+        .parts()
+        .map(|part| match part {
+            PostContentPart::Plain(text) => {
+                Cow::Owned(helper::escape_html(text).replace('\n', "<br>"))
+            }
+            PostContentPart::Link { display, url } => Cow::Owned(format!(
+                "<a href=\"{}\">{}</a>",
+                helper::escape_html(url),
+                helper::escape_html(display)
+            )),
+            PostContentPart::InlineAttachment(_) => {
+                // Ignore, we handle it in post.attachments
+                Cow::Borrowed("")
+            }
+        })
+        .collect()
+}
+
+fn wrap_html(body: &str) -> String {
+    format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head><body>\n{body}\n</body></html>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{platform::PlatformMetadata, source::PostAttachmentImage};
+
+    fn source() -> StatusSource {
+        StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        }
+    }
+
+    #[test]
+    fn render_post() {
+        let post = Post::mock(
+            PostContent::plain("<b>meow</b>\n").with_link("link", "https://example.com/link"),
+            vec![PostAttachment::Image(PostAttachmentImage {
+                media_url: "https://example.com/0.jpg".into(),
+                has_spoiler: false,
+            })],
+        );
+
+        let entry = super::post(true, &post, &source(), "");
+        assert_eq!(entry.subject, "[test] user: <b>meow</b>");
+        assert_eq!(
+            entry.plain,
+            "<b>meow</b>\n[link](https://example.com/link)\n🖼️ https://example.com/0.jpg\n\nView: https://example.com/post"
+        );
+        assert!(entry.html.contains(
+            "<p>&lt;b&gt;meow&lt;/b&gt;<br><a href=\"https://example.com/link\">link</a></p>"
+        ));
+        assert!(entry
+            .html
+            .contains("<img src=\"https://example.com/0.jpg\" style=\"max-width: 100%\">"));
+        assert!(entry
+            .html
+            .contains("<a href=\"https://example.com/post\">View</a>"));
+
+        let entry = super::post(false, &post, &source(), "✏️ ");
+        assert_eq!(entry.subject, "[test] ✏️ <b>meow</b>");
+        assert!(!entry.html.contains("user"));
+    }
+
+    #[test]
+    fn digest() {
+        let mail = Mail::digest(&[log("first"), log("<second>")]);
+        assert_eq!(mail.subject, "[closely] 2 notifications");
+        assert_eq!(
+            mail.plain,
+            "[closely] Log\n\nfirst\n\n---\n\n[closely] Log\n\n<second>"
+        );
+        assert!(mail.html.contains(
+            "<h3>[closely] Log</h3>\n<pre>first</pre>\n<hr>\n<h3>[closely] Log</h3>\n<pre>&lt;second&gt;</pre>"
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{borrow::Cow, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::{
+        robot::{self, RobotCard},
+        NotifierTrait,
+    },
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::Notification,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    // e.g. "https://open.feishu.cn/open-apis/bot/v2/hook/xxx", or the one of Lark
+    // under "open.larksuite.com"
+    #[serde(flatten)]
+    pub webhook: ConfigWebhook,
+    // Required if the robot is secured by signing
+    #[serde(flatten)]
+    pub secret: Option<robot::ConfigSecret>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        self.webhook.validate()?;
+        let url = self
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for Feishu: {err}"))?;
+        ensure!(url.starts_with("https://"), "invalid webhook URL");
+        self.secret.validate()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The webhook URL is the secret
+        write!(f, "feishu:webhook")
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            webhook: new.webhook.unwrap_or(self.webhook),
+            secret: new.secret.or(self.secret),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+    #[serde(flatten)]
+    pub secret: Option<robot::ConfigSecret>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigWebhook {
+        WebhookUrl(String),
+    }
+}
+
+// The limit is 30 KB for the whole body
+const MARKDOWN_LIMIT: usize = 20000;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Feishu",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn webhook_url(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for Feishu: {err}"))
+    }
+
+    fn secret(&self) -> anyhow::Result<Option<Cow<'_, str>>> {
+        self.params
+            .secret
+            .as_ref()
+            .map(|secret| {
+                secret
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read secret for Feishu: {err}"))
+            })
+            .transpose()
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let mut errors = vec![];
+        for card in robot::make_cards(&self.params.notifications, notification).await {
+            if let Err(err) = self.send(&card).await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn send(&self, card: &RobotCard) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.webhook_url()?)
+            .map_err(|err| anyhow!("invalid webhook URL: {err}"))?;
+
+        let mut body = make_body(card);
+        if let Some(secret) = self.secret()? {
+            let timestamp = robot::unix_time().as_secs();
+            body["timestamp"] = timestamp.to_string().into();
+            body["sign"] = sign(&secret, timestamp).into();
+        }
+
+        let resp = robot::post(url, &body)
+            .await
+            .map_err(|err| anyhow!("failed to send message to Feishu: {err}"))?;
+        // Older robots respond with `StatusCode` and `StatusMessage`
+        let code = resp["code"]
+            .as_i64()
+            .or_else(|| resp["StatusCode"].as_i64())
+            .unwrap_or_default();
+        if code != 0 {
+            bail!(
+                "response contains error, code: {code}, msg: '{}'",
+                resp["msg"]
+                    .as_str()
+                    .or_else(|| resp["StatusMessage"].as_str())
+                    .unwrap_or("*no msg*")
+            );
+        }
+        Ok(())
+    }
+}
+
+// https://open.feishu.cn/document/client-docs/bot-v3/add-custom-bot#3c6592d6
+fn sign(secret: &str, timestamp: u64) -> String {
+    // Yes, the key is the string and the message is empty
+    robot::hmac_sha256_base64(format!("{timestamp}\n{secret}").as_bytes(), b"")
+}
+
+// https://open.feishu.cn/document/common-capabilities/message-card/message-cards-content/using-markdown-tags
+fn make_body(card: &RobotCard) -> json::Value {
+    let mut markdown = card.markdown.clone();
+    // Images in cards must be uploaded by apps, robots can only link them
+    let images = card
+        .cover
+        .iter()
+        .map(|cover| cover.image_url.as_str())
+        .chain(card.image_urls.iter().map(|url| url.as_str()))
+        .enumerate()
+        .map(|(i, url)| robot::link(&format!("🖼️ {}", i + 1), url))
+        .collect::<Vec<_>>();
+    if !images.is_empty() {
+        markdown.push_str("\n\n");
+        markdown.push_str(&images.join(" "));
+    }
+
+    let mut elements = vec![json!({
+        "tag": "markdown",
+        "content": helper::truncate_bytes(markdown, MARKDOWN_LIMIT),
+    })];
+    if !card.buttons.is_empty() {
+        elements.push(json!({
+            "tag": "action",
+            "actions": card.buttons.iter().map(|button| json!({
+                "tag": "button",
+                "text": { "tag": "plain_text", "content": button.text },
+                "type": "default",
+                "url": button.url,
+            })).collect::<Vec<_>>(),
+        }));
+    }
+
+    json!({
+        "msg_type": "interactive",
+        "card": {
+            "config": { "wide_screen_mode": true },
+            "header": {
+                "title": { "tag": "plain_text", "content": card.title },
+                "template": if card.cover.is_some() { "green" } else { "blue" },
+            },
+            "elements": elements,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::robot::{RobotButton, RobotCover};
+
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign("SECmeow", 1700000000),
+            "XRwXjbkD3TEmCRYeKtcwf0t6E+3Pw+A5gDIrkrT9ZiI="
+        );
+    }
+
+    #[test]
+    fn body() {
+        let card = RobotCard {
+            title: "[test] 🟢".into(),
+            markdown: "[title](https://example.com/live)".into(),
+            cover: Some(RobotCover {
+                image_url: "https://example.com/cover.jpg".into(),
+                title: "title".into(),
+                url: "https://example.com/live".into(),
+            }),
+            image_urls: vec![],
+            buttons: vec![RobotButton {
+                text: "Watch".into(),
+                url: "https://example.com/live".into(),
+            }],
+        };
+        assert_eq!(
+            make_body(&card),
+            json!({
+                "msg_type": "interactive",
+                "card": {
+                    "config": { "wide_screen_mode": true },
+                    "header": {
+                        "title": { "tag": "plain_text", "content": "[test] 🟢" },
+                        "template": "green",
+                    },
+                    "elements": [
+                        {
+                            "tag": "markdown",
+                            "content": "[title](https://example.com/live)\n\n[🖼️ 1](https://example.com/cover.jpg)",
+                        },
+                        {
+                            "tag": "action",
+                            "actions": [{
+                                "tag": "button",
+                                "text": { "tag": "plain_text", "content": "Watch" },
+                                "type": "default",
+                                "url": "https://example.com/live",
+                            }],
+                        },
+                    ],
+                },
+            })
+        );
+    }
+}
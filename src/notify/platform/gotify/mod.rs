@@ -0,0 +1,209 @@
+use std::{borrow::Cow, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::{
+        push::{self, PushMessage, PushPriority},
+        NotifierTrait,
+    },
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::Notification,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    pub server_url: String,
+    #[serde(flatten)]
+    pub app_token: ConfigAppToken,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.server_url)
+            .map_err(|err| anyhow!("invalid server URL: {err}"))?;
+        ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "invalid server URL scheme '{}'",
+            url.scheme()
+        );
+        self.app_token.validate()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let url = reqwest::Url::parse(&self.server_url).ok();
+        write!(
+            f,
+            "gotify:{}",
+            url.as_ref().and_then(|url| url.host_str()).unwrap_or("*")
+        )
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            server_url: new.server_url.unwrap_or(self.server_url),
+            app_token: new.app_token.unwrap_or(self.app_token),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    pub server_url: Option<String>,
+    #[serde(flatten)]
+    pub app_token: Option<ConfigAppToken>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigAppToken {
+        AppToken(String),
+    }
+}
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Gotify",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn app_token(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .app_token
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read app token for Gotify: {err}"))
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let mut errors = vec![];
+        for message in push::make_messages(&self.params.notifications, notification).await {
+            if let Err(err) = self.push(&message).await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn push(&self, message: &PushMessage) -> anyhow::Result<()> {
+        let token = self.app_token()?;
+        let url = format!("{}/message", self.params.server_url.trim_end_matches('/'));
+
+        let resp = helper::reqwest_client()?
+            .post(url)
+            .header("X-Gotify-Key", token.as_ref())
+            .json(&make_body(message))
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request to Gotify: {err}"))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            bail!("response status is not success: {status}, response '{text}'");
+        }
+        Ok(())
+    }
+}
+
+// https://gotify.net/docs/msgextras
+fn make_body(message: &PushMessage) -> json::Value {
+    let mut notification_extras = json::Map::new();
+    if let Some(url) = &message.click_url {
+        notification_extras.insert("click".into(), json!({ "url": url }));
+    }
+    if let Some(url) = &message.attachment_url {
+        notification_extras.insert("bigImageUrl".into(), url.as_str().into());
+    }
+
+    json!({
+        "title": message.title,
+        "message": message.message,
+        // The Android app pops up for 8 and above, and is silent below 4
+        "priority": match message.priority {
+            PushPriority::Low => 2,
+            PushPriority::Default => 5,
+            PushPriority::High => 8,
+        },
+        "extras": {
+            "client::display": { "contentType": "text/plain" },
+            "client::notification": notification_extras,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body() {
+        let message = PushMessage {
+            title: "title".into(),
+            message: "message".into(),
+            priority: PushPriority::High,
+            click_url: Some("https://example.com/live".into()),
+            attachment_url: None,
+        };
+        assert_eq!(
+            make_body(&message),
+            json!({
+                "title": "title",
+                "message": "message",
+                "priority": 8,
+                "extras": {
+                    "client::display": { "contentType": "text/plain" },
+                    "client::notification": { "click": { "url": "https://example.com/live" } },
+                },
+            })
+        );
+    }
+}
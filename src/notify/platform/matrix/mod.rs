@@ -0,0 +1,732 @@
+mod request;
+
+use std::{borrow::Cow, collections::VecDeque, fmt, future::Future, pin::Pin, time::SystemTime};
+
+use anyhow::{anyhow, ensure};
+use bytes::Bytes;
+use request::*;
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::NotifierTrait,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        DocumentRef, FileRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        PlaybackFormat, PlaybackRef, Post, PostAttachment, PostContent, PostContentPart,
+        PostPlatformUniqueId, PostUrl, PostsRef, RepostFrom, StatusSource,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    pub homeserver_url: String,
+    #[serde(flatten)]
+    pub access_token: ConfigAccessToken,
+    // Room ID "!id:server" or alias "#alias:server"
+    pub room: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.homeserver_url)
+            .map_err(|err| anyhow!("invalid homeserver URL: {err}"))?;
+        ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "invalid homeserver URL scheme '{}'",
+            url.scheme()
+        );
+        self.access_token.validate()?;
+        ensure!(
+            (self.room.starts_with('!') || self.room.starts_with('#')) && self.room.contains(':'),
+            "invalid room ID or alias '{}'",
+            self.room
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "matrix:{}", self.room)
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            homeserver_url: new.homeserver_url.unwrap_or(self.homeserver_url),
+            access_token: new.access_token.unwrap_or(self.access_token),
+            room: new.room.unwrap_or(self.room),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    pub homeserver_url: Option<String>,
+    #[serde(flatten)]
+    pub access_token: Option<ConfigAccessToken>,
+    pub room: Option<String>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigAccessToken {
+        AccessToken(String),
+    }
+}
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+    // Resolved from the alias on the first use
+    room_id: Mutex<Option<String>>,
+    current_live: Mutex<Option<CurrentLive>>,
+    // The latest first
+    sent_posts: Mutex<VecDeque<(PostPlatformUniqueId, String /* event ID */)>>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Matrix",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            room_id: Mutex::new(None),
+            current_live: Mutex::new(None),
+            sent_posts: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn access_token(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .access_token
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read access token for Matrix: {err}"))
+    }
+
+    async fn room_id(&self, client: &Client<'_>) -> anyhow::Result<String> {
+        if !self.params.room.starts_with('#') {
+            return Ok(self.params.room.clone());
+        }
+
+        let mut room_id = self.room_id.lock().await;
+        if let Some(room_id) = &*room_id {
+            return Ok(room_id.clone());
+        }
+        let resolved = client
+            .resolve_room_alias(&self.params.room)
+            .await
+            .map_err(|err| anyhow!("failed to resolve Matrix room alias: {err}"))?;
+        *room_id = Some(resolved.clone());
+        Ok(resolved)
+    }
+
+    // Returns the event ID
+    async fn send(&self, content: &json::Value) -> anyhow::Result<String> {
+        let token = self.access_token()?;
+        let client = Client::new(&self.params.homeserver_url, &token);
+        let room_id = self.room_id(&client).await?;
+        client
+            .send_message(&room_id, content)
+            .await
+            .map_err(|err| anyhow!("failed to send message to Matrix: {err}"))
+    }
+
+    // Returns the `mxc://` URI
+    async fn upload(&self, name: &str, mimetype: &str, data: Bytes) -> anyhow::Result<String> {
+        let token = self.access_token()?;
+        Client::new(&self.params.homeserver_url, &token)
+            .upload(name, mimetype, data)
+            .await
+            .map_err(|err| anyhow!("failed to upload media to Matrix: {err}"))
+    }
+
+    // Images from sources are not reachable by Matrix clients, so re-upload them to
+    // the media repository to display them inline
+    async fn send_image(&self, url: &str) -> anyhow::Result<()> {
+        let resp = helper::reqwest_client()?
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to download image '{url}': {err}"))?;
+        ensure!(
+            resp.status().is_success(),
+            "failed to download image '{url}', status: {}",
+            resp.status()
+        );
+        let mimetype = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_owned();
+        let data = resp
+            .bytes()
+            .await
+            .map_err(|err| anyhow!("failed to download image '{url}': {err}"))?;
+        let name = file_name_from_url(url);
+
+        let size = data.len();
+        let mxc = self.upload(name, &mimetype, data).await?;
+        self.send(&json!({
+            "msgtype": "m.image",
+            "body": name,
+            "url": mxc,
+            "info": {
+                "mimetype": mimetype,
+                "size": size,
+            },
+        }))
+        .await?;
+        Ok(())
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        match &notification.kind {
+            NotificationKind::LiveOnline(live_status) => {
+                self.notify_live(live_status, notification.source).await
+            }
+            NotificationKind::LiveTitle(live_status, _old_title) => {
+                self.notify_live_title(live_status, notification.source)
+                    .await
+            }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.notify_live_area(live_status, old_area, notification.source)
+                    .await
+            }
+            NotificationKind::Posts(posts) => self.notify_posts(posts, notification.source).await,
+            NotificationKind::PostEdited(post, _old) => {
+                if !self.params.notifications.post_edited {
+                    info!("post_edited notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post_changed(post, notification.source, "✏️ ")
+                    .await
+            }
+            NotificationKind::PostDeleted(post) => {
+                if !self.params.notifications.post_deleted {
+                    info!("post_deleted notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post_changed(post, notification.source, "🗑️ ")
+                    .await
+            }
+            NotificationKind::Log(message) => self.notify_log(message).await,
+            NotificationKind::Playback(playback) => {
+                self.notify_playback(playback, notification.source).await
+            }
+            NotificationKind::Document(document) => {
+                self.notify_document(document, notification.source).await
+            }
+        }
+    }
+
+    async fn notify_live(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_online {
+            info!("live_online notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        match live_status.kind {
+            LiveStatusKind::Online { start_time } => {
+                self.notify_live_online(live_status, source, start_time)
+                    .await
+            }
+            LiveStatusKind::Offline | LiveStatusKind::Banned => {
+                self.notify_live_offline(live_status, source).await
+            }
+        }
+    }
+
+    async fn notify_live_online(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+        start_time: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        let title_history = VecDeque::from([live_status.title.clone()]);
+        let start_time = start_time.unwrap_or_else(SystemTime::now);
+
+        let html = make_live_html(
+            self.params.notifications.author_name,
+            &title_history,
+            live_status,
+            source,
+            start_time,
+        );
+        let event_id = self.send(&html.into_content("m.text")).await?;
+
+        *self.current_live.lock().await = Some(CurrentLive {
+            start_time,
+            event_id,
+            title_history,
+        });
+
+        // The message is sent anyway, don't fail for the cover
+        if let Err(err) = self.send_image(&live_status.cover_image_url).await {
+            warn!("failed to send live cover image to Matrix: {err}");
+        }
+        Ok(())
+    }
+
+    async fn notify_live_offline(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if let Some(current_live) = self.current_live.lock().await.take() {
+            let html = make_live_html(
+                self.params.notifications.author_name,
+                &current_live.title_history,
+                live_status,
+                source,
+                current_live.start_time,
+            );
+            self.send(&make_replacement(
+                &current_live.event_id,
+                html.into_content("m.text"),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn notify_live_title(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        // Update the last message
+        if let Some(current_live) = self.current_live.lock().await.as_mut() {
+            current_live
+                .title_history
+                .push_front(live_status.title.clone());
+
+            let html = make_live_html(
+                self.params.notifications.author_name,
+                &current_live.title_history,
+                live_status,
+                source,
+                current_live.start_time,
+            );
+            self.send(&make_replacement(
+                &current_live.event_id,
+                html.into_content("m.text"),
+            ))
+            .await?;
+        }
+
+        // Send a new message
+        if !self.params.notifications.live_title {
+            info!("live_title notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let mut html = Html::plain(format!(
+            "[{}] ✏️ {}",
+            source.platform.display_name,
+            self.author_prefix(live_status)
+        ));
+        html.push_link(&live_status.title, &live_status.live_url);
+        self.send(&html.into_content("m.text")).await?;
+        Ok(())
+    }
+
+    async fn notify_live_area(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let Some(area) = &live_status.area else {
+            return Ok(());
+        };
+
+        let mut html = Html::plain(format!(
+            "[{}] 🏷️ {}",
+            source.platform.display_name,
+            self.author_prefix(live_status)
+        ));
+        html.push_link(format!("{old_area} ➡️ {area}"), &live_status.live_url);
+        self.send(&html.into_content("m.text")).await?;
+        Ok(())
+    }
+
+    fn author_prefix(&self, live_status: &LiveStatus) -> Cow<'_, str> {
+        if self.params.notifications.author_name {
+            Cow::Owned(format!("[{}] ", live_status.streamer_name))
+        } else {
+            Cow::Borrowed("")
+        }
+    }
+
+    async fn notify_posts(
+        &self,
+        posts: &PostsRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.post {
+            info!("post notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let mut errors = vec![];
+        for post in &posts.0 {
+            if let Err(err) = self.notify_post(post, source, "").await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn notify_post(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let html = make_post_html(
+            self.params.notifications.author_name,
+            post,
+            source,
+            annotation,
+        );
+        let event_id = self.send(&html.into_content("m.text")).await?;
+        self.remember_sent_post(post, event_id).await;
+
+        // Matrix has no albums, send the images one by one after the text
+        let mut errors = vec![];
+        for attachment in post.attachments_recursive(true) {
+            if let PostAttachment::Image(image) = attachment {
+                if let Err(err) = self.send_image(&image.media_url).await {
+                    errors.push(err);
+                }
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    // Replaces the message previously sent for the post with the annotation, or
+    // sends a new one if we don't remember it (e.g. sent before restarting)
+    async fn notify_post_changed(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let id = post.platform_unique_id();
+        let event_id = self
+            .sent_posts
+            .lock()
+            .await
+            .iter()
+            .find(|(sent_id, _)| *sent_id == id)
+            .map(|(_, event_id)| event_id.clone());
+        let Some(event_id) = event_id else {
+            return self.notify_post(post, source, annotation).await;
+        };
+
+        let html = make_post_html(
+            self.params.notifications.author_name,
+            post,
+            source,
+            annotation,
+        );
+        self.send(&make_replacement(&event_id, html.into_content("m.text")))
+            .await?;
+        Ok(())
+    }
+
+    async fn remember_sent_post(&self, post: &Post, event_id: String) {
+        let id = post.platform_unique_id();
+        let mut sent_posts = self.sent_posts.lock().await;
+        sent_posts.retain(|(sent_id, _)| *sent_id != id);
+        if sent_posts.len() >= SENT_POSTS_CAPACITY {
+            sent_posts.pop_back();
+        }
+        sent_posts.push_front((id, event_id));
+    }
+
+    async fn notify_log(&self, message: &str) -> anyhow::Result<()> {
+        if !self.params.notifications.log {
+            info!("log notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.send(&Html::plain(message).into_content("m.notice"))
+            .await?;
+        Ok(())
+    }
+
+    async fn notify_playback(
+        &self,
+        playback: &PlaybackRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.playback {
+            info!("playback notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let playback = playback.get(PlaybackFormat::Mp4).await?;
+        self.notify_file(&playback.file, source, "🎥", "m.video", "video/mp4")
+            .await
+    }
+
+    async fn notify_document(
+        &self,
+        document: &DocumentRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.document {
+            info!("document notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.notify_file(
+            &document.file,
+            source,
+            "📊",
+            "m.file",
+            "application/octet-stream",
+        )
+        .await
+    }
+
+    async fn notify_file(
+        &self,
+        file: &FileRef<'_>,
+        source: &StatusSource,
+        emoji: &str,
+        msgtype: &str,
+        mimetype: &str,
+    ) -> anyhow::Result<()> {
+        trace!("uploading file to Matrix '{file}'");
+        let mxc = self.upload(&file.name, mimetype, file.data.clone()).await?;
+
+        // `body` is treated as a caption if `filename` is present
+        self.send(&json!({
+            "msgtype": msgtype,
+            "body": format!("[{}] {emoji} {}", source.platform.display_name, file.name),
+            "filename": file.name,
+            "url": mxc,
+            "info": {
+                "mimetype": mimetype,
+                "size": file.size,
+            },
+        }))
+        .await?;
+        Ok(())
+    }
+}
+
+fn make_live_html<'a>(
+    author_name: bool,
+    title_history: impl IntoIterator<Item = &'a String>,
+    live_status: &'a LiveStatus,
+    source: &StatusSource,
+    start_time: SystemTime,
+) -> Html {
+    let mut html = Html::plain(format!(
+        "[{}] {} {}",
+        source.platform.display_name,
+        match live_status.kind {
+            LiveStatusKind::Online { start_time: _ } => "🟢",
+            LiveStatusKind::Offline => "🟠",
+            LiveStatusKind::Banned => "🔴",
+        },
+        if author_name {
+            Cow::Owned(format!("[{}] ", live_status.streamer_name))
+        } else {
+            Cow::Borrowed("")
+        },
+    ));
+    html.push_link(
+        itertools::join(title_history, " ⬅️ "),
+        &live_status.live_url,
+    );
+    if live_status.kind == LiveStatusKind::Offline || live_status.kind == LiveStatusKind::Banned {
+        if let Ok(dur) = start_time.elapsed() {
+            html.push_plain(format!(" ({})", helper::format_duration_in_min(dur)));
+        }
+    }
+    html
+}
+
+fn make_post_html(author_name: bool, post: &Post, source: &StatusSource, annotation: &str) -> Html {
+    let mut html = Html::plain(format!("[{}] {annotation}", source.platform.display_name));
+
+    match &post.repost_from {
+        Some(RepostFrom::Recursion(repost_from)) => {
+            if !post.content.is_empty() {
+                html.push_plain("💬 ");
+                if author_name {
+                    html.push_link(&post.user.nickname, &post.user.profile_url);
+                    html.push_plain(": ");
+                }
+                push_content(&mut html, &post.content);
+                html.push_plain("\n");
+            }
+
+            html.push_quote(|html| {
+                html.push_plain("🔁 ");
+                match repost_from.urls_recursive().major() {
+                    PostUrl::Clickable(url) => html.push_link(&repost_from.user.nickname, &url.url),
+                    PostUrl::Identity(_) => html.push_plain(&repost_from.user.nickname),
+                }
+                html.push_plain(": ");
+                push_content(html, &repost_from.content);
+            });
+        }
+        None => {
+            if author_name {
+                html.push_link(&post.user.nickname, &post.user.profile_url);
+                html.push_plain(": ");
+            }
+            push_content(&mut html, &post.content);
+        }
+    }
+
+    // Videos are linked rather than uploaded, they can be large
+    let videos = post
+        .attachments_recursive(true)
+        .into_iter()
+        .filter_map(|attachment| match attachment {
+            PostAttachment::Video(video) => Some(video.media_url.as_str()),
+            PostAttachment::Image(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if !videos.is_empty() {
+        html.push_plain("\n\n");
+        for (i, url) in videos.into_iter().enumerate() {
+            if i != 0 {
+                html.push_plain(" ");
+            }
+            html.push_link(format!("🎬 {}", i + 1), url);
+        }
+    }
+
+    // Jump links
+    let urls = post.urls_recursive();
+    let mut links = urls.iter().filter_map(|url| url.as_clickable()).peekable();
+    if links.peek().is_some() {
+        html.push_plain("\n\n");
+        while let Some(url) = links.next() {
+            html.push_link(&url.display, &url.url);
+            if links.peek().is_some() {
+                html.push_plain(" | ");
+            }
+        }
+    }
+    html
+}
+
+fn push_content(html: &mut Html, content: &PostContent) {
+    content.parts().for_each(|part| match part {
+        PostContentPart::Plain(text) => html.push_plain(text),
+        PostContentPart::Link { display, url } => html.push_link(display, url),
+        PostContentPart::InlineAttachment(_) => {
+            // Ignore, we handle it in post.attachments
+        }
+    });
+}
+
+fn file_name_from_url(url: &str) -> &str {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("image")
+}
+
+struct CurrentLive {
+    start_time: SystemTime,
+    event_id: String,
+    // The first is the current title, the last is the oldest title
+    title_history: VecDeque<String>,
+}
+
+// Number of sent posts to remember, for editing them when the posts change
+const SENT_POSTS_CAPACITY: usize = 100;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(
+            file_name_from_url("https://example.com/a/b.jpg?size=large"),
+            "b.jpg"
+        );
+        assert_eq!(file_name_from_url("https://example.com/a/"), "image");
+    }
+
+    #[test]
+    fn post_html() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = Post::mock(
+            PostContent::plain("<b>meow</b>\n").with_link("link", "https://example.com/link"),
+            vec![],
+        );
+
+        let content = make_post_html(false, &post, &source, "✏️ ").into_content("m.text");
+        assert_eq!(content["body"], "[test] ✏️ <b>meow</b>\nlink\n\nView");
+        assert_eq!(
+            content["formatted_body"],
+            "[test] ✏️ &lt;b&gt;meow&lt;/b&gt;<br><a href=\"https://example.com/link\">link</a><br><br><a href=\"https://example.com/post\">View</a>"
+        );
+    }
+}
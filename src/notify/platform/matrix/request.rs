@@ -0,0 +1,303 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use reqwest::{header, Method, RequestBuilder, StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::helper;
+
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+pub struct Client<'a> {
+    homeserver_url: &'a str,
+    access_token: &'a str,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(homeserver_url: &'a str, access_token: &'a str) -> Self {
+        Self {
+            homeserver_url,
+            access_token,
+        }
+    }
+
+    // Segments are percent-encoded, room aliases contain '#'
+    fn endpoint(&self, segments: &[&str]) -> anyhow::Result<Url> {
+        let mut url = Url::parse(self.homeserver_url)
+            .map_err(|err| anyhow!("invalid homeserver URL: {err}"))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid homeserver URL: cannot be a base"))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    pub async fn resolve_room_alias(&self, alias: &str) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        struct Response {
+            room_id: String,
+        }
+
+        let url = self.endpoint(&["_matrix", "client", "v3", "directory", "room", alias])?;
+        let resp: Response = self
+            .send(|client| client.request(Method::GET, url.clone()))
+            .await?;
+        Ok(resp.room_id)
+    }
+
+    // Returns the event ID
+    pub async fn send_message(
+        &self,
+        room_id: &str,
+        content: &json::Value,
+    ) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        struct Response {
+            event_id: String,
+        }
+
+        let txn_id = make_txn_id();
+        let url = self.endpoint(&[
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            room_id,
+            "send",
+            "m.room.message",
+            &txn_id,
+        ])?;
+        let resp: Response = self
+            .send(|client| client.request(Method::PUT, url.clone()).json(content))
+            .await?;
+        Ok(resp.event_id)
+    }
+
+    // Returns the `mxc://` URI
+    pub async fn upload(&self, name: &str, mimetype: &str, data: Bytes) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        struct Response {
+            content_uri: String,
+        }
+
+        let url = self.endpoint(&["_matrix", "media", "v3", "upload"])?;
+        let resp: Response = self
+            .send(|client| {
+                client
+                    .request(Method::POST, url.clone())
+                    .query(&[("filename", name)])
+                    .header(header::CONTENT_TYPE, mimetype)
+                    .body(data.clone())
+            })
+            .await?;
+        Ok(resp.content_uri)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        make_request: impl Fn(&reqwest::Client) -> RequestBuilder,
+    ) -> anyhow::Result<T> {
+        let client = helper::reqwest_client()?;
+
+        let mut retries = 0;
+        loop {
+            let resp = make_request(&client)
+                .bearer_auth(self.access_token)
+                .send()
+                .await
+                .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+            let status = resp.status();
+            let text = resp
+                .text()
+                .await
+                .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+            if status.is_success() {
+                return json::from_str(&text)
+                    .map_err(|err| anyhow!("failed to deserialize response: {err}"));
+            }
+
+            if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RATE_LIMITED_RETRIES {
+                let retry_after = json::from_str::<ErrorResponse>(&text)
+                    .ok()
+                    .and_then(|resp| resp.retry_after_ms)
+                    .map(Duration::from_millis)
+                    .unwrap_or(Duration::from_secs(1));
+                warn!("rate limited by Matrix homeserver, retry after {retry_after:?}");
+                tokio::time::sleep(retry_after).await;
+                retries += 1;
+                continue;
+            }
+            match json::from_str::<ErrorResponse>(&text) {
+                Ok(resp) => bail!(
+                    "response contains error, status '{status}', errcode '{}', error '{}'",
+                    resp.errcode,
+                    resp.error.unwrap_or_else(|| "*no error*".into())
+                ),
+                Err(_) => bail!("response status is not success: {status}, response '{text}'"),
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errcode: String,
+    error: Option<String>,
+    retry_after_ms: Option<u64>,
+}
+
+// Transaction IDs are used by the homeserver to deduplicate retried requests,
+// so they must be unique within the access token
+fn make_txn_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("closely.{now}.{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+pub struct Html {
+    plain: String,
+    html: String,
+}
+
+impl Html {
+    pub fn new() -> Self {
+        Self {
+            plain: String::new(),
+            html: String::new(),
+        }
+    }
+
+    pub fn plain(text: impl AsRef<str>) -> Self {
+        let mut ret = Self::new();
+        ret.push_plain(text);
+        ret
+    }
+
+    pub fn push_plain(&mut self, text: impl AsRef<str>) {
+        let text = text.as_ref();
+        self.plain.push_str(text);
+        self.html
+            .push_str(&helper::escape_html(text).replace('\n', "<br>"));
+    }
+
+    pub fn push_link(&mut self, text: impl AsRef<str>, url: &str) {
+        let text = text.as_ref();
+        self.plain.push_str(text);
+        self.html.push_str(&format!(
+            "<a href=\"{}\">{}</a>",
+            helper::escape_html(url),
+            helper::escape_html(text).replace('\n', "<br>")
+        ));
+    }
+
+    pub fn push_quote(&mut self, content: impl FnOnce(&mut Self)) {
+        self.plain.push_str("> ");
+        self.html.push_str("<blockquote>");
+        content(self);
+        self.html.push_str("</blockquote>");
+    }
+
+    // Content of a `m.text` or `m.notice` event
+    pub fn into_content(self, msgtype: &str) -> json::Value {
+        json!({
+            "msgtype": msgtype,
+            "body": self.plain,
+            "format": "org.matrix.custom.html",
+            "formatted_body": self.html,
+        })
+    }
+}
+
+// Content of an event replacing the text of `event_id`
+// https://spec.matrix.org/v1.11/client-server-api/#event-replacements
+pub fn make_replacement(event_id: &str, new_content: json::Value) -> json::Value {
+    let mut content = json!({
+        "msgtype": new_content["msgtype"],
+        "body": format!("* {}", new_content["body"].as_str().unwrap_or_default()),
+        "m.relates_to": {
+            "rel_type": "m.replace",
+            "event_id": event_id,
+        },
+    });
+    if let Some(formatted_body) = new_content["formatted_body"].as_str() {
+        content["format"] = new_content["format"].clone();
+        content["formatted_body"] = format!("* {formatted_body}").into();
+    }
+    content["m.new_content"] = new_content;
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html() {
+        let mut html = Html::plain("<meow>\n");
+        html.push_link("a & b", "https://example.com/?a=1&b=2");
+        html.push_quote(|html| html.push_plain("quoted"));
+        assert_eq!(
+            html.into_content("m.text"),
+            json!({
+                "msgtype": "m.text",
+                "body": "<meow>\na & b> quoted",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "&lt;meow&gt;<br><a href=\"https://example.com/?a=1&amp;b=2\">a &amp; b</a><blockquote>quoted</blockquote>",
+            })
+        );
+    }
+
+    #[test]
+    fn endpoint() {
+        let client = Client::new("https://matrix.example.com/", "token");
+        assert_eq!(
+            client
+                .endpoint(&[
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "directory",
+                    "room",
+                    "#room:example.com"
+                ])
+                .unwrap()
+                .as_str(),
+            "https://matrix.example.com/_matrix/client/v3/directory/room/%23room:example.com"
+        );
+    }
+
+    #[test]
+    fn replacement() {
+        let content = make_replacement("$event", Html::plain("new").into_content("m.text"));
+        assert_eq!(
+            content,
+            json!({
+                "msgtype": "m.text",
+                "body": "* new",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "* new",
+                "m.relates_to": {
+                    "rel_type": "m.replace",
+                    "event_id": "$event",
+                },
+                "m.new_content": {
+                    "msgtype": "m.text",
+                    "body": "new",
+                    "format": "org.matrix.custom.html",
+                    "formatted_body": "new",
+                },
+            })
+        );
+    }
+}
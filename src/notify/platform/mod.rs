@@ -1,5 +1,15 @@
+pub mod dingtalk;
+pub mod discord;
+pub mod email;
+pub mod feishu;
+pub mod gotify;
+pub mod matrix;
+pub mod ntfy;
 pub mod qq;
+pub mod slack;
 pub mod telegram;
+pub mod webhook;
+pub mod wecom;
 
 use std::fmt;
 
@@ -11,16 +21,37 @@ use crate::config::{self, Overridable};
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(tag = "platform")]
 pub enum Config {
+    DingTalk(config::Accessor<dingtalk::ConfigParams>),
+    Discord(config::Accessor<discord::ConfigParams>),
+    Email(config::Accessor<email::ConfigParams>),
+    #[serde(alias = "Lark")]
+    Feishu(config::Accessor<feishu::ConfigParams>),
+    Gotify(config::Accessor<gotify::ConfigParams>),
+    Matrix(config::Accessor<matrix::ConfigParams>),
+    Ntfy(config::Accessor<ntfy::ConfigParams>),
     #[serde(rename = "QQ")]
     Qq(config::Accessor<qq::ConfigParams>),
+    Slack(config::Accessor<slack::ConfigParams>),
     Telegram(config::Accessor<telegram::ConfigParams>),
+    Webhook(config::Accessor<webhook::ConfigParams>),
+    WeCom(config::Accessor<wecom::ConfigParams>),
 }
 
 impl config::Validator for Config {
     fn validate(&self) -> anyhow::Result<()> {
         match self {
+            Self::DingTalk(p) => p.validate(),
+            Self::Discord(p) => p.validate(),
+            Self::Email(p) => p.validate(),
+            Self::Feishu(p) => p.validate(),
+            Self::Gotify(p) => p.validate(),
+            Self::Matrix(p) => p.validate(),
+            Self::Ntfy(p) => p.validate(),
             Self::Qq(p) => p.validate(),
+            Self::Slack(p) => p.validate(),
             Self::Telegram(p) => p.validate(),
+            Self::Webhook(p) => p.validate(),
+            Self::WeCom(p) => p.validate(),
         }
         .map_err(|err| anyhow!("[{self}] {err}"))
     }
@@ -32,12 +63,65 @@ impl Config {
         Self: Sized,
     {
         match self {
+            Self::DingTalk(n) => {
+                let new: <dingtalk::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::DingTalk(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Discord(n) => {
+                let new: <discord::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::Discord(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Email(n) => {
+                let new: <email::ConfigParams as config::Overridable>::Override = new.try_into()?;
+                Ok(Self::Email(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Feishu(n) => {
+                let new: <feishu::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::Feishu(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Gotify(n) => {
+                let new: <gotify::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::Gotify(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Matrix(n) => {
+                let new: <matrix::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::Matrix(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::Ntfy(n) => {
+                let new: <ntfy::ConfigParams as config::Overridable>::Override = new.try_into()?;
+                Ok(Self::Ntfy(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
             Self::Qq(n) => {
                 let new: <qq::ConfigParams as config::Overridable>::Override = new.try_into()?;
                 Ok(Self::Qq(config::Accessor::new_then_validate(
                     n.into_inner().override_into(new),
                 )?))
             }
+            Self::Slack(n) => {
+                let new: <slack::ConfigParams as config::Overridable>::Override = new.try_into()?;
+                Ok(Self::Slack(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
             Self::Telegram(n) => {
                 let new: <telegram::ConfigParams as config::Overridable>::Override =
                     new.try_into()?;
@@ -45,6 +129,19 @@ impl Config {
                     n.into_inner().override_into(new),
                 )?))
             }
+            Self::Webhook(n) => {
+                let new: <webhook::ConfigParams as config::Overridable>::Override =
+                    new.try_into()?;
+                Ok(Self::Webhook(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
+            Self::WeCom(n) => {
+                let new: <wecom::ConfigParams as config::Overridable>::Override = new.try_into()?;
+                Ok(Self::WeCom(config::Accessor::new_then_validate(
+                    n.into_inner().override_into(new),
+                )?))
+            }
         }
     }
 }
@@ -52,8 +149,18 @@ impl Config {
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::DingTalk(p) => write!(f, "{p}"),
+            Self::Discord(p) => write!(f, "{p}"),
+            Self::Email(p) => write!(f, "{p}"),
+            Self::Feishu(p) => write!(f, "{p}"),
+            Self::Gotify(p) => write!(f, "{p}"),
+            Self::Matrix(p) => write!(f, "{p}"),
+            Self::Ntfy(p) => write!(f, "{p}"),
             Self::Qq(p) => write!(f, "{p}"),
+            Self::Slack(p) => write!(f, "{p}"),
             Self::Telegram(p) => write!(f, "{p}"),
+            Self::Webhook(p) => write!(f, "{p}"),
+            Self::WeCom(p) => write!(f, "{p}"),
         }
     }
 }
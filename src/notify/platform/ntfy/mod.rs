@@ -0,0 +1,225 @@
+use std::{borrow::Cow, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use serde::{Deserialize, Serialize};
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::{
+        push::{self, PushMessage, PushPriority},
+        NotifierTrait,
+    },
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::Notification,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    // e.g. "https://ntfy.sh/my_topic"
+    pub topic_url: String,
+    #[serde(flatten)]
+    pub token: Option<ConfigToken>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        split_topic_url(&self.topic_url)?;
+        self.token.validate()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match split_topic_url(&self.topic_url) {
+            Ok((_, topic)) => write!(f, "ntfy:{topic}"),
+            Err(_) => write!(f, "ntfy:*"),
+        }
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            topic_url: new.topic_url.unwrap_or(self.topic_url),
+            token: new.token.or(self.token),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    pub topic_url: Option<String>,
+    #[serde(flatten)]
+    pub token: Option<ConfigToken>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigToken {
+        Token(String),
+    }
+}
+
+// Publishing as JSON requires posting to the server root with the topic in the
+// body, while non-ASCII titles are not allowed in the headers
+fn split_topic_url(topic_url: &str) -> anyhow::Result<(reqwest::Url, String)> {
+    let mut url =
+        reqwest::Url::parse(topic_url).map_err(|err| anyhow!("invalid topic URL: {err}"))?;
+    ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "invalid topic URL scheme '{}'",
+        url.scheme()
+    );
+    let topic = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|topic| !topic.is_empty())
+        .ok_or_else(|| anyhow!("topic URL has no topic"))?
+        .to_owned();
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("invalid topic URL"))?
+        .pop();
+    Ok((url, topic))
+}
+
+// https://docs.ntfy.sh/publish/#publish-as-json
+#[derive(Debug, Serialize)]
+struct Publish<'a> {
+    topic: &'a str,
+    title: &'a str,
+    message: &'a str,
+    priority: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    click: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attach: Option<&'a str>,
+}
+
+impl<'a> Publish<'a> {
+    fn new(topic: &'a str, message: &'a PushMessage) -> Self {
+        Self {
+            topic,
+            title: &message.title,
+            message: &message.message,
+            priority: match message.priority {
+                PushPriority::Low => 2,
+                PushPriority::Default => 3,
+                PushPriority::High => 4,
+            },
+            click: message.click_url.as_deref(),
+            attach: message.attachment_url.as_deref(),
+        }
+    }
+}
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "ntfy",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn token(&self) -> anyhow::Result<Option<Cow<'_, str>>> {
+        self.params
+            .token
+            .as_ref()
+            .map(|token| {
+                token
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read token for ntfy: {err}"))
+            })
+            .transpose()
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let mut errors = vec![];
+        for message in push::make_messages(&self.params.notifications, notification).await {
+            if let Err(err) = self.publish(&message).await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn publish(&self, message: &PushMessage) -> anyhow::Result<()> {
+        let (server_url, topic) = split_topic_url(&self.params.topic_url)?;
+
+        let mut request = helper::reqwest_client()?
+            .post(server_url)
+            .json(&Publish::new(&topic, message));
+        if let Some(token) = self.token()? {
+            request = request.bearer_auth(token);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request to ntfy: {err}"))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            bail!("response status is not success: {status}, response '{text}'");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topic_url() {
+        let (url, topic) = split_topic_url("https://ntfy.sh/my_topic").unwrap();
+        assert_eq!(url.as_str(), "https://ntfy.sh/");
+        assert_eq!(topic, "my_topic");
+
+        let (url, topic) = split_topic_url("https://example.com/ntfy/my_topic").unwrap();
+        assert_eq!(url.as_str(), "https://example.com/ntfy");
+        assert_eq!(topic, "my_topic");
+
+        assert!(split_topic_url("https://ntfy.sh/").is_err());
+        assert!(split_topic_url("ftp://ntfy.sh/my_topic").is_err());
+    }
+}
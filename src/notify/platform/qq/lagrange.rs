@@ -1,14 +1,23 @@
-use std::{borrow::Cow, fmt::Debug, time::Duration};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{anyhow, ensure};
+use base64::prelude::*;
+use once_cell::sync::OnceCell;
 use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use serde_json::{self as json, json};
 use tokio::time::timeout;
 
-use super::ConfigChat;
+use super::{ws, ConfigChat};
 use crate::{
     config::{AsSecretRef, Validator},
     helper, secret_enum,
+    source::FileRef,
 };
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
@@ -17,16 +26,64 @@ pub struct RemoteHttp {
     pub port: u16,
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RemoteWs {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ListenWs {
+    pub host: String,
+    pub port: u16,
+}
+
+impl ListenWs {
+    pub(super) fn to_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(SocketAddr::new(
+            self.host
+                .parse()
+                .map_err(|err| anyhow!("invalid host '{}' for QQ listen_ws: {err}", self.host))?,
+            self.port,
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigTransport {
+    RemoteHttp(RemoteHttp),
+    // Forward WebSocket, connects to the OneBot implementation
+    RemoteWs(RemoteWs),
+    // Reverse WebSocket, the OneBot implementation connects to us
+    ListenWs(ListenWs),
+}
+
+impl ConfigTransport {
+    // The host where the OneBot implementation runs, if known
+    fn remote_host(&self) -> Option<&str> {
+        match self {
+            Self::RemoteHttp(remote) => Some(&remote.host),
+            Self::RemoteWs(remote) => Some(&remote.host),
+            Self::ListenWs(_) => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct ConfigLagrange {
-    pub remote_http: RemoteHttp,
+    #[serde(flatten)]
+    pub transport: ConfigTransport,
     #[serde(flatten)]
     pub access_token: Option<ConfigAccessToken>,
 }
 
 impl Validator for ConfigLagrange {
     fn validate(&self) -> anyhow::Result<()> {
-        // TODO: Validate remote_http
+        // TODO: Validate remote_http and remote_ws
+        if let ConfigTransport::ListenWs(listen) = &self.transport {
+            listen.to_addr()?;
+        }
         if let Some(access_token) = &self.access_token {
             access_token.validate()?;
         }
@@ -42,13 +99,25 @@ secret_enum! {
     }
 }
 
-pub struct LagrangeOnebot<'a> {
-    config: &'a ConfigLagrange,
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 30);
+// Files sent in the request are base64-encoded into a single message, larger
+// ones would exceed the message size limits of OneBot implementations (64 MiB
+// by default for WebSocket)
+const MAX_INLINE_FILE_SIZE: u64 = 32 * 1024 * 1024;
+
+pub struct LagrangeOnebot {
+    config: ConfigLagrange,
+    // Held to keep the WebSocket connection open as long as the notifier
+    ws: OnceCell<Arc<ws::Connection>>,
 }
 
-impl<'a> LagrangeOnebot<'a> {
-    pub fn new(config: &'a ConfigLagrange) -> Self {
-        Self { config }
+impl LagrangeOnebot {
+    pub fn new(config: &ConfigLagrange) -> Self {
+        Self {
+            config: config.clone(),
+            ws: OnceCell::new(),
+        }
         // instance
         //     .version_info_retry_timeout(Duration::from_secs(5))
         //     .await
@@ -66,24 +135,39 @@ impl<'a> LagrangeOnebot<'a> {
         method: &str,
         arguments: Option<json::Value>,
     ) -> anyhow::Result<Response<T>> {
-        async {
-            let mut resp = helper::reqwest_client()?
-                .post(format!(
-                    "http://{}:{}/{method}",
-                    self.config.remote_http.host, self.config.remote_http.port
-                ))
-                .json(&arguments.unwrap_or(json::Value::Null));
-            if let Some(access_token) = self.config.access_token.as_ref() {
-                resp = resp.bearer_auth(access_token.as_secret_ref().get_str()?);
-            }
-            let resp = resp.send().await?;
+        self.request_with_timeout(method, arguments, None).await
+    }
 
-            let status = resp.status();
-            ensure!(
-                status.is_success(),
-                "response status is not success '{status}'"
-            );
-            let resp: Response<T> = resp.json().await?;
+    async fn request_with_timeout<T: DeserializeOwned + Debug>(
+        &self,
+        method: &str,
+        arguments: Option<json::Value>,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Response<T>> {
+        async {
+            let access_token = match &self.config.access_token {
+                Some(access_token) => Some(access_token.as_secret_ref().get_str()?.into_owned()),
+                None => None,
+            };
+            let resp: Response<T> = match &self.config.transport {
+                ConfigTransport::RemoteHttp(remote) => {
+                    request_http(remote, access_token, method, arguments, timeout).await?
+                }
+                transport => {
+                    let connection = self
+                        .ws
+                        .get_or_init(|| ws::connection(transport, access_token.clone()));
+                    connection.set_access_token(access_token);
+                    let resp = connection
+                        .call(
+                            method,
+                            arguments.unwrap_or(json::Value::Null),
+                            timeout.unwrap_or(REQUEST_TIMEOUT),
+                        )
+                        .await?;
+                    json::from_value(resp)?
+                }
+            };
             ensure!(
                 resp.retcode == 0,
                 "response contains error, response '{resp:?}'"
@@ -118,6 +202,12 @@ impl<'a> LagrangeOnebot<'a> {
             .map(|resp| resp.data.unwrap())
     }
 
+    pub async fn login_info(&self) -> anyhow::Result<LoginInfo> {
+        self.request("get_login_info", None)
+            .await
+            .map(|resp| resp.data.unwrap())
+    }
+
     pub async fn send_message(
         &self,
         chat: &ConfigChat,
@@ -140,6 +230,102 @@ impl<'a> LagrangeOnebot<'a> {
             .await
             .map(|resp| resp.data.unwrap())
     }
+
+    pub async fn send_forward_message(
+        &self,
+        chat: &ConfigChat,
+        nodes: Vec<ForwardNode>,
+    ) -> anyhow::Result<MessageId> {
+        let (method, args) = match chat {
+            ConfigChat::GroupId(id) => (
+                "send_group_forward_msg",
+                json!({ "group_id": *id, "messages": nodes }),
+            ),
+            ConfigChat::UserId(id) => (
+                "send_private_forward_msg",
+                json!({ "user_id": *id, "messages": nodes }),
+            ),
+        };
+        self.request::<_>(method, Some(args))
+            .await
+            .map(|resp| resp.data.unwrap())
+    }
+
+    pub async fn upload_file(&self, chat: &ConfigChat, file: &FileRef<'_>) -> anyhow::Result<()> {
+        let file_arg = file_argument(self.config.transport.remote_host(), file).await?;
+        let (method, args) = match chat {
+            ConfigChat::GroupId(id) => (
+                "upload_group_file",
+                json!({ "group_id": *id, "file": file_arg, "name": file.name }),
+            ),
+            ConfigChat::UserId(id) => (
+                "upload_private_file",
+                json!({ "user_id": *id, "file": file_arg, "name": file.name }),
+            ),
+        };
+        // Responds after the file is uploaded to QQ, which takes a while for
+        // recordings. The data is unspecified and differs between
+        // implementations
+        self.request_with_timeout::<json::Value>(method, Some(args), Some(UPLOAD_TIMEOUT))
+            .await?;
+        Ok(())
+    }
+}
+
+async fn request_http<T: DeserializeOwned + Debug>(
+    remote: &RemoteHttp,
+    access_token: Option<String>,
+    method: &str,
+    arguments: Option<json::Value>,
+    timeout: Option<Duration>,
+) -> anyhow::Result<Response<T>> {
+    let mut resp = helper::reqwest_client()?
+        .post(format!("http://{}:{}/{method}", remote.host, remote.port))
+        .json(&arguments.unwrap_or(json::Value::Null));
+    if let Some(timeout) = timeout {
+        resp = resp.timeout(timeout);
+    }
+    if let Some(access_token) = access_token {
+        resp = resp.bearer_auth(access_token);
+    }
+    let resp = resp.send().await?;
+
+    let status = resp.status();
+    ensure!(
+        status.is_success(),
+        "response status is not success '{status}'"
+    );
+    Ok(resp.json().await?)
+}
+
+// Lagrange on the same host reads the file from the path, otherwise the content
+// has to be sent in the request, up to `MAX_INLINE_FILE_SIZE`
+async fn file_argument(host: Option<&str>, file: &FileRef<'_>) -> anyhow::Result<String> {
+    let is_local = host.is_some_and(|host| {
+        host == "localhost"
+            || host
+                .trim_matches(['[', ']'])
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    });
+    match file.path {
+        Some(path) if is_local => {
+            let path = tokio::fs::canonicalize(path).await.map_err(|err| {
+                anyhow!("failed to canonicalize path '{}': {err}", path.display())
+            })?;
+            Ok(path.to_string_lossy().into_owned())
+        }
+        _ => {
+            ensure!(
+                file.size <= MAX_INLINE_FILE_SIZE,
+                "file '{}' is too large ({}) to be sent in the request, the limit is {}. Run Lagrange on the same host and connect to it via 'localhost' so that it reads the file from the path",
+                file.name,
+                humansize::format_size(file.size, humansize::BINARY),
+                humansize::format_size(MAX_INLINE_FILE_SIZE, humansize::BINARY)
+            );
+            Ok(format!("base64://{}", BASE64_STANDARD.encode(&file.data)))
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
@@ -157,6 +343,12 @@ pub struct VersionInfo {
     pub nt_protocol: String,
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LoginInfo {
+    pub user_id: u64,
+    pub nickname: String,
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct MessageId {
     pub message_id: i64,
@@ -216,6 +408,30 @@ impl MessageBuilder {
         self
     }
 
+    pub fn ref_video(&mut self, file: impl Into<String>) -> &mut Self {
+        self.0 .0.push(MessageSegment::Video(MessageSegmentVideo {
+            file: file.into(),
+        }));
+        self
+    }
+
+    pub fn video(mut self, file: impl Into<String>) -> Self {
+        self.ref_video(file);
+        self
+    }
+
+    pub fn ref_reply(&mut self, message_id: i64) -> &mut Self {
+        self.0 .0.push(MessageSegment::Reply(MessageSegmentReply {
+            id: message_id.to_string(),
+        }));
+        self
+    }
+
+    pub fn reply(mut self, message_id: i64) -> Self {
+        self.ref_reply(message_id);
+        self
+    }
+
     pub fn ref_mention(&mut self, user_id: u64, newline: bool) -> &mut Self {
         if newline {
             self.ref_text("\n");
@@ -256,6 +472,10 @@ impl MessageBuilder {
         self
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0 .0.is_empty()
+    }
+
     pub fn ref_build(&self) -> Message {
         self.0.clone()
     }
@@ -265,11 +485,55 @@ impl MessageBuilder {
     }
 }
 
+// A custom node of a merged forward message, it is displayed as if `name` sent
+// the `content`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForwardNode {
+    pub name: String,
+    pub uin: u64,
+    pub content: Message,
+}
+
+impl ForwardNode {
+    pub fn new(name: impl Into<String>, uin: u64, content: Message) -> Self {
+        Self {
+            name: name.into(),
+            uin,
+            content,
+        }
+    }
+}
+
+impl Serialize for ForwardNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Data<'a> {
+            name: &'a str,
+            uin: String,
+            content: &'a Message,
+        }
+
+        let mut node = serializer.serialize_struct("ForwardNode", 2)?;
+        node.serialize_field("type", "node")?;
+        node.serialize_field(
+            "data",
+            &Data {
+                name: &self.name,
+                uin: self.uin.to_string(),
+                content: &self.content,
+            },
+        )?;
+        node.end()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(tag = "type", content = "data", rename_all = "snake_case")]
 enum MessageSegment {
     Text(MessageSegmentText),
     Image(MessageSegmentImage),
+    Video(MessageSegmentVideo),
+    Reply(MessageSegmentReply),
     At(MessageSegmentAt),
 }
 
@@ -283,6 +547,16 @@ struct MessageSegmentImage {
     file: String,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct MessageSegmentVideo {
+    file: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct MessageSegmentReply {
+    id: String,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 enum MessageSegmentAt {
     UserId(u64),
@@ -300,3 +574,74 @@ impl Serialize for MessageSegmentAt {
         at.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn file_arguments() {
+        let path = std::env::current_dir().unwrap().join("Cargo.toml");
+        let file = FileRef {
+            path: Some(&path),
+            name: "Cargo.toml".into(),
+            data: Bytes::from_static(b"meow"),
+            size: 4,
+        };
+        assert_eq!(
+            file_argument(Some("127.0.0.1"), &file).await.unwrap(),
+            path.to_string_lossy()
+        );
+        assert_eq!(
+            file_argument(Some("192.168.1.2"), &file).await.unwrap(),
+            "base64://bWVvdw=="
+        );
+
+        assert_eq!(
+            file_argument(None, &file).await.unwrap(),
+            "base64://bWVvdw=="
+        );
+
+        let file = FileRef { path: None, ..file };
+        assert_eq!(
+            file_argument(Some("localhost"), &file).await.unwrap(),
+            "base64://bWVvdw=="
+        );
+
+        let file = FileRef {
+            size: MAX_INLINE_FILE_SIZE + 1,
+            ..file
+        };
+        assert!(file_argument(None, &file).await.is_err());
+    }
+
+    #[test]
+    fn serialize_forward_nodes() {
+        let node = ForwardNode::new(
+            "meow",
+            114514,
+            Message::builder()
+                .reply(42)
+                .text("hi")
+                .video("https://example.com/v.mp4")
+                .build(),
+        );
+        assert_eq!(
+            json::to_value(&node).unwrap(),
+            json!({
+                "type": "node",
+                "data": {
+                    "name": "meow",
+                    "uin": "114514",
+                    "content": [
+                        { "type": "reply", "data": { "id": "42" } },
+                        { "type": "text", "data": { "text": "hi" } },
+                        { "type": "video", "data": { "file": "https://example.com/v.mp4" } },
+                    ]
+                }
+            })
+        );
+    }
+}
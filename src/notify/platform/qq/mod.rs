@@ -1,21 +1,35 @@
 pub mod lagrange;
-
-use std::{borrow::Cow, collections::HashMap, fmt, future::Future, pin::Pin};
+mod ws;
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+};
 
 use anyhow::{anyhow, ensure};
 use serde::Deserialize;
 use spdlog::prelude::*;
+use tokio::sync::{Mutex, OnceCell};
 
 use crate::{
     config::{self, Accessor, Config, Validator},
-    notify::{platform::qq::lagrange::MessageBuilder, NotifierTrait},
+    notify::{
+        platform::qq::lagrange::{ForwardNode, MessageBuilder},
+        NotifierTrait,
+    },
     platform::{PlatformMetadata, PlatformTrait},
     source::{
-        LiveStatus, LiveStatusKind, Notification, NotificationKind, Post, PostAttachment, PostsRef,
-        RepostFrom, StatusSource,
+        DocumentRef, FileRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        PlaybackFormat, PlaybackRef, Post, PostAttachment, PostsRef, RepostFrom, StatusSource,
     },
 };
 
+// Posts with more images than this are sent as a merged forward
+const MAX_INLINE_IMAGES: usize = 4;
+
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct ConfigGlobal {
     pub account: HashMap<String, Accessor<ConfigAccount>>,
@@ -125,7 +139,12 @@ impl fmt::Display for ConfigChat {
 
 pub struct Notifier {
     params: config::Accessor<ConfigParams>,
-    backend: lagrange::LagrangeOnebot<'static>,
+    backend: lagrange::LagrangeOnebot,
+    // Names of the files announced but not yet uploaded, so retries from the outbox
+    // don't announce them again
+    announced_files: Mutex<HashSet<String>>,
+    // Looked up once for the sender of merged forward nodes
+    uin: OnceCell<u64>,
 }
 
 impl PlatformTrait for Notifier {
@@ -159,6 +178,8 @@ impl Notifier {
         Self {
             params,
             backend: lagrange,
+            announced_files: Mutex::new(HashSet::new()),
+            uin: OnceCell::new(),
         }
     }
 
@@ -173,10 +194,24 @@ impl Notifier {
                 self.notify_live_title(live_status, notification.source)
                     .await
             }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.notify_live_area(live_status, old_area, notification.source)
+                    .await
+            }
             NotificationKind::Posts(posts) => self.notify_posts(posts, notification.source).await,
+            NotificationKind::PostEdited(post, _old) => {
+                self.notify_post_edited(post, notification.source).await
+            }
+            NotificationKind::PostDeleted(post) => {
+                self.notify_post_deleted(post, notification.source).await
+            }
             NotificationKind::Log(message) => self.notify_log(message).await,
-            NotificationKind::Playback(_) => unimplemented!(),
-            NotificationKind::Document(_) => unimplemented!(),
+            NotificationKind::Playback(playback) => {
+                self.notify_playback(playback, notification.source).await
+            }
+            NotificationKind::Document(document) => {
+                self.notify_document(document, notification.source).await
+            }
         }
     }
 
@@ -246,6 +281,43 @@ impl Notifier {
         Ok(())
     }
 
+    async fn notify_live_area(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let Some(area) = &live_status.area else {
+            return Ok(());
+        };
+
+        let message = lagrange::Message::builder()
+            .text(format!(
+                "[{}] 🏷️ {}{} ➡️ {}\n{}",
+                source.platform.display_name,
+                if self.params.notifications.author_name {
+                    Cow::Owned(format!("[{}] ", live_status.streamer_name))
+                } else {
+                    Cow::Borrowed("")
+                },
+                old_area,
+                area,
+                live_status.live_url
+            ))
+            .mention_all_if(self.params.mention_all, true)
+            .build();
+
+        self.backend
+            .send_message(&self.params.chat, message)
+            .await?;
+
+        Ok(())
+    }
+
     async fn notify_posts(
         &self,
         posts: &PostsRef<'_>,
@@ -258,7 +330,7 @@ impl Notifier {
 
         let mut errors = vec![];
         for post in &posts.0 {
-            if let Err(err) = self.notify_post(post, source).await {
+            if let Err(err) = self.notify_post(post, source, "").await {
                 errors.push(err);
             }
         }
@@ -266,53 +338,63 @@ impl Notifier {
         Ok(())
     }
 
-    async fn notify_post(&self, post: &Post, source: &StatusSource) -> anyhow::Result<()> {
-        let mut builder = lagrange::Message::builder();
-        builder.ref_text(format!("[{}] ", source.platform.display_name));
-
-        fn append_media<'a>(
-            builder: &mut MessageBuilder,
-            attachments: impl Iterator<Item = &'a PostAttachment>,
-        ) {
-            builder.ref_images(attachments.filter_map(|attachment| match attachment {
-                PostAttachment::Image(image) => Some(image.media_url.as_str()),
-                PostAttachment::Video(_) => None, // TODO: Handle videos
-            }));
+    // Messages are not tracked, so send a new message marked as edited instead
+    async fn notify_post_edited(&self, post: &Post, source: &StatusSource) -> anyhow::Result<()> {
+        if !self.params.notifications.post_edited {
+            info!("post_edited notification is disabled, skip notifying");
+            return Ok(());
         }
+        self.notify_post(post, source, "✏️ ").await
+    }
 
-        match &post.repost_from {
-            Some(RepostFrom::Recursion(repost_from)) => {
-                if !post.content.is_empty() {
-                    builder.ref_text("💬 ");
-                    if self.params.notifications.author_name {
-                        builder.ref_text(format!("{}: ", post.user.nickname));
-                    }
-                    append_media(&mut builder, post.attachments(false));
-                    builder.ref_text(post.content.fallback());
-                    builder.ref_text("\n\n");
-                }
-
-                builder.ref_text("🔁 ");
-                builder.ref_text(format!("{}: ", repost_from.user.nickname));
-                append_media(&mut builder, repost_from.attachments(false));
-                builder.ref_text(repost_from.content.fallback());
-            }
-            None => {
-                if self.params.notifications.author_name {
-                    builder.ref_text(format!("{}: ", post.user.nickname));
-                }
-                append_media(&mut builder, post.attachments(false));
-                builder.ref_text(post.content.fallback());
-            }
+    async fn notify_post_deleted(&self, post: &Post, source: &StatusSource) -> anyhow::Result<()> {
+        if !self.params.notifications.post_deleted {
+            info!("post_deleted notification is disabled, skip notifying");
+            return Ok(());
         }
-        builder.ref_text("\n");
-        for url in post
-            .urls_recursive()
+        self.notify_post(post, source, "🗑️ ").await
+    }
+
+    async fn notify_post(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let num_images = post
+            .attachments_recursive(false)
             .into_iter()
-            .filter_map(|url| url.as_clickable())
-        {
-            builder.ref_text(format!("\n{}: {}", url.display, url.url));
+            .filter(|attachment| matches!(attachment, PostAttachment::Image(_)))
+            .count();
+
+        if post.repost_from.is_some() || num_images > MAX_INLINE_IMAGES {
+            self.notify_post_forward(post, source, annotation).await
+        } else {
+            self.notify_post_inline(post, source, annotation).await
+        }
+    }
+
+    async fn notify_post_inline(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let mut builder = lagrange::Message::builder();
+        builder.ref_text(format!("[{}] {annotation}", source.platform.display_name));
+        if self.params.notifications.author_name {
+            builder.ref_text(format!("{}: ", post.user.nickname));
         }
+        builder.ref_images(
+            post.attachments(false)
+                .filter_map(|attachment| match attachment {
+                    PostAttachment::Image(image) => Some(image.media_url.as_str()),
+                    PostAttachment::Video(_) => None,
+                }),
+        );
+        builder.ref_text(post.content.fallback());
+        builder.ref_text("\n\n");
+        append_urls(&mut builder, post);
 
         self.backend
             .send_message(
@@ -323,6 +405,105 @@ impl Notifier {
             )
             .await?;
 
+        // QQ displays a video only if it's the sole segment of a message. The text is
+        // already sent, failing here would make the outbox send it again, so only log
+        for attachment in post.attachments(false) {
+            if let PostAttachment::Video(video) = attachment {
+                if let Err(err) = self
+                    .backend
+                    .send_message(
+                        &self.params.chat,
+                        lagrange::Message::builder().video(&video.media_url).build(),
+                    )
+                    .await
+                {
+                    error!("failed to send video '{}' to QQ: {err}", video.media_url);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reposts and image-heavy posts are sent as a merged forward, each part of the
+    // post becomes a node, so the chat is not flooded by a huge message
+    async fn notify_post_forward(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let uin = *self
+            .uin
+            .get_or_try_init(|| async { self.backend.login_info().await.map(|info| info.user_id) })
+            .await?;
+        let platform_name = source.platform.display_name;
+
+        let mut nodes = vec![ForwardNode::new(
+            platform_name,
+            uin,
+            lagrange::Message::text(format!("[{platform_name}] {annotation}")),
+        )];
+
+        let mut append_post = |post: &Post, name: &str, prefix: &str| {
+            if !post.content.is_empty() {
+                nodes.push(ForwardNode::new(
+                    name,
+                    uin,
+                    lagrange::Message::text(format!("{prefix}{}", post.content.fallback())),
+                ));
+            }
+            nodes.extend(post.attachments(false).map(|attachment| {
+                let content = match attachment {
+                    PostAttachment::Image(image) => {
+                        lagrange::Message::builder().image(&image.media_url)
+                    }
+                    PostAttachment::Video(video) => {
+                        lagrange::Message::builder().video(&video.media_url)
+                    }
+                };
+                ForwardNode::new(name, uin, content.build())
+            }));
+        };
+
+        let name = if self.params.notifications.author_name {
+            post.user.nickname.as_str()
+        } else {
+            platform_name
+        };
+        match &post.repost_from {
+            Some(RepostFrom::Recursion(repost_from)) => {
+                append_post(post, name, "💬 ");
+                append_post(repost_from, &repost_from.user.nickname, "🔁 ");
+            }
+            None => append_post(post, name, ""),
+        }
+
+        let mut builder = lagrange::Message::builder();
+        append_urls(&mut builder, post);
+        if !builder.is_empty() {
+            nodes.push(ForwardNode::new(platform_name, uin, builder.build()));
+        }
+
+        let message_id = self
+            .backend
+            .send_forward_message(&self.params.chat, nodes)
+            .await?
+            .message_id;
+
+        // Merged forwards cannot contain mentions, mention in a reply instead
+        if self.params.mention_all {
+            self.backend
+                .send_message(
+                    &self.params.chat,
+                    lagrange::Message::builder()
+                        .reply(message_id)
+                        .mention_all(false)
+                        .build(),
+                )
+                .await?;
+        }
+
         Ok(())
     }
 
@@ -343,4 +524,83 @@ impl Notifier {
 
         Ok(())
     }
+
+    async fn notify_playback(
+        &self,
+        playback: &PlaybackRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.playback {
+            info!("playback notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let playback = playback.get(PlaybackFormat::Mp4).await?;
+        self.notify_file(&playback.file, source, "🎥").await
+    }
+
+    async fn notify_document(
+        &self,
+        document: &DocumentRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.document {
+            info!("document notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.notify_file(&document.file, source, "📊").await
+    }
+
+    // Messages cannot be edited to attach the file like Telegram, so announce the
+    // upload first, as it takes a while for recordings
+    async fn notify_file(
+        &self,
+        file: &FileRef<'_>,
+        source: &StatusSource,
+        emoji: &str,
+    ) -> anyhow::Result<()> {
+        if !self.announced_files.lock().await.contains(&file.name) {
+            self.backend
+                .send_message(
+                    &self.params.chat,
+                    lagrange::Message::text(make_file_text(emoji, file, source)),
+                )
+                .await?;
+            self.announced_files.lock().await.insert(file.name.clone());
+        }
+
+        trace!("uploading file to QQ '{file}'");
+        self.backend
+            .upload_file(&self.params.chat, file)
+            .await
+            .map_err(|err| anyhow!("failed to upload file '{file}' to QQ: {err}"))?;
+        trace!("finished uploading file to QQ '{file}'");
+
+        self.announced_files.lock().await.remove(&file.name);
+        Ok(())
+    }
+}
+
+fn append_urls(builder: &mut MessageBuilder, post: &Post) {
+    let mut urls = post
+        .urls_recursive()
+        .into_iter()
+        .filter_map(|url| url.as_clickable())
+        .peekable();
+    while let Some(url) = urls.next() {
+        builder.ref_text(format!("{}: {}", url.display, url.url));
+        if urls.peek().is_some() {
+            builder.ref_text("\n");
+        }
+    }
+}
+
+fn make_file_text(emoji: &str, file: &FileRef<'_>, source: &StatusSource) -> String {
+    format!(
+        "[{}] {emoji} {} ({})",
+        source.platform.display_name,
+        file.name,
+        humansize::format_size(file.size, humansize::BINARY)
+    )
 }
@@ -0,0 +1,424 @@
+// OneBot v11 WebSocket transport, either connecting to the implementation
+// (forward) or accepting the connection from it (reverse). Requests are matched
+// with responses by the `echo` field, events pushed by the implementation are
+// ignored
+//
+// Protocol reference: https://github.com/botuniverse/onebot-11/blob/master/communication/ws.md
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock, Weak},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{mpsc, oneshot, Mutex},
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        client::IntoClientRequest,
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header, StatusCode},
+        Message,
+    },
+    WebSocketStream,
+};
+
+use super::lagrange::ConfigTransport;
+
+const RECONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+// Connections are shared between notifiers of the same account, as a reverse
+// WebSocket can only bind the address once. A connection is closed once all its
+// notifiers are gone, e.g. the transport is changed on reloading.
+static CONNECTIONS: Lazy<StdMutex<Vec<Weak<Connection>>>> = Lazy::new(Default::default);
+
+pub fn connection(transport: &ConfigTransport, access_token: Option<String>) -> Arc<Connection> {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    connections.retain(|connection| connection.strong_count() > 0);
+    if let Some(connection) = connections
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|connection| connection.transport == *transport)
+    {
+        connection.set_access_token(access_token);
+        return connection;
+    }
+
+    let connection = Arc::new(Connection::spawn(transport.clone(), access_token));
+    connections.push(Arc::downgrade(&connection));
+    connection
+}
+
+struct Call {
+    action: String,
+    params: json::Value,
+    responder: oneshot::Sender<json::Value>,
+}
+
+pub struct Connection {
+    transport: ConfigTransport,
+    calls: mpsc::UnboundedSender<Call>,
+    access_token: Arc<StdRwLock<Option<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Sessions in progress end by themselves as the calls sender is dropped
+        self.task.abort();
+    }
+}
+
+impl Connection {
+    fn spawn(transport: ConfigTransport, access_token: Option<String>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let access_token = Arc::new(StdRwLock::new(access_token));
+
+        let receiver = Arc::new(Mutex::new(receiver));
+        let task = match &transport {
+            ConfigTransport::RemoteWs(remote) => {
+                let url = format!("ws://{}:{}/", remote.host, remote.port);
+                tokio::spawn(run_forward(url, Arc::clone(&access_token), receiver))
+            }
+            ConfigTransport::ListenWs(listen) => tokio::spawn(run_reverse(
+                listen.to_addr().unwrap(),
+                Arc::clone(&access_token),
+                receiver,
+            )),
+            ConfigTransport::RemoteHttp(_) => unreachable!(),
+        };
+
+        Self {
+            transport,
+            calls: sender,
+            access_token,
+            task,
+        }
+    }
+
+    // Updated on each call, so that it follows config reloads
+    pub fn set_access_token(&self, access_token: Option<String>) {
+        *self.access_token.write().unwrap() = access_token;
+    }
+
+    // Calls made while disconnected are sent once reconnected, unless they have
+    // timed out
+    pub async fn call(
+        &self,
+        action: &str,
+        params: json::Value,
+        timeout: Duration,
+    ) -> anyhow::Result<json::Value> {
+        let (responder, response) = oneshot::channel();
+        self.calls
+            .send(Call {
+                action: action.into(),
+                params,
+                responder,
+            })
+            .map_err(|_| anyhow!("connection task exited"))?;
+
+        tokio::time::timeout(timeout, response)
+            .await
+            .map_err(|_| anyhow!("timeout while waiting for response"))?
+            .map_err(|_| anyhow!("connection lost before response"))
+    }
+}
+
+async fn run_forward(
+    url: String,
+    access_token: Arc<StdRwLock<Option<String>>>,
+    calls: Arc<Mutex<mpsc::UnboundedReceiver<Call>>>,
+) {
+    let mut interval = RECONNECT_MIN_INTERVAL;
+    loop {
+        let result: anyhow::Result<()> = async {
+            let mut request = url.as_str().into_client_request()?;
+            if let Some(access_token) = access_token.read().unwrap().as_ref() {
+                request.headers_mut().insert(
+                    header::AUTHORIZATION,
+                    format!("Bearer {access_token}").parse()?,
+                );
+            }
+            let (stream, _) = tokio_tungstenite::connect_async(request)
+                .await
+                .map_err(|err| anyhow!("failed to connect: {err}"))?;
+            info!("connected to OneBot WebSocket '{url}'");
+            interval = RECONNECT_MIN_INTERVAL;
+
+            run_session(stream, &mut *calls.lock().await).await
+        }
+        .await;
+
+        match result {
+            Ok(()) => return,
+            Err(err) => warn!(
+                "OneBot WebSocket '{url}' disconnected: {err}, reconnect in {}s",
+                interval.as_secs()
+            ),
+        }
+        tokio::time::sleep(interval).await;
+        interval = (interval * 2).min(RECONNECT_MAX_INTERVAL);
+    }
+}
+
+async fn run_reverse(
+    addr: SocketAddr,
+    access_token: Arc<StdRwLock<Option<String>>>,
+    calls: Arc<Mutex<mpsc::UnboundedReceiver<Call>>>,
+) {
+    // The address may still be held by the previous connection for a moment after
+    // reloading, so keep retrying
+    let mut interval = RECONNECT_MIN_INTERVAL;
+    let listener = loop {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => break listener,
+            Err(err) => error!(
+                "failed to listen OneBot reverse WebSocket on '{addr}': {err}, retry in {}s",
+                interval.as_secs()
+            ),
+        }
+        tokio::time::sleep(interval).await;
+        interval = (interval * 2).min(RECONNECT_MAX_INTERVAL);
+    };
+    info!("listening OneBot reverse WebSocket on '{addr}'");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!("failed to accept OneBot reverse WebSocket: {err}");
+                continue;
+            }
+        };
+
+        let access_token = Arc::clone(&access_token);
+        let calls = Arc::clone(&calls);
+        tokio::spawn(async move {
+            #[allow(clippy::result_large_err)]
+            let authorize = |request: &Request, response: Response| {
+                authorize(request, access_token.read().unwrap().as_deref())?;
+                check_role(request)?;
+                Ok(response)
+            };
+            let stream = match tokio_tungstenite::accept_hdr_async(stream, authorize).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("failed to handshake OneBot reverse WebSocket from '{peer}': {err}");
+                    return;
+                }
+            };
+            info!("OneBot reverse WebSocket connected from '{peer}'");
+
+            // Only one connection is served at a time, the others wait until it's
+            // disconnected
+            if let Err(err) = run_session(stream, &mut *calls.lock().await).await {
+                warn!("OneBot reverse WebSocket from '{peer}' disconnected: {err}");
+            }
+        });
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn authorize(request: &Request, access_token: Option<&str>) -> Result<(), ErrorResponse> {
+    let Some(access_token) = access_token else {
+        return Ok(());
+    };
+
+    let from_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let from_query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("access_token="))
+    });
+
+    if from_header.or(from_query) == Some(access_token) {
+        Ok(())
+    } else {
+        let mut response = ErrorResponse::new(None);
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(response)
+    }
+}
+
+// `Event` connections only push events and never answer the calls, they must
+// not take the calls from `API` or `Universal` ones. Missing role is treated as
+// `Universal`, for implementations not sending it.
+#[allow(clippy::result_large_err)]
+fn check_role(request: &Request) -> Result<(), ErrorResponse> {
+    let role = request
+        .headers()
+        .get("X-Client-Role")
+        .and_then(|value| value.to_str().ok());
+    match role {
+        None => Ok(()),
+        Some(role)
+            if role.eq_ignore_ascii_case("API") || role.eq_ignore_ascii_case("Universal") =>
+        {
+            Ok(())
+        }
+        Some(_) => {
+            let mut response = ErrorResponse::new(None);
+            *response.status_mut() = StatusCode::FORBIDDEN;
+            Err(response)
+        }
+    }
+}
+
+// Returns `Ok` only if all notifiers are gone
+async fn run_session<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: WebSocketStream<S>,
+    calls: &mut mpsc::UnboundedReceiver<Call>,
+) -> anyhow::Result<()> {
+    let mut pending = HashMap::<String, oneshot::Sender<json::Value>>::new();
+    let mut next_echo = 0_u64;
+
+    loop {
+        tokio::select! {
+            call = calls.recv() => {
+                let Some(call) = call else {
+                    return Ok(());
+                };
+                if call.responder.is_closed() {
+                    continue;
+                }
+
+                let echo = next_echo.to_string();
+                next_echo += 1;
+                let frame = json!({ "action": call.action, "params": call.params, "echo": echo });
+                pending.insert(echo, call.responder);
+                stream
+                    .send(Message::text(frame.to_string()))
+                    .await
+                    .map_err(|err| anyhow!("failed to send request: {err}"))?;
+            }
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(frame))) => bail!("connection closed by peer: {frame:?}"),
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => bail!("failed to receive message: {err}"),
+                    None => bail!("connection closed"),
+                };
+                let mut value = match json::from_str::<json::Value>(&text) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        warn!("failed to parse OneBot WebSocket message: {err}");
+                        continue;
+                    }
+                };
+                match value.get_mut("echo").map(json::Value::take) {
+                    Some(echo) => match echo.as_str().and_then(|echo| pending.remove(echo)) {
+                        Some(responder) => _ = responder.send(value),
+                        None => trace!("OneBot WebSocket response with unknown echo '{echo}'"),
+                    },
+                    None => trace!("OneBot WebSocket event ignored"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    use super::*;
+
+    #[test]
+    fn authorize_reverse() {
+        let request = |uri: &str, header: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(header) = header {
+                request = request.header(header::AUTHORIZATION, header);
+            }
+            request.body(()).unwrap()
+        };
+
+        assert!(authorize(&request("/", None), None).is_ok());
+        assert!(authorize(&request("/", None), Some("meow")).is_err());
+        assert!(authorize(&request("/", Some("Bearer meow")), Some("meow")).is_ok());
+        assert!(authorize(&request("/", Some("Bearer woof")), Some("meow")).is_err());
+        assert!(authorize(&request("/?access_token=meow", None), Some("meow")).is_ok());
+    }
+
+    #[test]
+    fn client_role() {
+        let request = |role: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(role) = role {
+                request = request.header("X-Client-Role", role);
+            }
+            request.body(()).unwrap()
+        };
+
+        assert!(check_role(&request(None)).is_ok());
+        assert!(check_role(&request(Some("API"))).is_ok());
+        assert!(check_role(&request(Some("Universal"))).is_ok());
+        assert!(check_role(&request(Some("Event"))).is_err());
+    }
+
+    #[tokio::test]
+    async fn session() {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+        let mut client = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let session = tokio::spawn(async move { run_session(server, &mut receiver).await });
+
+        let call = |action: &str| {
+            let (responder, response) = oneshot::channel();
+            sender
+                .send(Call {
+                    action: action.into(),
+                    params: json!({}),
+                    responder,
+                })
+                .unwrap();
+            response
+        };
+        let first = call("first");
+        let second = call("second");
+
+        let mut echoes = vec![];
+        for action in ["first", "second"] {
+            let Some(Ok(Message::Text(text))) = client.next().await else {
+                panic!("request is not received")
+            };
+            let frame = json::from_str::<json::Value>(&text).unwrap();
+            assert_eq!(frame["action"], action);
+            echoes.push(frame["echo"].clone());
+        }
+
+        // Events and unknown echoes are ignored
+        for frame in [
+            json!({ "post_type": "meta_event" }),
+            json!({ "echo": "unknown", "data": "unknown" }),
+            json!({ "echo": echoes[1], "data": "second" }),
+        ] {
+            client.send(Message::text(frame.to_string())).await.unwrap();
+        }
+        assert_eq!(second.await.unwrap()["data"], "second");
+
+        // Pending calls fail once disconnected
+        drop(client);
+        assert!(session.await.unwrap().is_err());
+        assert!(first.await.is_err());
+    }
+}
@@ -0,0 +1,755 @@
+mod request;
+
+use std::{borrow::Cow, collections::VecDeque, fmt, future::Future, pin::Pin, time::SystemTime};
+
+use anyhow::{anyhow, bail, ensure};
+use request::*;
+use serde::Deserialize;
+use spdlog::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::NotifierTrait,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        KindPayload, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        NotificationPayload, Post, PostAttachment, PostContent, PostContentPart,
+        PostPlatformUniqueId, PostUrl, PostsRef, RepostFrom, StatusSource,
+    },
+};
+
+// Either an incoming webhook, or a bot token with a channel. Only the latter is
+// able to update sent messages
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+    #[serde(flatten)]
+    pub bot_token: Option<ConfigBotToken>,
+    // Channel ID, e.g. "C0123456789"
+    pub channel: Option<String>,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        match (&self.webhook, &self.bot_token) {
+            (Some(webhook), None) => {
+                webhook.validate()?;
+                let url = webhook
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read webhook URL for Slack: {err}"))?;
+                ensure!(url.starts_with("https://"), "invalid webhook URL");
+                ensure!(
+                    self.channel.is_none(),
+                    "channel is not configurable for incoming webhooks"
+                );
+            }
+            (None, Some(bot_token)) => {
+                bot_token.validate()?;
+                ensure!(
+                    self.channel
+                        .as_ref()
+                        .is_some_and(|channel| !channel.is_empty()),
+                    "channel is required for bot token"
+                );
+            }
+            (Some(_), Some(_)) => bail!("webhook URL and bot token are mutually exclusive"),
+            (None, None) => bail!("either webhook URL or bot token is required"),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.channel {
+            Some(channel) => write!(f, "slack:{channel}"),
+            // The webhook URL is the secret
+            None => write!(f, "slack:webhook"),
+        }
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            webhook: new.webhook.or(self.webhook),
+            bot_token: new.bot_token.or(self.bot_token),
+            channel: new.channel.or(self.channel),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+    #[serde(flatten)]
+    pub bot_token: Option<ConfigBotToken>,
+    pub channel: Option<String>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigWebhook {
+        WebhookUrl(String),
+    }
+}
+
+// `secret_enum!` can only be used once per module
+mod bot_token {
+    use super::*;
+
+    secret_enum! {
+        #[derive(Clone, Debug, PartialEq, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum ConfigBotToken {
+            BotToken(String),
+        }
+    }
+}
+pub use bot_token::ConfigBotToken;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+    current_live: Mutex<Option<CurrentLive>>,
+    // The latest first
+    sent_posts: Mutex<VecDeque<(PostPlatformUniqueId, MessageRef)>>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Slack",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            current_live: Mutex::new(None),
+            sent_posts: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn bot_token(&self) -> anyhow::Result<Option<Cow<'_, str>>> {
+        self.params
+            .bot_token
+            .as_ref()
+            .map(|bot_token| {
+                bot_token
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read bot token for Slack: {err}"))
+            })
+            .transpose()
+    }
+
+    // Returns the reference for updating the message, if sent with a bot token
+    async fn send(&self, message: &Message) -> anyhow::Result<Option<MessageRef>> {
+        if let Some(bot_token) = self.bot_token()? {
+            let channel = self
+                .params
+                .channel
+                .as_deref()
+                .ok_or_else(|| anyhow!("channel is required for bot token"))?;
+            return Api::new(&bot_token)
+                .post_message(channel, message)
+                .await
+                .map(Some)
+                .map_err(|err| anyhow!("failed to post message to Slack: {err}"));
+        }
+
+        let webhook = self
+            .params
+            .webhook
+            .as_ref()
+            .ok_or_else(|| anyhow!("either webhook URL or bot token is required"))?;
+        let url = webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for Slack: {err}"))?;
+        post_webhook(&url, message)
+            .await
+            .map_err(|err| anyhow!("failed to post message to Slack webhook: {err}"))?;
+        Ok(None)
+    }
+
+    async fn update(&self, target: &MessageRef, message: &Message) -> anyhow::Result<()> {
+        // References only come from messages sent with a bot token
+        let bot_token = self
+            .bot_token()?
+            .ok_or_else(|| anyhow!("updating messages requires a bot token"))?;
+        Api::new(&bot_token)
+            .update(target, message)
+            .await
+            .map_err(|err| anyhow!("failed to update message on Slack: {err}"))?;
+        Ok(())
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        match &notification.kind {
+            NotificationKind::LiveOnline(live_status) => {
+                self.notify_live(live_status, notification.source).await
+            }
+            NotificationKind::LiveTitle(live_status, _old_title) => {
+                self.notify_live_title(live_status, notification.source)
+                    .await
+            }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.notify_live_area(live_status, old_area, notification.source)
+                    .await
+            }
+            NotificationKind::Posts(posts) => self.notify_posts(posts, notification.source).await,
+            NotificationKind::PostEdited(post, _old) => {
+                if !self.params.notifications.post_edited {
+                    info!("post_edited notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post_changed(post, notification.source, "✏️ ")
+                    .await
+            }
+            NotificationKind::PostDeleted(post) => {
+                if !self.params.notifications.post_deleted {
+                    info!("post_deleted notification is disabled, skip notifying");
+                    return Ok(());
+                }
+                self.notify_post_changed(post, notification.source, "🗑️ ")
+                    .await
+            }
+            NotificationKind::Log(message) => self.notify_log(message).await,
+            NotificationKind::Playback(_) | NotificationKind::Document(_) => {
+                self.notify_file(notification).await
+            }
+        }
+    }
+
+    async fn notify_live(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_online {
+            info!("live_online notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        match live_status.kind {
+            LiveStatusKind::Online { start_time } => {
+                let title_history = VecDeque::from([live_status.title.clone()]);
+                let start_time = start_time.unwrap_or_else(SystemTime::now);
+
+                let message = make_live_message(
+                    self.params.notifications.author_name,
+                    &title_history,
+                    live_status,
+                    source,
+                    start_time,
+                );
+                // Messages sent by incoming webhooks cannot be updated
+                if let Some(message_ref) = self.send(&message).await? {
+                    *self.current_live.lock().await = Some(CurrentLive {
+                        start_time,
+                        message_ref,
+                        title_history,
+                    });
+                }
+                Ok(())
+            }
+            LiveStatusKind::Offline | LiveStatusKind::Banned => {
+                if let Some(current_live) = self.current_live.lock().await.take() {
+                    let message = make_live_message(
+                        self.params.notifications.author_name,
+                        &current_live.title_history,
+                        live_status,
+                        source,
+                        current_live.start_time,
+                    );
+                    self.update(&current_live.message_ref, &message).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn notify_live_title(
+        &self,
+        live_status: &LiveStatus,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        // Update the last message
+        if let Some(current_live) = self.current_live.lock().await.as_mut() {
+            current_live
+                .title_history
+                .push_front(live_status.title.clone());
+
+            let message = make_live_message(
+                self.params.notifications.author_name,
+                &current_live.title_history,
+                live_status,
+                source,
+                current_live.start_time,
+            );
+            self.update(&current_live.message_ref, &message).await?;
+        }
+
+        // Send a new message
+        if !self.params.notifications.live_title {
+            info!("live_title notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let text = format!(
+            "[{}] ✏️ {}{}",
+            source.platform.display_name,
+            self.author_prefix(live_status),
+            link(&live_status.title, &live_status.live_url)
+        );
+        self.send(&Message::new(
+            format!("✏️ {}", live_status.title),
+            vec![section(text)],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn notify_live_area(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let Some(area) = &live_status.area else {
+            return Ok(());
+        };
+
+        let text = format!(
+            "[{}] 🏷️ {}{}",
+            source.platform.display_name,
+            self.author_prefix(live_status),
+            link(&format!("{old_area} ➡️ {area}"), &live_status.live_url)
+        );
+        self.send(&Message::new(
+            format!("🏷️ {old_area} ➡️ {area}"),
+            vec![section(text)],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    fn author_prefix(&self, live_status: &LiveStatus) -> String {
+        if self.params.notifications.author_name {
+            format!("[{}] ", escape(&live_status.streamer_name))
+        } else {
+            String::new()
+        }
+    }
+
+    async fn notify_posts(
+        &self,
+        posts: &PostsRef<'_>,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.post {
+            info!("post notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let mut errors = vec![];
+        for post in &posts.0 {
+            if let Err(err) = self.notify_post(post, source, "").await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn notify_post(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let message = make_post_message(
+            self.params.notifications.author_name,
+            post,
+            source,
+            annotation,
+        );
+        if let Some(message_ref) = self.send(&message).await? {
+            self.remember_sent_post(post, message_ref).await;
+        }
+        Ok(())
+    }
+
+    // Updates the message previously sent for the post with the annotation, or
+    // sends a new one if we don't remember it (e.g. sent by a webhook)
+    async fn notify_post_changed(
+        &self,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let id = post.platform_unique_id();
+        let message_ref = self
+            .sent_posts
+            .lock()
+            .await
+            .iter()
+            .find(|(sent_id, _)| *sent_id == id)
+            .map(|(_, message_ref)| message_ref.clone());
+        let Some(message_ref) = message_ref else {
+            return self.notify_post(post, source, annotation).await;
+        };
+
+        let message = make_post_message(
+            self.params.notifications.author_name,
+            post,
+            source,
+            annotation,
+        );
+        self.update(&message_ref, &message).await
+    }
+
+    async fn remember_sent_post(&self, post: &Post, message_ref: MessageRef) {
+        let id = post.platform_unique_id();
+        let mut sent_posts = self.sent_posts.lock().await;
+        sent_posts.retain(|(sent_id, _)| *sent_id != id);
+        if sent_posts.len() >= SENT_POSTS_CAPACITY {
+            sent_posts.pop_back();
+        }
+        sent_posts.push_front((id, message_ref));
+    }
+
+    async fn notify_log(&self, message: &str) -> anyhow::Result<()> {
+        if !self.params.notifications.log {
+            info!("log notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        self.send(&Message::new(message, vec![section(escape(message))]))
+            .await?;
+        Ok(())
+    }
+
+    // Uploading requires a multi-step flow with extra scopes, so only let the users
+    // know about the file. The payload gives the metadata without loading the
+    // playback
+    async fn notify_file(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        let (toggle, enabled, emoji) = match &notification.kind {
+            NotificationKind::Playback(_) => ("playback", self.params.notifications.playback, "🎥"),
+            _ => ("document", self.params.notifications.document, "📊"),
+        };
+        if !enabled {
+            info!("{toggle} notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let payload = NotificationPayload::new(notification).await;
+        let (KindPayload::Playback { file, .. } | KindPayload::Document { file }) = payload.kind
+        else {
+            unreachable!()
+        };
+        let mut text = format!(
+            "[{}] {emoji} {}",
+            notification.source.platform.display_name, file.name
+        );
+        if let Some(size) = file.size {
+            text.push_str(&format!(
+                " ({})",
+                humansize::format_size(size, humansize::BINARY)
+            ));
+        }
+        self.send(&Message::new(text.clone(), vec![section(escape(&text))]))
+            .await?;
+        Ok(())
+    }
+}
+
+fn make_live_message<'a>(
+    author_name: bool,
+    title_history: impl IntoIterator<Item = &'a String>,
+    live_status: &'a LiveStatus,
+    source: &StatusSource,
+    start_time: SystemTime,
+) -> Message {
+    let emoji = match live_status.kind {
+        LiveStatusKind::Online { start_time: _ } => "🟢",
+        LiveStatusKind::Offline => "🟠",
+        LiveStatusKind::Banned => "🔴",
+    };
+    let mut text = format!("[{}] {emoji} ", source.platform.display_name);
+    if author_name {
+        text.push_str(&format!("[{}] ", escape(&live_status.streamer_name)));
+    }
+    text.push_str(&link(
+        &itertools::join(title_history, " ⬅️ "),
+        &live_status.live_url,
+    ));
+    if live_status.kind == LiveStatusKind::Offline || live_status.kind == LiveStatusKind::Banned {
+        if let Ok(dur) = start_time.elapsed() {
+            text.push_str(&format!(" ({})", helper::format_duration_in_min(dur)));
+        }
+    }
+
+    let mut blocks = vec![section(text)];
+    if let Some(area) = &live_status.area {
+        blocks.push(context(escape(&area.to_string())));
+    }
+    blocks.push(image(&live_status.cover_image_url, &live_status.title));
+    Message::new(format!("{emoji} {}", live_status.title), blocks)
+}
+
+fn make_post_message(
+    author_name: bool,
+    post: &Post,
+    source: &StatusSource,
+    annotation: &str,
+) -> Message {
+    let mut blocks = vec![section(make_post_mrkdwn(
+        author_name,
+        post,
+        source,
+        annotation,
+    ))];
+
+    blocks.extend(
+        post.attachments_recursive(true)
+            .into_iter()
+            .filter_map(|attachment| match attachment {
+                PostAttachment::Image(image) => Some(request::image(&image.media_url, "image")),
+                PostAttachment::Video(_) => None,
+            }),
+    );
+
+    // Jump links
+    let urls = post.urls_recursive();
+    let actions = buttons(
+        urls.iter()
+            .filter_map(|url| url.as_clickable())
+            .map(|url| (url.display.as_str(), url.url.as_str())),
+    );
+    // Keep the buttons even if there are too many images
+    if let Some(actions) = actions {
+        blocks.truncate(BLOCKS_LIMIT - 1);
+        blocks.push(actions);
+    }
+
+    let fallback = format!(
+        "[{}] {annotation}{}: {}",
+        source.platform.display_name,
+        post.user.nickname,
+        post.content.fallback()
+    );
+    Message::new(helper::truncate_chars(fallback, SECTION_TEXT_LIMIT), blocks)
+}
+
+fn make_post_mrkdwn(
+    author_name: bool,
+    post: &Post,
+    source: &StatusSource,
+    annotation: &str,
+) -> String {
+    let mut text = format!("[{}] {annotation}", source.platform.display_name);
+
+    match &post.repost_from {
+        Some(RepostFrom::Recursion(repost_from)) => {
+            if !post.content.is_empty() {
+                text.push_str("💬 ");
+                if author_name {
+                    text.push_str(&link(&post.user.nickname, &post.user.profile_url));
+                    text.push_str(": ");
+                }
+                push_content(&mut text, &post.content);
+                text.push('\n');
+            }
+
+            let mut quote = "🔁 ".to_owned();
+            match repost_from.urls_recursive().major() {
+                PostUrl::Clickable(url) => {
+                    quote.push_str(&link(&repost_from.user.nickname, &url.url))
+                }
+                PostUrl::Identity(_) => quote.push_str(&escape(&repost_from.user.nickname)),
+            }
+            quote.push_str(": ");
+            push_content(&mut quote, &repost_from.content);
+            text.push_str(&itertools::join(
+                quote.lines().map(|line| format!("> {line}")),
+                "\n",
+            ));
+        }
+        None => {
+            if author_name {
+                text.push_str(&link(&post.user.nickname, &post.user.profile_url));
+                text.push_str(": ");
+            }
+            push_content(&mut text, &post.content);
+        }
+    }
+
+    // Videos are linked, image blocks only accept images
+    let videos = post
+        .attachments_recursive(true)
+        .into_iter()
+        .filter_map(|attachment| match attachment {
+            PostAttachment::Video(video) => Some(video.media_url.as_str()),
+            PostAttachment::Image(_) => None,
+        })
+        .enumerate()
+        .map(|(i, url)| link(&format!("🎬 {}", i + 1), url))
+        .collect::<Vec<_>>();
+    if !videos.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(&videos.join(" "));
+    }
+    text
+}
+
+fn push_content(text: &mut String, content: &PostContent) {
+    content.parts().for_each(|part| match part {
+        PostContentPart::Plain(plain) => text.push_str(&escape(plain)),
+        PostContentPart::Link { display, url } => text.push_str(&link(display, url)),
+        PostContentPart::InlineAttachment(_) => {
+            // Ignore, we handle it in post.attachments
+        }
+    });
+}
+
+struct CurrentLive {
+    start_time: SystemTime,
+    message_ref: MessageRef,
+    // The first is the current title, the last is the oldest title
+    title_history: VecDeque<String>,
+}
+
+// Number of sent posts to remember, for updating them when the posts change
+const SENT_POSTS_CAPACITY: usize = 100;
+
+#[cfg(test)]
+mod tests {
+    use serde_json as json;
+
+    use super::*;
+    use crate::source::{PostAttachmentImage, PostAttachmentVideo};
+
+    #[test]
+    fn config() {
+        let params: ConfigParams = toml::from_str(
+            r#"
+bot_token_env = "PATH"
+channel = "C0123456789"
+            "#,
+        )
+        .unwrap();
+        config::Validator::validate(&params).unwrap();
+        assert!(params.webhook.is_none());
+        assert_eq!(params.to_string(), "slack:C0123456789");
+
+        let params: ConfigParams = toml::from_str(
+            r#"
+webhook_url = "https://hooks.slack.com/services/T0/B0/meow"
+            "#,
+        )
+        .unwrap();
+        config::Validator::validate(&params).unwrap();
+        assert!(params.bot_token.is_none());
+        assert_eq!(params.to_string(), "slack:webhook");
+
+        let params: ConfigParams = toml::from_str(
+            r#"
+webhook_url = "https://hooks.slack.com/services/T0/B0/meow"
+bot_token = "xoxb-meow"
+            "#,
+        )
+        .unwrap();
+        assert!(config::Validator::validate(&params).is_err());
+
+        let params: ConfigParams = toml::from_str(r#"bot_token = "xoxb-meow""#).unwrap();
+        assert!(config::Validator::validate(&params).is_err());
+    }
+
+    #[test]
+    fn post_message() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = Post::mock(
+            PostContent::plain("<b>meow</b>\n").with_link("link", "https://example.com/link"),
+            vec![
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: "https://example.com/0.jpg".into(),
+                    has_spoiler: false,
+                }),
+                PostAttachment::Video(PostAttachmentVideo {
+                    media_url: "https://example.com/1.mp4".into(),
+                    has_spoiler: false,
+                }),
+            ],
+        );
+
+        let message = make_post_message(true, &post, &source, "✏️ ");
+        assert_eq!(
+            message.text,
+            "[test] ✏️ user: <b>meow</b>\n[link](https://example.com/link)"
+        );
+        assert_eq!(
+            json::to_value(&message.blocks).unwrap(),
+            json::json!([
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": "[test] ✏️ <https://example.com/user|user>: &lt;b&gt;meow&lt;/b&gt;\n<https://example.com/link|link>\n\n<https://example.com/1.mp4|🎬 1>",
+                    },
+                },
+                { "type": "image", "image_url": "https://example.com/0.jpg", "alt_text": "image" },
+                {
+                    "type": "actions",
+                    "elements": [{
+                        "type": "button",
+                        "text": { "type": "plain_text", "text": "View" },
+                        "url": "https://example.com/post",
+                    }],
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, ensure};
+use reqwest::{header, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::helper;
+
+// Limits of Block Kit
+// https://api.slack.com/reference/block-kit/blocks
+pub const SECTION_TEXT_LIMIT: usize = 3000;
+pub const BLOCKS_LIMIT: usize = 50;
+pub const ACTIONS_ELEMENTS_LIMIT: usize = 25;
+pub const BUTTON_TEXT_LIMIT: usize = 75;
+
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Message {
+    // Shown in notifications and by clients that cannot render blocks
+    pub text: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<json::Value>,
+    pub unfurl_links: bool,
+    pub unfurl_media: bool,
+}
+
+impl Message {
+    pub fn new(text: impl Into<String>, blocks: Vec<json::Value>) -> Self {
+        let mut blocks = blocks;
+        blocks.truncate(BLOCKS_LIMIT);
+        Self {
+            text: text.into(),
+            blocks,
+            ..Default::default()
+        }
+    }
+}
+
+pub fn section(mrkdwn: impl Into<String>) -> json::Value {
+    json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": helper::truncate_chars(mrkdwn.into(), SECTION_TEXT_LIMIT) },
+    })
+}
+
+pub fn context(mrkdwn: impl Into<String>) -> json::Value {
+    json!({
+        "type": "context",
+        "elements": [{ "type": "mrkdwn", "text": mrkdwn.into() }],
+    })
+}
+
+pub fn image(url: &str, alt_text: &str) -> json::Value {
+    json!({
+        "type": "image",
+        "image_url": url,
+        "alt_text": alt_text,
+    })
+}
+
+pub fn buttons<'a>(buttons: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<json::Value> {
+    let elements = buttons
+        .into_iter()
+        .take(ACTIONS_ELEMENTS_LIMIT)
+        .map(|(text, url)| {
+            json!({
+                "type": "button",
+                "text": { "type": "plain_text", "text": helper::truncate_chars(text.into(), BUTTON_TEXT_LIMIT) },
+                "url": url,
+            })
+        })
+        .collect::<Vec<_>>();
+    (!elements.is_empty()).then(|| json!({ "type": "actions", "elements": elements }))
+}
+
+// https://api.slack.com/reference/surfaces/formatting#escaping
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn link(display: &str, url: &str) -> String {
+    // `|` separates the URL and the display text
+    format!("<{}|{}>", url, escape(display).replace('|', "¦"))
+}
+
+pub async fn post_webhook(url: &str, message: &Message) -> anyhow::Result<()> {
+    let resp = send(|client| client.post(url).json(message)).await?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+    ensure!(
+        status.is_success(),
+        "response status is not success: {status}, response '{text}'"
+    );
+    Ok(())
+}
+
+pub struct Api<'a> {
+    token: &'a str,
+}
+
+// Identifies a sent message for updating
+#[derive(Clone, Debug)]
+pub struct MessageRef {
+    pub channel: String,
+    pub ts: String,
+}
+
+impl<'a> Api<'a> {
+    pub fn new(token: &'a str) -> Self {
+        Self { token }
+    }
+
+    // https://api.slack.com/methods/chat.postMessage
+    pub async fn post_message(
+        &self,
+        channel: &str,
+        message: &Message,
+    ) -> anyhow::Result<MessageRef> {
+        let mut body =
+            json::to_value(message).map_err(|err| anyhow!("failed to serialize message: {err}"))?;
+        body["channel"] = channel.into();
+        self.call("chat.postMessage", &body).await
+    }
+
+    // https://api.slack.com/methods/chat.update
+    pub async fn update(
+        &self,
+        target: &MessageRef,
+        message: &Message,
+    ) -> anyhow::Result<MessageRef> {
+        let mut body =
+            json::to_value(message).map_err(|err| anyhow!("failed to serialize message: {err}"))?;
+        body["channel"] = target.channel.as_str().into();
+        body["ts"] = target.ts.as_str().into();
+        self.call("chat.update", &body).await
+    }
+
+    async fn call(&self, method: &str, body: &json::Value) -> anyhow::Result<MessageRef> {
+        #[derive(Deserialize)]
+        struct Response {
+            ok: bool,
+            error: Option<String>,
+            channel: Option<String>,
+            ts: Option<String>,
+        }
+
+        let url = format!("https://slack.com/api/{method}");
+        let resp = send(|client| client.post(&url).bearer_auth(self.token).json(body)).await?;
+        let status = resp.status();
+        ensure!(
+            status.is_success(),
+            "response status is not success: {status}"
+        );
+        let resp: Response = resp
+            .json()
+            .await
+            .map_err(|err| anyhow!("failed to deserialize response: {err}"))?;
+        if !resp.ok {
+            bail!(
+                "response contains error, error '{}'",
+                resp.error.unwrap_or_else(|| "*no error*".into())
+            );
+        }
+        match (resp.channel, resp.ts) {
+            (Some(channel), Some(ts)) => Ok(MessageRef { channel, ts }),
+            _ => bail!("response is missing channel or ts"),
+        }
+    }
+}
+
+async fn send(
+    make_request: impl Fn(&reqwest::Client) -> RequestBuilder,
+) -> anyhow::Result<reqwest::Response> {
+    let client = helper::reqwest_client()?;
+
+    let mut retries = 0;
+    loop {
+        let resp = make_request(&client)
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request: {err}"))?;
+        if resp.status() != StatusCode::TOO_MANY_REQUESTS || retries >= MAX_RATE_LIMITED_RETRIES {
+            return Ok(resp);
+        }
+
+        let retry_after = resp
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(1));
+        warn!("rate limited by Slack, retry after {retry_after:?}");
+        tokio::time::sleep(retry_after).await;
+        retries += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mrkdwn() {
+        assert_eq!(escape("<b> & </b>"), "&lt;b&gt; &amp; &lt;/b&gt;");
+        assert_eq!(
+            link("a|b <c>", "https://example.com/"),
+            "<https://example.com/|a¦b &lt;c&gt;>"
+        );
+    }
+
+    #[test]
+    fn actions() {
+        assert_eq!(buttons([]), None);
+        assert_eq!(
+            buttons([("View", "https://example.com/post")]),
+            Some(json!({
+                "type": "actions",
+                "elements": [{
+                    "type": "button",
+                    "text": { "type": "plain_text", "text": "View" },
+                    "url": "https://example.com/post",
+                }],
+            }))
+        );
+    }
+}
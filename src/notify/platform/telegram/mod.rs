@@ -25,9 +25,9 @@ use crate::{
     platform::{PlatformMetadata, PlatformTrait},
     secret_enum, serde_impl_default_for,
     source::{
-        DocumentRef, FileRef, LiveStatus, LiveStatusKind, Notification, NotificationKind,
-        PlaybackFormat, PlaybackRef, Post, PostAttachment, PostUrl, PostsRef, RepostFrom,
-        StatusSource,
+        DocumentRef, FileRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind,
+        PlaybackFormat, PlaybackRef, Post, PostAttachment, PostPlatformUniqueId, PostUrl, PostsRef,
+        RepostFrom, StatusSource,
     },
 };
 
@@ -177,6 +177,8 @@ impl fmt::Display for ConfigChat {
 pub struct Notifier {
     params: config::Accessor<ConfigParams>,
     current_live: Mutex<Option<CurrentLive>>,
+    // The latest first
+    sent_posts: Mutex<VecDeque<(PostPlatformUniqueId, SentPost)>>,
 }
 
 impl PlatformTrait for Notifier {
@@ -201,26 +203,26 @@ impl Notifier {
         Self {
             params,
             current_live: Mutex::new(None),
+            sent_posts: Mutex::new(VecDeque::new()),
         }
     }
 
     fn token(&self) -> anyhow::Result<Cow<str>> {
-        self.params
-            .token
-            .as_ref()
-            .unwrap_or_else(|| {
-                Config::global()
-                    .platform()
-                    .telegram
-                    .as_ref()
-                    .unwrap()
-                    .token
-                    .as_ref()
-                    .unwrap()
-            })
-            .as_secret_ref()
-            .get_str()
-            .map_err(|err| anyhow!("failed to read token for telegram: {err}"))
+        let token = match &self.params.token {
+            Some(token) => token.as_secret_ref().get_str(),
+            None => Config::global()
+                .platform()
+                .telegram
+                .as_ref()
+                .unwrap()
+                .token
+                .as_ref()
+                .unwrap()
+                .as_secret_ref()
+                .get_str()
+                .map(|token| Cow::Owned(token.into_owned())),
+        };
+        token.map_err(|err| anyhow!("failed to read token for telegram: {err}"))
     }
 
     async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
@@ -234,7 +236,17 @@ impl Notifier {
                 self.notify_live_title(live_status, notification.source)
                     .await
             }
+            NotificationKind::LiveArea(live_status, old_area) => {
+                self.notify_live_area(live_status, old_area, notification.source)
+                    .await
+            }
             NotificationKind::Posts(posts) => self.notify_posts(posts, notification.source).await,
+            NotificationKind::PostEdited(post, _old) => {
+                self.notify_post_edited(post, notification.source).await
+            }
+            NotificationKind::PostDeleted(post) => {
+                self.notify_post_deleted(post, notification.source).await
+            }
             NotificationKind::Log(message) => self.notify_log(message).await,
             NotificationKind::Playback(playback) => {
                 self.notify_playback(playback, notification.source).await
@@ -431,6 +443,52 @@ impl Notifier {
         Ok(())
     }
 
+    async fn notify_live_area(
+        &self,
+        live_status: &LiveStatus,
+        old_area: &LiveArea,
+        source: &StatusSource,
+    ) -> anyhow::Result<()> {
+        if !self.params.notifications.live_area {
+            info!("live_area notification is disabled, skip notifying");
+            return Ok(());
+        }
+        let Some(area) = &live_status.area else {
+            return Ok(());
+        };
+
+        let token = self.token()?;
+
+        let text = Text::link(
+            format!(
+                "[{}] 🏷️ {}{old_area} ➡️ {area}",
+                source.platform.display_name,
+                if self.params.notifications.author_name {
+                    Cow::Owned(format!("[{}] ", live_status.streamer_name))
+                } else {
+                    Cow::Borrowed("")
+                },
+            ),
+            &live_status.live_url,
+        );
+
+        let resp = Request::new(&token)
+            .send_message(&self.params.chat, text)
+            .thread_id_opt(self.params.thread_id)
+            .link_preview(LinkPreview::Disabled)
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request to Telegram: {err}"))?;
+        ensure!(
+            resp.ok,
+            "response contains error, description '{}'",
+            resp.description
+                .unwrap_or_else(|| "*no description*".into())
+        );
+
+        Ok(())
+    }
+
     async fn notify_posts(
         &self,
         posts: &PostsRef<'_>,
@@ -445,7 +503,7 @@ impl Notifier {
 
         let mut errors = vec![];
         for post in &posts.0 {
-            if let Err(err) = self.notify_post(token.as_ref(), post, source).await {
+            if let Err(err) = self.notify_post(token.as_ref(), post, source, "").await {
                 errors.push(err);
             }
         }
@@ -453,13 +511,88 @@ impl Notifier {
         Ok(())
     }
 
-    async fn notify_post(
+    async fn notify_post_edited(&self, post: &Post, source: &StatusSource) -> anyhow::Result<()> {
+        if !self.params.notifications.post_edited {
+            info!("post_edited notification is disabled, skip notifying");
+            return Ok(());
+        }
+        self.notify_post_changed(post, source, "✏️ ").await
+    }
+
+    async fn notify_post_deleted(&self, post: &Post, source: &StatusSource) -> anyhow::Result<()> {
+        if !self.params.notifications.post_deleted {
+            info!("post_deleted notification is disabled, skip notifying");
+            return Ok(());
+        }
+        self.notify_post_changed(post, source, "🗑️ ").await
+    }
+
+    // Edits the message previously sent for the post with the annotation, or sends
+    // a new one if we don't remember it (e.g. sent before restarting)
+    async fn notify_post_changed(
         &self,
-        token: &str,
         post: &Post,
         source: &StatusSource,
+        annotation: &str,
     ) -> anyhow::Result<()> {
-        let mut text = Text::plain(format!("[{}] ", source.platform.display_name));
+        let token = self.token()?;
+
+        let id = post.platform_unique_id();
+        let sent = self
+            .sent_posts
+            .lock()
+            .await
+            .iter()
+            .find(|(sent_id, _)| *sent_id == id)
+            .map(|(_, sent)| *sent);
+        let Some(sent) = sent else {
+            return self.notify_post(&token, post, source, annotation).await;
+        };
+
+        let mut text = self.make_post_text(post, source, annotation);
+        let resp = match sent.kind {
+            SentPostKind::Text => {
+                Request::new(&token)
+                    .edit_message_text(&self.params.chat, sent.message_id, text)
+                    .markup(Markup::InlineKeyboard(make_jump_buttons(post)))
+                    .send()
+                    .await
+            }
+            SentPostKind::Media => {
+                Request::new(&token)
+                    .edit_message_caption(&self.params.chat, sent.message_id)
+                    .text(text)
+                    .markup(Markup::InlineKeyboard(make_jump_buttons(post)))
+                    .send()
+                    .await
+            }
+            SentPostKind::MediaGroup => {
+                push_jump_links(&mut text, post);
+                Request::new(&token)
+                    .edit_message_caption(&self.params.chat, sent.message_id)
+                    .text(text)
+                    .send()
+                    .await
+            }
+        }
+        .map_err(|err| anyhow!("failed to send request to Telegram: {err}"))?;
+        ensure!(
+            resp.ok,
+            "response contains error, description '{}'",
+            resp.description
+                .unwrap_or_else(|| "*no description*".into())
+        );
+
+        Ok(())
+    }
+
+    fn make_post_text<'a>(
+        &self,
+        post: &'a Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> Text<'a> {
+        let mut text = Text::plain(format!("[{}] {annotation}", source.platform.display_name));
 
         match &post.repost_from {
             Some(RepostFrom::Recursion(repost_from)) => {
@@ -497,35 +630,41 @@ impl Notifier {
                 text.push_content(&post.content)
             }
         }
+        text
+    }
+
+    async fn notify_post(
+        &self,
+        token: &str,
+        post: &Post,
+        source: &StatusSource,
+        annotation: &str,
+    ) -> anyhow::Result<()> {
+        let mut text = self.make_post_text(post, source, annotation);
 
         const DISABLE_NOTIFICATION: bool = true; // TODO: Make it configurable
 
         let attachments = post.attachments_recursive(true);
         let num_attachments = attachments.len();
 
-        let resp = match num_attachments {
+        let (resp, kind) = match num_attachments {
             0 | 1 => {
                 // Jump buttons
-                let buttons = vec![post
-                    .urls_recursive()
-                    .into_iter()
-                    .filter_map(|url| url.as_clickable())
-                    .map(|url| Button::new_url(&url.display, &url.url))
-                    .collect::<Vec<_>>()];
+                let buttons = make_jump_buttons(post);
 
                 if num_attachments == 0 {
-                    Request::new(token)
+                    let resp = Request::new(token)
                         .send_message(&self.params.chat, text)
                         .thread_id_opt(self.params.thread_id)
                         .disable_notification_bool(DISABLE_NOTIFICATION)
                         .markup(Markup::InlineKeyboard(buttons))
                         .send()
-                        .await
-                        .map(|resp| resp.discard_result())
+                        .await;
+                    (resp, SentPostKind::Text)
                 } else {
                     let attachment = attachments.first().unwrap();
 
-                    match attachment {
+                    let resp = match attachment {
                         PostAttachment::Image(image) => {
                             // TODO: `sendAnimation` for single GIF?
                             Request::new(token).send_photo(&self.params.chat, image.into())
@@ -539,27 +678,12 @@ impl Notifier {
                     .disable_notification_bool(DISABLE_NOTIFICATION)
                     .markup(Markup::InlineKeyboard(buttons))
                     .send()
-                    .await
-                    .map(|resp| resp.discard_result())
+                    .await;
+                    (resp, SentPostKind::Media)
                 }
             }
             _ => {
-                text.push_plain("\n\n");
-
-                // Jump buttons
-                {
-                    let mut iter = post
-                        .urls_recursive()
-                        .into_iter()
-                        .filter_map(|url| url.as_clickable())
-                        .peekable();
-                    while let Some(url) = iter.next() {
-                        text.push_link(format!(">> {} <<", url.display), &url.url);
-                        if iter.peek().is_some() {
-                            text.push_plain(" | ");
-                        }
-                    }
-                }
+                push_jump_links(&mut text, post);
 
                 let medias = attachments.iter().map(|attachment| match attachment {
                     // TODO: Mixing GIF in media group to send is not yet supported in Telegram, add
@@ -568,7 +692,7 @@ impl Notifier {
                     PostAttachment::Video(video) => Media::Video(video.into()),
                 });
 
-                Request::new(token)
+                let resp = Request::new(token)
                     .send_media_group(&self.params.chat)
                     .medias(medias)
                     .text(text)
@@ -576,10 +700,12 @@ impl Notifier {
                     .disable_notification_bool(DISABLE_NOTIFICATION)
                     .send()
                     .await
-                    .map(|resp| resp.discard_result())
+                    // The caption is attached to the first message
+                    .map(|resp| resp.and_then_result(|messages| messages.into_iter().next()));
+                (resp, SentPostKind::MediaGroup)
             }
-        }
-        .map_err(|err| anyhow!("failed to send request to Telegram: {err}"))?;
+        };
+        let resp = resp.map_err(|err| anyhow!("failed to send request to Telegram: {err}"))?;
 
         ensure!(
             resp.ok,
@@ -588,9 +714,31 @@ impl Notifier {
                 .unwrap_or_else(|| "*no description*".into())
         );
 
+        let message = resp
+            .result
+            .ok_or_else(|| anyhow!("response contains no sent message"))?;
+        self.remember_sent_post(
+            post,
+            SentPost {
+                message_id: message.message_id,
+                kind,
+            },
+        )
+        .await;
+
         Ok(())
     }
 
+    async fn remember_sent_post(&self, post: &Post, sent: SentPost) {
+        let id = post.platform_unique_id();
+        let mut sent_posts = self.sent_posts.lock().await;
+        sent_posts.retain(|(sent_id, _)| *sent_id != id);
+        if sent_posts.len() >= SENT_POSTS_CAPACITY {
+            sent_posts.pop_back();
+        }
+        sent_posts.push_front((id, sent));
+    }
+
     async fn notify_log(&self, message: &str) -> anyhow::Result<()> {
         if !self.params.notifications.log {
             info!("log notification is disabled, skip notifying");
@@ -840,6 +988,32 @@ fn make_live_text<'a>(
     Text::link(text, &live_status.live_url)
 }
 
+fn make_jump_buttons(post: &Post) -> Vec<Vec<Button<'_>>> {
+    vec![post
+        .urls_recursive()
+        .into_iter()
+        .filter_map(|url| url.as_clickable())
+        .map(|url| Button::new_url(&url.display, &url.url))
+        .collect::<Vec<_>>()]
+}
+
+// Used instead of buttons for media groups, as they cannot have buttons
+fn push_jump_links<'a>(text: &mut Text<'a>, post: &'a Post) {
+    text.push_plain("\n\n");
+
+    let mut iter = post
+        .urls_recursive()
+        .into_iter()
+        .filter_map(|url| url.as_clickable())
+        .peekable();
+    while let Some(url) = iter.next() {
+        text.push_link(format!(">> {} <<", url.display), &url.url);
+        if iter.peek().is_some() {
+            text.push_plain(" | ");
+        }
+    }
+}
+
 enum FileUploadStage {
     PlaybackUploading,
     PlaybackFinished,
@@ -884,6 +1058,22 @@ struct CurrentLive {
     title_history: VecDeque<String>,
 }
 
+// Number of sent posts to remember, for editing them when the posts change
+const SENT_POSTS_CAPACITY: usize = 100;
+
+#[derive(Clone, Copy)]
+struct SentPost {
+    message_id: i64,
+    kind: SentPostKind,
+}
+
+#[derive(Clone, Copy)]
+enum SentPostKind {
+    Text,
+    Media,
+    MediaGroup,
+}
+
 struct NotifyPlaybackRetry {
     //
 }
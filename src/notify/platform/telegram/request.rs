@@ -206,6 +206,7 @@ impl<'a> Request<'a> {
             message_id,
             text,
             link_preview: None,
+            markup: None,
         }
     }
 
@@ -219,6 +220,7 @@ impl<'a> Request<'a> {
             chat,
             message_id,
             text: None,
+            markup: None,
         }
     }
 
@@ -250,7 +252,8 @@ impl<'a> Request<'a> {
 fn make_api_url(token: &str, method: &str, prefer_self_host: bool) -> String {
     static OFFICIAL: Lazy<Uri> = Lazy::new(|| "https://api.telegram.org".parse().unwrap());
 
-    let url_opts = Config::global()
+    let config = Config::global();
+    let url_opts = config
         .platform()
         .telegram
         .as_ref()
@@ -857,6 +860,7 @@ pub struct EditMessageText<'a> {
     message_id: i64,
     text: Text<'a>,
     link_preview: Option<LinkPreview<'a>>,
+    markup: Option<Markup<'a>>,
 }
 
 impl<'a> EditMessageText<'a> {
@@ -867,6 +871,13 @@ impl<'a> EditMessageText<'a> {
         }
     }
 
+    pub fn markup(self, markup: Markup<'a>) -> Self {
+        Self {
+            markup: Some(markup),
+            ..self
+        }
+    }
+
     pub async fn send(self) -> anyhow::Result<Response<ResultMessage>> {
         let mut body = json!(
             {
@@ -883,6 +894,9 @@ impl<'a> EditMessageText<'a> {
         if let Some(link_preview) = self.link_preview {
             body["link_preview_options"] = link_preview.into_json();
         }
+        if let Some(markup) = self.markup {
+            body["reply_markup"] = markup.into_json();
+        }
         self.base
             .send_request("editMessageText", &body, [], false)
             .await
@@ -894,6 +908,7 @@ pub struct EditMessageCaption<'a> {
     chat: &'a ConfigChat,
     message_id: i64,
     text: Option<Text<'a>>,
+    markup: Option<Markup<'a>>,
 }
 
 impl<'a> EditMessageCaption<'a> {
@@ -904,6 +919,13 @@ impl<'a> EditMessageCaption<'a> {
         }
     }
 
+    pub fn markup(self, markup: Markup<'a>) -> Self {
+        Self {
+            markup: Some(markup),
+            ..self
+        }
+    }
+
     pub async fn send(self) -> anyhow::Result<Response<ResultMessage>> {
         let mut body = json!(
             {
@@ -917,6 +939,9 @@ impl<'a> EditMessageCaption<'a> {
             body.insert("caption".into(), text);
             body.insert("caption_entities".into(), entities);
         }
+        if let Some(markup) = self.markup {
+            body["reply_markup"] = markup.into_json();
+        }
         self.base
             .send_request("editMessageCaption", &body, [], false)
             .await
@@ -1138,6 +1163,14 @@ impl<R> Response<R> {
             result: Some(IgnoredAny),
         }
     }
+
+    pub fn and_then_result<T>(self, f: impl FnOnce(R) -> Option<T>) -> Response<T> {
+        Response {
+            ok: self.ok,
+            description: self.description,
+            result: self.result.and_then(f),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,330 @@
+use std::{collections::BTreeMap, fmt, future::Future, pin::Pin, time::Duration};
+
+use anyhow::{anyhow, bail, ensure};
+use hmac::{Hmac, Mac};
+use reqwest::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::Deserialize;
+use sha2::Sha256;
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::NotifierTrait,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum, serde_impl_default_for,
+    source::{Notification, NotificationKind, NotificationPayload},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    // Only the toggles apply, payloads always include authors
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // Key for signing the body with HMAC-SHA256
+    #[serde(flatten)]
+    pub secret: Option<ConfigSecret>,
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    #[serde(default)]
+    pub retry: ConfigRetry,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        let url =
+            reqwest::Url::parse(&self.url).map_err(|err| anyhow!("invalid webhook URL: {err}"))?;
+        ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "invalid webhook URL scheme '{}'",
+            url.scheme()
+        );
+        for (name, value) in &self.headers {
+            HeaderName::try_from(name).map_err(|err| anyhow!("invalid header '{name}': {err}"))?;
+            HeaderValue::try_from(value)
+                .map_err(|err| anyhow!("invalid value of header '{name}': {err}"))?;
+        }
+        self.secret.validate()?;
+        ensure!(!self.timeout.is_zero(), "timeout must not be zero");
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the host, the rest of the URL may contain tokens
+        let url = reqwest::Url::parse(&self.url).ok();
+        write!(
+            f,
+            "webhook:{}",
+            url.as_ref().and_then(|url| url.host_str()).unwrap_or("*")
+        )
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            url: new.url.unwrap_or(self.url),
+            headers: match new.headers {
+                Some(headers) => self.headers.into_iter().chain(headers).collect(),
+                None => self.headers,
+            },
+            secret: new.secret.or(self.secret),
+            timeout: new.timeout.unwrap_or(self.timeout),
+            retry: new.retry.unwrap_or(self.retry),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    pub url: Option<String>,
+    // Merged into the original headers
+    pub headers: Option<BTreeMap<String, String>>,
+    #[serde(flatten)]
+    pub secret: Option<ConfigSecret>,
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    pub retry: Option<ConfigRetry>,
+}
+
+// Retries in place before giving the notification to the outbox, which retries
+// later with a much longer backoff
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRetry {
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_retry_delay", with = "humantime_serde")]
+    pub delay: Duration,
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+serde_impl_default_for!(ConfigRetry);
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigSecret {
+        Secret(String),
+    }
+}
+
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Webhook",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        if let Some(toggle) = self.disabled_toggle(&notification.kind) {
+            info!("{toggle} notification is disabled, skip notifying");
+            return Ok(());
+        }
+
+        let payload = NotificationPayload::new(notification).await;
+        let body = serde_json::to_vec(&payload)
+            .map_err(|err| anyhow!("failed to serialize payload: {err}"))?;
+        let signature = match &self.params.secret {
+            Some(secret) => {
+                let secret = secret
+                    .as_secret_ref()
+                    .get_str()
+                    .map_err(|err| anyhow!("failed to read secret for webhook: {err}"))?;
+                Some(sign(secret.as_bytes(), &body))
+            }
+            None => None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .post(payload.kind.name(), &body, signature.as_deref())
+                .await;
+            let Err(err) = result else {
+                return Ok(());
+            };
+            if !err.retryable || attempt >= self.params.retry.attempts {
+                bail!("failed to deliver webhook: {}", err.error);
+            }
+
+            let delay =
+                helper::exponential_backoff(self.params.retry.delay, attempt, RETRY_MAX_DELAY);
+            attempt += 1;
+            warn!(
+                "failed to deliver webhook to '{}' (attempt {attempt}), retry in {delay:?}: {}",
+                self.params, err.error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn disabled_toggle(&self, kind: &NotificationKind<'_>) -> Option<&'static str> {
+        let notifications = &self.params.notifications;
+        let (toggle, enabled) = match kind {
+            NotificationKind::LiveOnline(_) => ("live_online", notifications.live_online),
+            NotificationKind::LiveTitle(..) => ("live_title", notifications.live_title),
+            NotificationKind::LiveArea(..) => ("live_area", notifications.live_area),
+            NotificationKind::Posts(_) => ("post", notifications.post),
+            NotificationKind::PostEdited(..) => ("post_edited", notifications.post_edited),
+            NotificationKind::PostDeleted(_) => ("post_deleted", notifications.post_deleted),
+            NotificationKind::Log(_) => ("log", notifications.log),
+            NotificationKind::Playback(_) => ("playback", notifications.playback),
+            NotificationKind::Document(_) => ("document", notifications.document),
+        };
+        (!enabled).then_some(toggle)
+    }
+
+    async fn post(
+        &self,
+        kind: &str,
+        body: &[u8],
+        signature: Option<&str>,
+    ) -> Result<(), PostError> {
+        let mut request = helper::reqwest_client()
+            .map_err(PostError::fatal)?
+            .post(&self.params.url)
+            .timeout(self.params.timeout)
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Closely-Event", kind)
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header("X-Closely-Signature-256", format!("sha256={signature}"));
+        }
+        for (name, value) in &self.params.headers {
+            request = request.header(name, value);
+        }
+
+        let resp = request.send().await.map_err(|err| PostError {
+            error: anyhow!("failed to send request: {err}"),
+            retryable: true,
+        })?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = resp.text().await.unwrap_or_default();
+        Err(PostError {
+            error: anyhow!("response status is not success: {status}, response '{text}'"),
+            // Client errors other than rate limiting won't be fixed by retrying
+            retryable: status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        })
+    }
+}
+
+struct PostError {
+    error: anyhow::Error,
+    retryable: bool,
+}
+
+impl PostError {
+    fn fatal(error: anyhow::Error) -> Self {
+        Self {
+            error,
+            retryable: false,
+        }
+    }
+}
+
+// Hex-encoded HMAC-SHA256, the same scheme as GitHub webhooks
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature() {
+        // Example from GitHub docs
+        assert_eq!(
+            sign(b"It's a Secret to Everybody", b"Hello, World!"),
+            "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+    }
+
+    #[test]
+    fn config() {
+        let params: ConfigParams = toml::from_str(
+            r#"
+url = "https://example.com/hooks/closely?token=meow"
+headers = { Authorization = "Bearer meow" }
+secret_env = "PATH"
+retry = { attempts = 5 }
+            "#,
+        )
+        .unwrap();
+        config::Validator::validate(&params).unwrap();
+        assert_eq!(params.timeout, Duration::from_secs(10));
+        assert_eq!(
+            params.retry,
+            ConfigRetry {
+                attempts: 5,
+                delay: Duration::from_secs(1),
+            }
+        );
+        assert_eq!(params.to_string(), "webhook:example.com");
+
+        let params = ConfigParams {
+            headers: BTreeMap::from([("Bad Header".into(), "meow".into())]),
+            ..params
+        };
+        assert!(config::Validator::validate(&params).is_err());
+    }
+}
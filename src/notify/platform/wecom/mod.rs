@@ -0,0 +1,254 @@
+use std::{borrow::Cow, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    notify::{
+        robot::{self, RobotCard},
+        NotifierTrait,
+    },
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::Notification,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    #[serde(default)]
+    pub notifications: config::Notifications,
+    // e.g. "https://qyapi.weixin.qq.com/cgi-bin/webhook/send?key=xxx", the key is
+    // the only credential, WeCom robots have no signing
+    #[serde(flatten)]
+    pub webhook: ConfigWebhook,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        self.webhook.validate()?;
+        let url = self
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for WeCom: {err}"))?;
+        let url = reqwest::Url::parse(&url).map_err(|err| anyhow!("invalid webhook URL: {err}"))?;
+        ensure!(
+            url.scheme() == "https" && url.query_pairs().any(|(key, _)| key == "key"),
+            "invalid webhook URL"
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The webhook URL is the secret
+        write!(f, "wecom:webhook")
+    }
+}
+
+impl config::Overridable for ConfigParams {
+    type Override = ConfigOverride;
+
+    fn override_into(self, new: Self::Override) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            notifications: match new.notifications {
+                Some(notifications) => self.notifications.override_into(notifications),
+                None => self.notifications,
+            },
+            webhook: new.webhook.unwrap_or(self.webhook),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverride {
+    pub notifications: Option<config::NotificationsOverride>,
+    #[serde(flatten)]
+    pub webhook: Option<ConfigWebhook>,
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigWebhook {
+        WebhookUrl(String),
+    }
+}
+
+// Limits of the message types
+// https://developer.work.weixin.qq.com/document/path/91770
+const MARKDOWN_LIMIT: usize = 4096;
+const NEWS_TITLE_LIMIT: usize = 128;
+const NEWS_DESCRIPTION_LIMIT: usize = 512;
+
+pub struct Notifier {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Notifier {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "WeCom",
+        }
+    }
+}
+
+impl NotifierTrait for Notifier {
+    fn notify<'a>(
+        &'a self,
+        notification: &'a Notification<'_>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.notify_impl(notification))
+    }
+}
+
+impl Notifier {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    fn webhook_url(&self) -> anyhow::Result<Cow<'_, str>> {
+        self.params
+            .webhook
+            .as_secret_ref()
+            .get_str()
+            .map_err(|err| anyhow!("failed to read webhook URL for WeCom: {err}"))
+    }
+
+    async fn notify_impl(&self, notification: &Notification<'_>) -> anyhow::Result<()> {
+        info!("notifying to '{}'", self.params);
+
+        let mut errors = vec![];
+        for card in robot::make_cards(&self.params.notifications, notification).await {
+            if let Err(err) = self.send(&card).await {
+                errors.push(err);
+            }
+        }
+        ensure!(errors.is_empty(), "{errors:?}");
+        Ok(())
+    }
+
+    async fn send(&self, card: &RobotCard) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.webhook_url()?)
+            .map_err(|err| anyhow!("invalid webhook URL: {err}"))?;
+
+        let resp = robot::post(url, &make_body(card))
+            .await
+            .map_err(|err| anyhow!("failed to send message to WeCom: {err}"))?;
+        let errcode = resp["errcode"].as_i64().unwrap_or_default();
+        if errcode != 0 {
+            bail!(
+                "response contains error, errcode: {errcode}, errmsg: '{}'",
+                resp["errmsg"].as_str().unwrap_or("*no errmsg*")
+            );
+        }
+        Ok(())
+    }
+}
+
+fn make_body(card: &RobotCard) -> json::Value {
+    // The markdown of WeCom doesn't display images, so the live with a cover is
+    // sent as a news article
+    if let Some(cover) = &card.cover {
+        return json!({
+            "msgtype": "news",
+            "news": {
+                "articles": [{
+                    "title": helper::truncate_bytes(cover.title.clone(), NEWS_TITLE_LIMIT),
+                    "description": helper::truncate_bytes(card.title.clone(), NEWS_DESCRIPTION_LIMIT),
+                    "url": cover.url,
+                    "picurl": cover.image_url,
+                }],
+            },
+        });
+    }
+
+    let mut content = format!("**{}**\n{}", card.title, card.markdown);
+    let links = card
+        .image_urls
+        .iter()
+        .enumerate()
+        .map(|(i, url)| robot::link(&format!("🖼️ {}", i + 1), url))
+        .chain(
+            card.buttons
+                .iter()
+                .map(|button| robot::link(&button.text, &button.url)),
+        )
+        .collect::<Vec<_>>();
+    if !links.is_empty() {
+        content.push_str("\n\n");
+        content.push_str(&links.join(" | "));
+    }
+
+    json!({
+        "msgtype": "markdown",
+        "markdown": { "content": helper::truncate_bytes(content, MARKDOWN_LIMIT) },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::robot::{RobotButton, RobotCover};
+
+    #[test]
+    fn body() {
+        let mut card = RobotCard {
+            title: "[test] user".into(),
+            markdown: "meow".into(),
+            cover: None,
+            image_urls: vec!["https://example.com/0.jpg".into()],
+            buttons: vec![RobotButton {
+                text: "View".into(),
+                url: "https://example.com/post".into(),
+            }],
+        };
+        assert_eq!(
+            make_body(&card),
+            json!({
+                "msgtype": "markdown",
+                "markdown": {
+                    "content": "**[test] user**\nmeow\n\n[🖼️ 1](https://example.com/0.jpg) | [View](https://example.com/post)",
+                },
+            })
+        );
+
+        card.cover = Some(RobotCover {
+            image_url: "https://example.com/cover.jpg".into(),
+            title: "title".into(),
+            url: "https://example.com/live".into(),
+        });
+        assert_eq!(
+            make_body(&card)["news"]["articles"][0],
+            json!({
+                "title": "title",
+                "description": "[test] user",
+                "url": "https://example.com/live",
+                "picurl": "https://example.com/cover.jpg",
+            })
+        );
+    }
+
+    #[test]
+    fn config() {
+        let params: ConfigParams =
+            toml::from_str(r#"webhook_url = "https://qyapi.weixin.qq.com/cgi-bin/webhook/send""#)
+                .unwrap();
+        assert!(config::Validator::validate(&params).is_err());
+
+        let params: ConfigParams = toml::from_str(
+            r#"webhook_url = "https://qyapi.weixin.qq.com/cgi-bin/webhook/send?key=meow""#,
+        )
+        .unwrap();
+        config::Validator::validate(&params).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+// Shared by the notifiers of push services, which only display a title and a
+// message with a few extras
+
+use spdlog::prelude::*;
+
+use crate::{
+    config,
+    source::{
+        KindPayload, LiveStatusKind, Notification, NotificationKind, NotificationPayload, Post,
+        PostAttachment, PostUrl, RepostFrom, StatusSource,
+    },
+};
+
+#[derive(Debug, PartialEq)]
+pub struct PushMessage {
+    pub title: String,
+    pub message: String,
+    pub priority: PushPriority,
+    pub click_url: Option<String>,
+    pub attachment_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PushPriority {
+    Low,
+    Default,
+    High,
+}
+
+pub async fn make_messages(
+    notifications: &config::Notifications,
+    notification: &Notification<'_>,
+) -> Vec<PushMessage> {
+    let source = notification.source;
+    let platform = source.platform.display_name;
+    let author = |name: &str| {
+        if notifications.author_name {
+            format!(" {name}")
+        } else {
+            String::new()
+        }
+    };
+
+    match &notification.kind {
+        NotificationKind::LiveOnline(live_status) => {
+            if !notifications.live_online {
+                info!("live_online notification is disabled, skip notifying");
+                return vec![];
+            }
+            // Pushes cannot be edited, so only the online is notified
+            let LiveStatusKind::Online { .. } = live_status.kind else {
+                return vec![];
+            };
+            vec![PushMessage {
+                title: format!("[{platform}] 🟢{}", author(&live_status.streamer_name)),
+                message: match &live_status.area {
+                    Some(area) => format!("{}\n{area}", live_status.title),
+                    None => live_status.title.clone(),
+                },
+                priority: PushPriority::High,
+                click_url: Some(live_status.live_url.clone()),
+                attachment_url: Some(live_status.cover_image_url.clone()),
+            }]
+        }
+        NotificationKind::LiveTitle(live_status, old_title) => {
+            if !notifications.live_title {
+                info!("live_title notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![PushMessage {
+                title: format!("[{platform}] ✏️{}", author(&live_status.streamer_name)),
+                message: format!("{} ⬅️ {old_title}", live_status.title),
+                priority: PushPriority::Default,
+                click_url: Some(live_status.live_url.clone()),
+                attachment_url: None,
+            }]
+        }
+        NotificationKind::LiveArea(live_status, old_area) => {
+            if !notifications.live_area {
+                info!("live_area notification is disabled, skip notifying");
+                return vec![];
+            }
+            let Some(area) = &live_status.area else {
+                return vec![];
+            };
+            vec![PushMessage {
+                title: format!("[{platform}] 🏷️{}", author(&live_status.streamer_name)),
+                message: format!("{old_area} ➡️ {area}"),
+                priority: PushPriority::Default,
+                click_url: Some(live_status.live_url.clone()),
+                attachment_url: None,
+            }]
+        }
+        NotificationKind::Posts(posts) => {
+            if !notifications.post {
+                info!("post notification is disabled, skip notifying");
+                return vec![];
+            }
+            posts
+                .0
+                .iter()
+                .map(|post| {
+                    make_post_message(
+                        notifications.author_name,
+                        post,
+                        source,
+                        "",
+                        PushPriority::Default,
+                    )
+                })
+                .collect()
+        }
+        NotificationKind::PostEdited(post, _old) => {
+            if !notifications.post_edited {
+                info!("post_edited notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![make_post_message(
+                notifications.author_name,
+                post,
+                source,
+                "✏️ ",
+                PushPriority::Low,
+            )]
+        }
+        NotificationKind::PostDeleted(post) => {
+            if !notifications.post_deleted {
+                info!("post_deleted notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![make_post_message(
+                notifications.author_name,
+                post,
+                source,
+                "🗑️ ",
+                PushPriority::Low,
+            )]
+        }
+        NotificationKind::Log(message) => {
+            if !notifications.log {
+                info!("log notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![PushMessage {
+                title: "[closely] Log".into(),
+                message: message.clone(),
+                priority: PushPriority::Low,
+                click_url: None,
+                attachment_url: None,
+            }]
+        }
+        NotificationKind::Playback(_) | NotificationKind::Document(_) => {
+            let (toggle, enabled, emoji) = match &notification.kind {
+                NotificationKind::Playback(_) => ("playback", notifications.playback, "🎥"),
+                _ => ("document", notifications.document, "📊"),
+            };
+            if !enabled {
+                info!("{toggle} notification is disabled, skip notifying");
+                return vec![];
+            }
+            // Files cannot be pushed, only let the users know. The payload gives the
+            // metadata without loading the playback
+            let payload = NotificationPayload::new(notification).await;
+            let (KindPayload::Playback { file, .. } | KindPayload::Document { file }) =
+                payload.kind
+            else {
+                unreachable!()
+            };
+            let message = match file.size {
+                Some(size) => format!(
+                    "{emoji} {} ({})",
+                    file.name,
+                    humansize::format_size(size, humansize::BINARY)
+                ),
+                None => format!("{emoji} {}", file.name),
+            };
+            vec![PushMessage {
+                title: format!("[{platform}] {emoji}"),
+                message,
+                priority: PushPriority::Default,
+                click_url: None,
+                attachment_url: None,
+            }]
+        }
+    }
+}
+
+fn make_post_message(
+    author_name: bool,
+    post: &Post,
+    source: &StatusSource,
+    annotation: &str,
+    priority: PushPriority,
+) -> PushMessage {
+    let mut message = post.content.fallback();
+    if let Some(RepostFrom::Recursion(repost_from)) = &post.repost_from {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message.push_str(&format!(
+            "🔁 {}: {}",
+            repost_from.user.nickname,
+            repost_from.content.fallback()
+        ));
+    }
+
+    PushMessage {
+        title: if author_name {
+            format!(
+                "[{}] {annotation}{}",
+                source.platform.display_name, post.user.nickname
+            )
+        } else {
+            format!(
+                "[{}] {}",
+                source.platform.display_name,
+                annotation.trim_end()
+            )
+            .trim_end()
+            .into()
+        },
+        message,
+        priority,
+        click_url: match post.urls_recursive().major() {
+            PostUrl::Clickable(url) => Some(url.url.clone()),
+            PostUrl::Identity(_) => None,
+        },
+        attachment_url: post
+            .attachments_recursive(true)
+            .into_iter()
+            .find_map(|attachment| match attachment {
+                PostAttachment::Image(image) => Some(image.media_url.clone()),
+                PostAttachment::Video(_) => None,
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        platform::PlatformMetadata,
+        source::{LiveStatus, PostAttachmentImage, PostAttachmentVideo, PostContent, PostsRef},
+    };
+
+    fn source() -> StatusSource {
+        StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn live_messages() {
+        let source = source();
+        let mut live_status = LiveStatus {
+            kind: LiveStatusKind::Online { start_time: None },
+            title: "title".into(),
+            area: None,
+            streamer_name: "streamer".into(),
+            cover_image_url: "https://example.com/cover.jpg".into(),
+            live_url: "https://example.com/live".into(),
+        };
+
+        let notification = Notification {
+            kind: NotificationKind::LiveOnline(&live_status),
+            source: &source,
+        };
+        let mut notifications = config::Notifications {
+            author_name: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            make_messages(&notifications, &notification).await,
+            vec![PushMessage {
+                title: "[test] 🟢 streamer".into(),
+                message: "title".into(),
+                priority: PushPriority::High,
+                click_url: Some("https://example.com/live".into()),
+                attachment_url: Some("https://example.com/cover.jpg".into()),
+            }]
+        );
+
+        notifications.author_name = false;
+        assert_eq!(
+            make_messages(&notifications, &notification).await[0].title,
+            "[test] 🟢"
+        );
+
+        live_status.kind = LiveStatusKind::Offline;
+        let notification = Notification {
+            kind: NotificationKind::LiveOnline(&live_status),
+            source: &source,
+        };
+        assert!(make_messages(&notifications, &notification)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn post_messages() {
+        let source = source();
+        let post = Post::mock(
+            PostContent::plain("meow"),
+            vec![
+                PostAttachment::Video(PostAttachmentVideo {
+                    media_url: "https://example.com/0.mp4".into(),
+                    has_spoiler: false,
+                }),
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: "https://example.com/1.jpg".into(),
+                    has_spoiler: false,
+                }),
+            ],
+        );
+
+        let notification = Notification {
+            kind: NotificationKind::Posts(PostsRef(vec![&post])),
+            source: &source,
+        };
+        let mut notifications = config::Notifications {
+            author_name: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            make_messages(&notifications, &notification).await,
+            vec![PushMessage {
+                title: "[test] user".into(),
+                message: "meow".into(),
+                priority: PushPriority::Default,
+                click_url: Some("https://example.com/post".into()),
+                attachment_url: Some("https://example.com/1.jpg".into()),
+            }]
+        );
+
+        notifications.author_name = false;
+        assert_eq!(
+            make_messages(&notifications, &notification).await[0].title,
+            "[test]"
+        );
+
+        notifications.post = false;
+        assert!(make_messages(&notifications, &notification)
+            .await
+            .is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+// Shared by the notifiers of group robots in enterprise IMs, which render a
+// card with a markdown body, images and jump buttons. Messages of the robots
+// cannot be edited, so live status changes other than online are not notified
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, ensure};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json as json;
+use sha2::Sha256;
+use spdlog::prelude::*;
+
+use crate::{
+    config, helper, secret_enum,
+    source::{
+        KindPayload, LiveStatusKind, Notification, NotificationKind, NotificationPayload, Post,
+        PostAttachment, PostContent, PostContentPart, PostUrl, RepostFrom, StatusSource,
+    },
+};
+
+// Key for signing the requests, if the robot has signature verification enabled
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigSecret {
+        Secret(String),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RobotCard {
+    pub title: String,
+    pub markdown: String,
+    pub cover: Option<RobotCover>,
+    pub image_urls: Vec<String>,
+    pub buttons: Vec<RobotButton>,
+}
+
+// Cover of the live, some robots display it with the title as a news article
+#[derive(Debug, PartialEq)]
+pub struct RobotCover {
+    pub image_url: String,
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RobotButton {
+    pub text: String,
+    pub url: String,
+}
+
+pub async fn make_cards(
+    notifications: &config::Notifications,
+    notification: &Notification<'_>,
+) -> Vec<RobotCard> {
+    let source = notification.source;
+    let platform = source.platform.display_name;
+    let author_prefix = |name: &str| {
+        if notifications.author_name {
+            format!("[{name}] ")
+        } else {
+            String::new()
+        }
+    };
+
+    match &notification.kind {
+        NotificationKind::LiveOnline(live_status) => {
+            if !notifications.live_online {
+                info!("live_online notification is disabled, skip notifying");
+                return vec![];
+            }
+            let LiveStatusKind::Online { .. } = live_status.kind else {
+                return vec![];
+            };
+            let mut markdown = link(&live_status.title, &live_status.live_url);
+            if let Some(area) = &live_status.area {
+                markdown.push_str(&format!("\n{area}"));
+            }
+            vec![RobotCard {
+                title: format!(
+                    "[{platform}] 🟢 {}",
+                    author_prefix(&live_status.streamer_name)
+                )
+                .trim_end()
+                .to_owned(),
+                markdown,
+                cover: Some(RobotCover {
+                    image_url: live_status.cover_image_url.clone(),
+                    title: live_status.title.clone(),
+                    url: live_status.live_url.clone(),
+                }),
+                image_urls: vec![],
+                buttons: vec![RobotButton {
+                    text: "Watch".into(),
+                    url: live_status.live_url.clone(),
+                }],
+            }]
+        }
+        NotificationKind::LiveTitle(live_status, old_title) => {
+            if !notifications.live_title {
+                info!("live_title notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![RobotCard {
+                title: format!(
+                    "[{platform}] ✏️ {}",
+                    author_prefix(&live_status.streamer_name)
+                )
+                .trim_end()
+                .to_owned(),
+                markdown: format!(
+                    "{} ⬅️ {old_title}",
+                    link(&live_status.title, &live_status.live_url)
+                ),
+                cover: None,
+                image_urls: vec![],
+                buttons: vec![],
+            }]
+        }
+        NotificationKind::LiveArea(live_status, old_area) => {
+            if !notifications.live_area {
+                info!("live_area notification is disabled, skip notifying");
+                return vec![];
+            }
+            let Some(area) = &live_status.area else {
+                return vec![];
+            };
+            vec![RobotCard {
+                title: format!(
+                    "[{platform}] 🏷️ {}",
+                    author_prefix(&live_status.streamer_name)
+                )
+                .trim_end()
+                .to_owned(),
+                markdown: link(&format!("{old_area} ➡️ {area}"), &live_status.live_url),
+                cover: None,
+                image_urls: vec![],
+                buttons: vec![],
+            }]
+        }
+        NotificationKind::Posts(posts) => {
+            if !notifications.post {
+                info!("post notification is disabled, skip notifying");
+                return vec![];
+            }
+            posts
+                .0
+                .iter()
+                .map(|post| make_post_card(notifications.author_name, post, source, ""))
+                .collect()
+        }
+        NotificationKind::PostEdited(post, _old) => {
+            if !notifications.post_edited {
+                info!("post_edited notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![make_post_card(
+                notifications.author_name,
+                post,
+                source,
+                "✏️ ",
+            )]
+        }
+        NotificationKind::PostDeleted(post) => {
+            if !notifications.post_deleted {
+                info!("post_deleted notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![make_post_card(
+                notifications.author_name,
+                post,
+                source,
+                "🗑️ ",
+            )]
+        }
+        NotificationKind::Log(message) => {
+            if !notifications.log {
+                info!("log notification is disabled, skip notifying");
+                return vec![];
+            }
+            vec![RobotCard {
+                title: "[closely] Log".into(),
+                markdown: message.clone(),
+                cover: None,
+                image_urls: vec![],
+                buttons: vec![],
+            }]
+        }
+        NotificationKind::Playback(_) | NotificationKind::Document(_) => {
+            let (toggle, enabled, emoji) = match &notification.kind {
+                NotificationKind::Playback(_) => ("playback", notifications.playback, "🎥"),
+                _ => ("document", notifications.document, "📊"),
+            };
+            if !enabled {
+                info!("{toggle} notification is disabled, skip notifying");
+                return vec![];
+            }
+            // Robots cannot send files, only let the users know. The payload gives the
+            // metadata without loading the playback
+            let payload = NotificationPayload::new(notification).await;
+            let (KindPayload::Playback { file, .. } | KindPayload::Document { file }) =
+                payload.kind
+            else {
+                unreachable!()
+            };
+            let markdown = match file.size {
+                Some(size) => format!(
+                    "{} ({})",
+                    file.name,
+                    humansize::format_size(size, humansize::BINARY)
+                ),
+                None => file.name,
+            };
+            vec![RobotCard {
+                title: format!("[{platform}] {emoji}"),
+                markdown,
+                cover: None,
+                image_urls: vec![],
+                buttons: vec![],
+            }]
+        }
+    }
+}
+
+fn make_post_card(
+    author_name: bool,
+    post: &Post,
+    source: &StatusSource,
+    annotation: &str,
+) -> RobotCard {
+    let mut title = format!("[{}] {annotation}", source.platform.display_name);
+    if author_name {
+        title.push_str(&post.user.nickname);
+    }
+
+    let mut markdown = String::new();
+    match &post.repost_from {
+        Some(RepostFrom::Recursion(repost_from)) => {
+            if !post.content.is_empty() {
+                markdown.push_str("💬 ");
+                push_content(&mut markdown, &post.content);
+                markdown.push_str("\n\n");
+            }
+
+            let mut quote = "🔁 ".to_owned();
+            match repost_from.urls_recursive().major() {
+                PostUrl::Clickable(url) => {
+                    quote.push_str(&link(&repost_from.user.nickname, &url.url))
+                }
+                PostUrl::Identity(_) => quote.push_str(&repost_from.user.nickname),
+            }
+            quote.push_str(": ");
+            push_content(&mut quote, &repost_from.content);
+            markdown.push_str(&itertools::join(
+                quote.lines().map(|line| format!("> {line}")),
+                "\n",
+            ));
+        }
+        None => push_content(&mut markdown, &post.content),
+    }
+
+    let mut image_urls = vec![];
+    let mut videos = vec![];
+    for attachment in post.attachments_recursive(true) {
+        match attachment {
+            PostAttachment::Image(image) => image_urls.push(image.media_url.clone()),
+            PostAttachment::Video(video) => {
+                videos.push(link(&format!("🎬 {}", videos.len() + 1), &video.media_url))
+            }
+        }
+    }
+    if !videos.is_empty() {
+        markdown.push_str("\n\n");
+        markdown.push_str(&videos.join(" "));
+    }
+
+    RobotCard {
+        title: title.trim_end().to_owned(),
+        markdown,
+        cover: None,
+        image_urls,
+        buttons: post
+            .urls_recursive()
+            .iter()
+            .filter_map(|url| url.as_clickable())
+            .map(|url| RobotButton {
+                text: url.display.clone(),
+                url: url.url.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn push_content(markdown: &mut String, content: &PostContent) {
+    content.parts().for_each(|part| match part {
+        PostContentPart::Plain(text) => markdown.push_str(text),
+        PostContentPart::Link { display, url } => markdown.push_str(&link(display, url)),
+        PostContentPart::InlineAttachment(_) => {
+            // Ignore, we handle it in post.attachments
+        }
+    });
+}
+
+pub fn link(display: &str, url: &str) -> String {
+    // Escaping is not supported by all of them, replace the brackets instead
+    format!("[{}]({url})", display.replace('[', "［").replace(']', "］"))
+}
+
+pub fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+pub fn hmac_sha256_base64(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(message);
+    BASE64_STANDARD.encode(mac.finalize().into_bytes())
+}
+
+// Returns the response body, the robots report errors in the body with a status
+// of success
+pub async fn post(url: reqwest::Url, body: &json::Value) -> anyhow::Result<json::Value> {
+    let resp = helper::reqwest_client()?
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|err| anyhow!("failed to send request: {err}"))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+    ensure!(
+        status.is_success(),
+        "response status is not success: {status}, response '{text}'"
+    );
+    json::from_str(&text).map_err(|err| anyhow!("failed to deserialize response '{text}': {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        platform::PlatformMetadata,
+        source::{PostAttachmentImage, PostAttachmentVideo, PostsRef},
+    };
+
+    #[tokio::test]
+    async fn post_cards() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = Post::mock(
+            PostContent::plain("meow ").with_link("[link]", "https://example.com/link"),
+            vec![
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: "https://example.com/0.jpg".into(),
+                    has_spoiler: false,
+                }),
+                PostAttachment::Video(PostAttachmentVideo {
+                    media_url: "https://example.com/1.mp4".into(),
+                    has_spoiler: false,
+                }),
+            ],
+        );
+
+        let notification = Notification {
+            kind: NotificationKind::Posts(PostsRef(vec![&post])),
+            source: &source,
+        };
+        let mut notifications = config::Notifications {
+            author_name: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            make_cards(&notifications, &notification).await,
+            vec![RobotCard {
+                title: "[test] user".into(),
+                markdown:
+                    "meow [［link］](https://example.com/link)\n\n[🎬 1](https://example.com/1.mp4)"
+                        .into(),
+                cover: None,
+                image_urls: vec!["https://example.com/0.jpg".into()],
+                buttons: vec![RobotButton {
+                    text: "View".into(),
+                    url: "https://example.com/post".into(),
+                }],
+            }]
+        );
+
+        notifications.author_name = false;
+        assert_eq!(
+            make_cards(&notifications, &notification).await[0].title,
+            "[test]"
+        );
+    }
+}
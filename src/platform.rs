@@ -1,8 +1,41 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+
 pub trait PlatformTrait: Send + Sync {
     fn metadata(&self) -> PlatformMetadata;
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct PlatformMetadata {
     pub display_name: &'static str,
 }
+
+impl<'de> Deserialize<'de> for PlatformMetadata {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            display_name: String,
+        }
+
+        // Display names are a small fixed set, intern them so that they are leaked at
+        // most once
+        static INTERNED: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(Default::default);
+
+        let raw = Raw::deserialize(deserializer)?;
+        let mut interned = INTERNED.lock().unwrap();
+        let display_name = match interned.get(raw.display_name.as_str()) {
+            Some(display_name) => *display_name,
+            None => {
+                let display_name = &*Box::leak(raw.display_name.into_boxed_str());
+                interned.insert(display_name);
+                display_name
+            }
+        };
+        Ok(Self { display_name })
+    }
+}
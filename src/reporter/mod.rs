@@ -116,6 +116,7 @@ impl ConfigHeartbeatHttpGet {
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct ReporterParams {
     pub heartbeat: config::Accessor<Option<ConfigHeartbeat>>,
 }
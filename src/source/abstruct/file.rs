@@ -7,6 +7,7 @@ use std::{
 use anyhow::{anyhow, ensure};
 use bytes::Bytes;
 use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
 use spdlog::prelude::*;
 use tempfile::tempdir;
 use tokio::{fs, sync::Mutex};
@@ -75,7 +76,7 @@ impl fmt::Debug for FileRef<'_> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Playback {
     pub live_start_time: Option<DateTime<Local>>,
     pub file_path: PathBuf,
@@ -95,7 +96,7 @@ impl fmt::Display for Playback {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PlaybackFormat {
     Flv,
     Mp4,
@@ -176,7 +177,7 @@ impl fmt::Display for PlaybackRef<'_> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Document {
     pub file_path: PathBuf,
 }
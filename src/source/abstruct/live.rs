@@ -1,11 +1,16 @@
 use std::{fmt, time::SystemTime};
 
 use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LiveStatus {
     pub kind: LiveStatusKind,
     pub title: String,
+    // Not all platforms have areas (categories), and it's missing in states
+    // persisted by older versions
+    #[serde(default)]
+    pub area: Option<LiveArea>,
     pub streamer_name: String,
     pub cover_image_url: String,
     pub live_url: String,
@@ -26,7 +31,24 @@ impl fmt::Display for LiveStatus {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LiveArea {
+    pub parent: String,
+    pub child: String,
+}
+
+impl fmt::Display for LiveArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Some platforms only have a single level of categories
+        if self.parent.is_empty() {
+            write!(f, "{}", self.child)
+        } else {
+            write!(f, "{} · {}", self.parent, self.child)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum LiveStatusKind {
     Online { start_time: Option<SystemTime> },
     Offline,
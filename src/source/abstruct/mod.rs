@@ -1,6 +1,7 @@
 mod file;
 mod live;
 mod notification;
+mod payload;
 mod post;
 mod status;
 mod update;
@@ -8,6 +9,7 @@ mod update;
 pub use file::*;
 pub use live::*;
 pub use notification::*;
+pub use payload::*;
 pub use post::*;
 pub use status::*;
 pub use update::*;
@@ -1,6 +1,13 @@
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
-use super::{DocumentRef, LiveStatus, PlaybackRef, PostsRef, StatusSource};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::{
+    Document, DocumentRef, FileRef, LiveArea, LiveStatus, Playback, PlaybackRef, Post, Posts,
+    PostsRef, StatusSource,
+};
 
 #[derive(Debug)]
 pub struct Notification<'a> {
@@ -18,7 +25,10 @@ impl fmt::Display for Notification<'_> {
 pub enum NotificationKind<'a> {
     LiveOnline(&'a LiveStatus),
     LiveTitle(&'a LiveStatus, &'a str /* old title */),
+    LiveArea(&'a LiveStatus, &'a LiveArea /* old area */),
     Posts(PostsRef<'a>),
+    PostEdited(&'a Post, &'a Post /* old */),
+    PostDeleted(&'a Post),
     Log(String),
     Playback(PlaybackRef<'a>),
     Document(DocumentRef<'a>),
@@ -31,10 +41,245 @@ impl fmt::Display for NotificationKind<'_> {
             Self::LiveTitle(live_status, old_title) => {
                 write!(f, "{live_status}, old title '{old_title}'")
             }
+            Self::LiveArea(live_status, old_area) => {
+                write!(f, "{live_status}, old area '{old_area}'")
+            }
             Self::Posts(posts) => write!(f, "{posts}"),
+            Self::PostEdited(post, _old) => write!(f, "edited post {:?}", post.urls.major()),
+            Self::PostDeleted(post) => write!(f, "deleted post {:?}", post.urls.major()),
             Self::Log(message) => write!(f, "log '{message}'"),
             Self::Playback(playback) => write!(f, "{playback}"),
             Self::Document(document) => write!(f, "{document}"),
         }
     }
 }
+
+// Owned form of `Notification`, so that it can be persisted and delivered later
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NotificationOwned {
+    kind: NotificationKindOwned,
+    source: StatusSource,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NotificationKindOwned {
+    LiveOnline(LiveStatus),
+    LiveTitle {
+        live: LiveStatus,
+        old_title: String,
+    },
+    LiveArea {
+        live: LiveStatus,
+        old_area: LiveArea,
+    },
+    Posts(Posts),
+    PostEdited {
+        post: Post,
+        old: Post,
+    },
+    PostDeleted(Post),
+    Log {
+        message: String,
+    },
+    Playback(Playback),
+    Document(Document),
+}
+
+impl NotificationOwned {
+    pub fn new(notification: &Notification<'_>) -> anyhow::Result<Self> {
+        let kind = match &notification.kind {
+            NotificationKind::LiveOnline(live) => {
+                NotificationKindOwned::LiveOnline((*live).clone())
+            }
+            NotificationKind::LiveTitle(live, old_title) => NotificationKindOwned::LiveTitle {
+                live: (*live).clone(),
+                old_title: old_title.to_string(),
+            },
+            NotificationKind::LiveArea(live, old_area) => NotificationKindOwned::LiveArea {
+                live: (*live).clone(),
+                old_area: (*old_area).clone(),
+            },
+            NotificationKind::Posts(posts) => NotificationKindOwned::Posts(Posts(
+                posts.0.iter().map(|&post| post.clone()).collect(),
+            )),
+            NotificationKind::PostEdited(post, old) => NotificationKindOwned::PostEdited {
+                post: (*post).clone(),
+                old: (*old).clone(),
+            },
+            NotificationKind::PostDeleted(post) => {
+                NotificationKindOwned::PostDeleted((*post).clone())
+            }
+            NotificationKind::Log(message) => NotificationKindOwned::Log {
+                message: message.clone(),
+            },
+            NotificationKind::Playback(playback) => NotificationKindOwned::Playback(Playback {
+                live_start_time: playback.live_start_time,
+                file_path: playback.local_file.0.into(),
+                format: playback.local_file.1,
+            }),
+            NotificationKind::Document(document) => NotificationKindOwned::Document(Document {
+                file_path: document
+                    .file
+                    .path
+                    .ok_or_else(|| anyhow!("document '{document}' is not backed by a file"))?
+                    .into(),
+            }),
+        };
+        Ok(Self {
+            kind,
+            source: notification.source.clone(),
+        })
+    }
+
+    pub async fn to_notification(&self) -> anyhow::Result<Notification<'_>> {
+        let kind = match &self.kind {
+            NotificationKindOwned::LiveOnline(live) => NotificationKind::LiveOnline(live),
+            NotificationKindOwned::LiveTitle { live, old_title } => {
+                NotificationKind::LiveTitle(live, old_title)
+            }
+            NotificationKindOwned::LiveArea { live, old_area } => {
+                NotificationKind::LiveArea(live, old_area)
+            }
+            NotificationKindOwned::Posts(posts) => {
+                NotificationKind::Posts(PostsRef(posts.0.iter().collect()))
+            }
+            NotificationKindOwned::PostEdited { post, old } => {
+                NotificationKind::PostEdited(post, old)
+            }
+            NotificationKindOwned::PostDeleted(post) => NotificationKind::PostDeleted(post),
+            NotificationKindOwned::Log { message } => NotificationKind::Log(message.clone()),
+            NotificationKindOwned::Playback(playback) => NotificationKind::Playback(PlaybackRef {
+                live_start_time: playback.live_start_time,
+                local_file: (&playback.file_path, playback.format),
+                loaded: Mutex::new(HashMap::new()),
+            }),
+            NotificationKindOwned::Document(document) => NotificationKind::Document(DocumentRef {
+                file: FileRef::new(&document.file_path).await.map_err(|err| {
+                    anyhow!(
+                        "failed to read document file '{:?}': {err}",
+                        document.file_path
+                    )
+                })?,
+            }),
+        };
+        Ok(Notification {
+            kind,
+            source: &self.source,
+        })
+    }
+}
+
+impl fmt::Display for NotificationOwned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            NotificationKindOwned::LiveOnline(live) => write!(f, "{live}"),
+            NotificationKindOwned::LiveTitle { live, old_title } => {
+                write!(f, "{live}, old title '{old_title}'")
+            }
+            NotificationKindOwned::LiveArea { live, old_area } => {
+                write!(f, "{live}, old area '{old_area}'")
+            }
+            NotificationKindOwned::Posts(posts) => write!(f, "{posts}"),
+            NotificationKindOwned::PostEdited { post, old: _ } => {
+                write!(f, "edited post {:?}", post.urls.major())
+            }
+            NotificationKindOwned::PostDeleted(post) => {
+                write!(f, "deleted post {:?}", post.urls.major())
+            }
+            NotificationKindOwned::Log { message } => write!(f, "log '{message}'"),
+            NotificationKindOwned::Playback(playback) => write!(f, "{playback}"),
+            NotificationKindOwned::Document(document) => write!(f, "{document}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        platform::PlatformMetadata,
+        source::{LiveStatusKind, Post, PostContent, PostUrl, User},
+    };
+
+    #[tokio::test]
+    async fn owned_round_trip() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let live = LiveStatus {
+            kind: LiveStatusKind::Online { start_time: None },
+            title: "title".into(),
+            area: Some(LiveArea {
+                parent: "parent".into(),
+                child: "child".into(),
+            }),
+            streamer_name: "streamer".into(),
+            cover_image_url: "https://example.com/cover.jpg".into(),
+            live_url: "https://example.com/live".into(),
+        };
+        let old_area = LiveArea {
+            parent: "old parent".into(),
+            child: "old child".into(),
+        };
+        let post = Post {
+            user: User {
+                nickname: "user".into(),
+                profile_url: "https://example.com/user".into(),
+                avatar_url: None,
+            },
+            content: PostContent::plain("meow"),
+            urls: PostUrl::new_clickable("https://example.com/post", "View").into(),
+            time: chrono::Local::now(),
+            is_pinned: false,
+            repost_from: None,
+            attachments: vec![],
+        };
+
+        let notifications = [
+            Notification {
+                kind: NotificationKind::LiveTitle(&live, "old"),
+                source: &source,
+            },
+            Notification {
+                kind: NotificationKind::LiveArea(&live, &old_area),
+                source: &source,
+            },
+            Notification {
+                kind: NotificationKind::Posts(PostsRef(vec![&post])),
+                source: &source,
+            },
+        ];
+        for notification in notifications {
+            let owned = NotificationOwned::new(&notification).unwrap();
+            let owned: NotificationOwned =
+                serde_json::from_str(&serde_json::to_string(&owned).unwrap()).unwrap();
+            let restored = owned.to_notification().await.unwrap();
+
+            assert_eq!(restored.source, &source);
+            match (restored.kind, notification.kind) {
+                (
+                    NotificationKind::LiveTitle(restored, restored_old),
+                    NotificationKind::LiveTitle(live, old),
+                ) => {
+                    assert_eq!(restored, live);
+                    assert_eq!(restored_old, old);
+                }
+                (
+                    NotificationKind::LiveArea(restored, restored_old),
+                    NotificationKind::LiveArea(live, old),
+                ) => {
+                    assert_eq!(restored, live);
+                    assert_eq!(restored_old, old);
+                }
+                (NotificationKind::Posts(restored), NotificationKind::Posts(posts)) => {
+                    assert_eq!(restored.0, posts.0)
+                }
+                _ => panic!("kind mismatched"),
+            }
+        }
+    }
+}
@@ -0,0 +1,439 @@
+// Stable JSON representation of notifications for external consumers, e.g. the
+// `Webhook` notifier. Unlike the forms persisted in states, it's a public
+// interface, so only add fields here, and bump `PAYLOAD_VERSION` for anything
+// breaking.
+//
+// ```json
+// {
+//   "version": 1,
+//   "kind": "live_online" | "live_title" | "live_area" | "posts" | "post_edited"
+//         | "post_deleted" | "log" | "playback" | "document",
+//   "source": {
+//     "platform": "bilibili.live",
+//     "user": { "display_name", "profile_url" } | null
+//   },
+//   ...fields of the kind, see `KindPayload`
+// }
+// ```
+//
+// Times are RFC 3339 strings. Tagged objects (content parts, URLs and
+// attachments) have a "type" field in snake_case.
+//
+// The payload is the same regardless of the notifier's presentation options,
+// e.g. `author_name`: authors are always included and it's up to consumers
+// whether to show them. Only the toggles apply, deciding which kinds are sent.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::{
+    FileRef, LiveArea, LiveStatus, LiveStatusKind, Notification, NotificationKind, Post,
+    PostAttachment, PostContentPart, PostUrl, RepostFrom, StatusSource,
+};
+
+pub const PAYLOAD_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct NotificationPayload<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub kind: KindPayload<'a>,
+    pub source: SourcePayload<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KindPayload<'a> {
+    // Also sent when the live goes offline or banned, see `LivePayload::status`
+    LiveOnline {
+        live: LivePayload<'a>,
+    },
+    LiveTitle {
+        live: LivePayload<'a>,
+        old_title: &'a str,
+    },
+    LiveArea {
+        live: LivePayload<'a>,
+        old_area: AreaPayload<'a>,
+    },
+    Posts {
+        posts: Vec<PostPayload<'a>>,
+    },
+    PostEdited {
+        post: PostPayload<'a>,
+        old: PostPayload<'a>,
+    },
+    PostDeleted {
+        post: PostPayload<'a>,
+    },
+    Log {
+        message: &'a str,
+    },
+    Playback {
+        live_start_time: Option<String>,
+        file: FilePayload,
+    },
+    Document {
+        file: FilePayload,
+    },
+}
+
+impl KindPayload<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LiveOnline { .. } => "live_online",
+            Self::LiveTitle { .. } => "live_title",
+            Self::LiveArea { .. } => "live_area",
+            Self::Posts { .. } => "posts",
+            Self::PostEdited { .. } => "post_edited",
+            Self::PostDeleted { .. } => "post_deleted",
+            Self::Log { .. } => "log",
+            Self::Playback { .. } => "playback",
+            Self::Document { .. } => "document",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourcePayload<'a> {
+    pub platform: &'static str,
+    pub user: Option<SourceUserPayload<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceUserPayload<'a> {
+    pub display_name: &'a str,
+    pub profile_url: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LivePayload<'a> {
+    // "online", "offline" or "banned"
+    pub status: &'static str,
+    pub start_time: Option<String>,
+    pub title: &'a str,
+    pub area: Option<AreaPayload<'a>>,
+    pub streamer_name: &'a str,
+    pub cover_image_url: &'a str,
+    pub live_url: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AreaPayload<'a> {
+    pub parent: &'a str,
+    pub child: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostPayload<'a> {
+    // Unique within the platform
+    pub id: &'a str,
+    pub user: UserPayload<'a>,
+    pub content: Vec<ContentPartPayload<'a>>,
+    // The content as plain text, links are rendered in Markdown
+    pub content_fallback: String,
+    pub urls: Vec<UrlPayload<'a>>,
+    pub time: String,
+    pub is_pinned: bool,
+    // Excluding the attachments inlined in the content
+    pub attachments: Vec<AttachmentPayload<'a>>,
+    pub repost_from: Option<Box<PostPayload<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPayload<'a> {
+    pub nickname: &'a str,
+    pub profile_url: &'a str,
+    pub avatar_url: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPartPayload<'a> {
+    Plain { text: &'a str },
+    Link { display: &'a str, url: &'a str },
+    InlineAttachment { attachment: AttachmentPayload<'a> },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UrlPayload<'a> {
+    Clickable { url: &'a str, display: &'a str },
+    // Posts without a URL are identified by this
+    Identity { id: &'a str },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AttachmentPayload<'a> {
+    Image {
+        media_url: &'a str,
+        has_spoiler: bool,
+    },
+    Video {
+        media_url: &'a str,
+        has_spoiler: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilePayload {
+    pub name: String,
+    pub size: Option<u64>,
+    // Local path on the host running closely
+    pub path: Option<String>,
+}
+
+impl<'a> NotificationPayload<'a> {
+    pub async fn new(notification: &'a Notification<'_>) -> Self {
+        let kind = match &notification.kind {
+            NotificationKind::LiveOnline(live) => KindPayload::LiveOnline {
+                live: LivePayload::new(live),
+            },
+            NotificationKind::LiveTitle(live, old_title) => KindPayload::LiveTitle {
+                live: LivePayload::new(live),
+                old_title,
+            },
+            NotificationKind::LiveArea(live, old_area) => KindPayload::LiveArea {
+                live: LivePayload::new(live),
+                old_area: AreaPayload::new(old_area),
+            },
+            NotificationKind::Posts(posts) => KindPayload::Posts {
+                posts: posts.0.iter().map(|post| PostPayload::new(post)).collect(),
+            },
+            NotificationKind::PostEdited(post, old) => KindPayload::PostEdited {
+                post: PostPayload::new(post),
+                old: PostPayload::new(old),
+            },
+            NotificationKind::PostDeleted(post) => KindPayload::PostDeleted {
+                post: PostPayload::new(post),
+            },
+            NotificationKind::Log(message) => KindPayload::Log { message },
+            NotificationKind::Playback(playback) => {
+                let (path, _format) = playback.local_file;
+                // Don't load the playback, it could be huge
+                let size = tokio::fs::metadata(path)
+                    .await
+                    .map(|metadata| metadata.len())
+                    .ok();
+                KindPayload::Playback {
+                    live_start_time: playback.live_start_time.map(|time| time.to_rfc3339()),
+                    file: FilePayload {
+                        name: path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into())
+                            .unwrap_or_default(),
+                        size,
+                        path: Some(path.to_string_lossy().into()),
+                    },
+                }
+            }
+            NotificationKind::Document(document) => KindPayload::Document {
+                file: FilePayload::new(&document.file),
+            },
+        };
+        Self {
+            version: PAYLOAD_VERSION,
+            kind,
+            source: SourcePayload::new(notification.source),
+        }
+    }
+}
+
+impl<'a> SourcePayload<'a> {
+    fn new(source: &'a StatusSource) -> Self {
+        Self {
+            platform: source.platform.display_name,
+            user: source.user.as_ref().map(|user| SourceUserPayload {
+                display_name: &user.display_name,
+                profile_url: &user.profile_url,
+            }),
+        }
+    }
+}
+
+impl<'a> LivePayload<'a> {
+    fn new(live: &'a LiveStatus) -> Self {
+        let (status, start_time) = match live.kind {
+            LiveStatusKind::Online { start_time } => ("online", start_time),
+            LiveStatusKind::Offline => ("offline", None),
+            LiveStatusKind::Banned => ("banned", None),
+        };
+        Self {
+            status,
+            start_time: start_time.map(format_system_time),
+            title: &live.title,
+            area: live.area.as_ref().map(AreaPayload::new),
+            streamer_name: &live.streamer_name,
+            cover_image_url: &live.cover_image_url,
+            live_url: &live.live_url,
+        }
+    }
+}
+
+impl<'a> AreaPayload<'a> {
+    fn new(area: &'a LiveArea) -> Self {
+        Self {
+            parent: &area.parent,
+            child: &area.child,
+        }
+    }
+}
+
+impl<'a> PostPayload<'a> {
+    fn new(post: &'a Post) -> Self {
+        Self {
+            id: post.urls.major().unique_id(),
+            user: UserPayload {
+                nickname: &post.user.nickname,
+                profile_url: &post.user.profile_url,
+                avatar_url: post.user.avatar_url.as_deref(),
+            },
+            content: post
+                .content
+                .parts()
+                .map(|part| match part {
+                    PostContentPart::Plain(text) => ContentPartPayload::Plain { text },
+                    PostContentPart::Link { display, url } => {
+                        ContentPartPayload::Link { display, url }
+                    }
+                    PostContentPart::InlineAttachment(attachment) => {
+                        ContentPartPayload::InlineAttachment {
+                            attachment: AttachmentPayload::new(attachment),
+                        }
+                    }
+                })
+                .collect(),
+            content_fallback: post.content.fallback(),
+            urls: post
+                .urls
+                .iter()
+                .map(|url| match url {
+                    PostUrl::Clickable(url) => UrlPayload::Clickable {
+                        url: &url.url,
+                        display: &url.display,
+                    },
+                    PostUrl::Identity(id) => UrlPayload::Identity { id },
+                })
+                .collect(),
+            time: post.time.to_rfc3339(),
+            is_pinned: post.is_pinned,
+            attachments: post
+                .attachments(false)
+                .map(AttachmentPayload::new)
+                .collect(),
+            repost_from: post
+                .repost_from
+                .as_ref()
+                .map(|repost_from| match repost_from {
+                    RepostFrom::Recursion(post) => Box::new(PostPayload::new(post)),
+                }),
+        }
+    }
+}
+
+impl<'a> AttachmentPayload<'a> {
+    fn new(attachment: &'a PostAttachment) -> Self {
+        match attachment {
+            PostAttachment::Image(image) => Self::Image {
+                media_url: &image.media_url,
+                has_spoiler: image.has_spoiler,
+            },
+            PostAttachment::Video(video) => Self::Video {
+                media_url: &video.media_url,
+                has_spoiler: video.has_spoiler,
+            },
+        }
+    }
+}
+
+impl FilePayload {
+    fn new(file: &FileRef<'_>) -> Self {
+        Self {
+            name: file.name.clone(),
+            size: Some(file.size),
+            path: file.path.map(|path| path.to_string_lossy().into()),
+        }
+    }
+}
+
+fn format_system_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{platform::PlatformMetadata, source::PostsRef};
+
+    #[tokio::test]
+    async fn serialize() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = Post {
+            user: crate::source::User {
+                nickname: "user".into(),
+                profile_url: "https://example.com/user".into(),
+                avatar_url: None,
+            },
+            content: crate::source::PostContent::plain("meow ")
+                .with_link("link", "https://example.com/link"),
+            urls: crate::source::PostUrls::new(PostUrl::new_clickable(
+                "https://example.com/post",
+                "View",
+            )),
+            time: DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z")
+                .unwrap()
+                .into(),
+            is_pinned: false,
+            repost_from: None,
+            attachments: vec![PostAttachment::Image(crate::source::PostAttachmentImage {
+                media_url: "https://example.com/0.jpg".into(),
+                has_spoiler: false,
+            })],
+        };
+        let notification = Notification {
+            kind: NotificationKind::Posts(PostsRef(vec![&post])),
+            source: &source,
+        };
+
+        let mut value =
+            serde_json::to_value(NotificationPayload::new(&notification).await).unwrap();
+        // Depends on the local timezone
+        value["posts"][0]["time"] = json!("*");
+        assert_eq!(
+            value,
+            json!({
+                "version": 1,
+                "kind": "posts",
+                "posts": [{
+                    "id": "https://example.com/post",
+                    "user": {
+                        "nickname": "user",
+                        "profile_url": "https://example.com/user",
+                        "avatar_url": null
+                    },
+                    "content": [
+                        { "type": "plain", "text": "meow " },
+                        { "type": "link", "display": "link", "url": "https://example.com/link" }
+                    ],
+                    "content_fallback": "meow [link](https://example.com/link)",
+                    "urls": [{ "type": "clickable", "url": "https://example.com/post", "display": "View" }],
+                    "time": "*",
+                    "is_pinned": false,
+                    "attachments": [{ "type": "image", "media_url": "https://example.com/0.jpg", "has_spoiler": false }],
+                    "repost_from": null
+                }],
+                "source": { "platform": "test", "user": null }
+            })
+        );
+    }
+}
@@ -2,11 +2,13 @@ use std::{borrow::Cow, fmt, slice, vec};
 
 use anyhow::ensure;
 use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct PostPlatformUniqueId(String);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Post {
     pub user: User,
     pub content: PostContent,
@@ -62,7 +64,27 @@ impl Post {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[cfg(test)]
+impl Post {
+    // For tests out of the sources, where the URLs and attachments are private
+    pub(crate) fn mock(content: PostContent, attachments: Vec<PostAttachment>) -> Self {
+        Self {
+            user: User {
+                nickname: "user".into(),
+                profile_url: "https://example.com/user".into(),
+                avatar_url: None,
+            },
+            content,
+            urls: PostUrl::new_clickable("https://example.com/post", "View").into(),
+            time: DateTime::UNIX_EPOCH.into(),
+            is_pinned: false,
+            repost_from: None,
+            attachments,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PostUrls(Vec<PostUrl>);
 
 impl PostUrls {
@@ -108,7 +130,7 @@ impl<'a> PostUrlsRef<'a> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PostUrl {
     Clickable(PostUrlClickable),
     // For some cases. a post doesn't have a URL (e.g. deleted post), but we still need something
@@ -121,6 +143,19 @@ impl PostUrl {
         PostUrl::Clickable(PostUrlClickable {
             url: url.into(),
             display: display.into(),
+            id: None,
+        })
+    }
+
+    pub fn new_clickable_with_id(
+        url: impl Into<String>,
+        display: impl Into<String>,
+        id: impl Into<String>,
+    ) -> Self {
+        PostUrl::Clickable(PostUrlClickable {
+            url: url.into(),
+            display: display.into(),
+            id: Some(id.into()),
         })
     }
 
@@ -133,7 +168,7 @@ impl PostUrl {
 
     pub fn unique_id(&self) -> &str {
         let id = match self {
-            PostUrl::Clickable(clickable) => &clickable.url,
+            PostUrl::Clickable(clickable) => clickable.id.as_ref().unwrap_or(&clickable.url),
             PostUrl::Identity(identity) => identity,
         };
         assert!(!id.is_empty());
@@ -147,13 +182,17 @@ impl PartialEq for PostUrl {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostUrlClickable {
     pub url: String,
     pub display: String,
+    // Identifies the post instead of the URL, for platforms providing a stable ID
+    // while the URL may change (e.g. feeds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PostContent(Vec<PostContentPart>);
 
 impl PostContent {
@@ -224,32 +263,32 @@ impl PostContent {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PostContentPart {
     Plain(String),
     Link { display: String, url: String },
     InlineAttachment(PostAttachment),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct User {
     pub nickname: String,
     pub profile_url: String,
     pub avatar_url: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RepostFrom {
     Recursion(Box<Post>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PostAttachment {
     Image(PostAttachmentImage),
     Video(PostAttachmentVideo),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostAttachmentImage {
     pub media_url: String,
     pub has_spoiler: bool,
@@ -261,7 +300,7 @@ impl PartialEq for PostAttachmentImage {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PostAttachmentVideo {
     pub media_url: String,
     pub has_spoiler: bool,
@@ -273,7 +312,7 @@ impl PartialEq for PostAttachmentVideo {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Posts(pub(in crate::source) Vec<Post>);
 
 impl fmt::Display for Posts {
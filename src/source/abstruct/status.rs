@@ -1,15 +1,21 @@
 use std::{fmt, vec};
 
-use super::{LiveStatus, Notification, NotificationKind, Posts, PostsRef};
-use crate::{platform::PlatformMetadata, source::diff};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use spdlog::prelude::*;
 
-#[derive(Clone, Debug, PartialEq)]
+use super::{
+    LiveStatus, Notification, NotificationKind, Post, PostPlatformUniqueId, Posts, PostsRef,
+};
+use crate::{platform::PlatformMetadata, source::diff, state};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct StatusSource {
     pub platform: PlatformMetadata,
     pub user: Option<StatusSourceUser>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct StatusSourceUser {
     pub display_name: String,
     pub profile_url: String,
@@ -22,6 +28,9 @@ pub struct Status(Option<StatusInner>);
 struct StatusInner {
     kind: StatusKind,
     source: StatusSource,
+    // Posts missing from the last fetch, they are considered deleted only if they
+    // are still missing from the next fetch, in case of flaky API results
+    missing: Vec<PostPlatformUniqueId>,
 }
 
 impl Status {
@@ -30,7 +39,11 @@ impl Status {
     }
 
     pub fn new(kind: StatusKind, source: StatusSource) -> Self {
-        Self(Some(StatusInner { kind, source }))
+        Self(Some(StatusInner {
+            kind,
+            source,
+            missing: vec![],
+        }))
     }
 
     pub fn sort(&mut self) {
@@ -44,56 +57,84 @@ impl Status {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
     pub fn generate_notifications<'a>(&'a self, last_status: &'a Status) -> Vec<Notification<'a>> {
         self.0
             .as_ref()
-            .map(
-                |status| match (&status.kind, last_status.0.as_ref().map(|s| &s.kind)) {
-                    (StatusKind::Live(live_status), Some(StatusKind::Live(last_live_status))) => {
-                        let mut notifications = vec![];
-                        if live_status.title != last_live_status.title {
-                            notifications.push(Notification {
-                                kind: NotificationKind::LiveTitle(
-                                    live_status,
-                                    &last_live_status.title,
-                                ),
-                                source: &status.source,
-                            })
-                        }
-                        if live_status.kind != last_live_status.kind {
-                            notifications.push(Notification {
-                                kind: NotificationKind::LiveOnline(live_status),
-                                source: &status.source,
-                            })
-                        }
-                        notifications
+            .map(|status| match (&status.kind, last_status.0.as_ref()) {
+                (_, None) => vec![],
+                (StatusKind::Live(live_status), Some(last)) => match &last.kind {
+                    StatusKind::Live(last_live_status) => {
+                        status.live_notifications(live_status, last_live_status)
                     }
-                    (StatusKind::Posts(posts), Some(StatusKind::Posts(last_posts))) => {
-                        let new_posts = diff::diff_by(&last_posts.0, &posts.0, |l, r| {
-                            l.platform_unique_id() == r.platform_unique_id()
-                        })
-                        .collect::<Vec<_>>();
-                        if !new_posts.is_empty() {
-                            vec![Notification {
-                                kind: NotificationKind::Posts(PostsRef(new_posts)),
-                                source: &status.source,
-                            }]
-                        } else {
-                            vec![]
-                        }
+                    _ => panic!("states mismatch"),
+                },
+                (StatusKind::Posts(posts), Some(last)) => match &last.kind {
+                    StatusKind::Posts(last_posts) => {
+                        let mut notifications = status.posts_notifications(posts, |post| {
+                            last_posts
+                                .0
+                                .iter()
+                                .any(|last| last.platform_unique_id() == post.platform_unique_id())
+                        });
+                        notifications.extend(status.changed_posts_notifications(
+                            posts,
+                            last_posts,
+                            &last.missing,
+                        ));
+                        notifications
                     }
-                    (_, None) => vec![],
-                    (_, _) => panic!("states mismatch"),
+                    _ => panic!("states mismatch"),
                 },
-            )
+            })
+            .unwrap_or_default()
+    }
+
+    // Same as `generate_notifications`, but compares with a state restored from
+    // the persistent storage instead of a fetched status
+    pub fn generate_notifications_since<'a>(
+        &'a self,
+        last_state: &'a StatusState,
+    ) -> Vec<Notification<'a>> {
+        self.0
+            .as_ref()
+            .map(|status| match (&status.kind, last_state) {
+                (StatusKind::Live(live_status), StatusState::Live(last_live_status)) => {
+                    status.live_notifications(live_status, last_live_status)
+                }
+                (StatusKind::Posts(posts), StatusState::Posts { seen }) => {
+                    status.posts_notifications(posts, |post| seen.contains(&post.platform_unique_id()))
+                }
+                (_, _) => {
+                    warn!("restored state mismatches the fetched status, ignore it. status: '{}', state: '{last_state:?}'", status.kind);
+                    vec![]
+                }
+            })
             .unwrap_or_default()
     }
 
+    pub fn state(&self) -> Option<StatusState> {
+        self.0.as_ref().map(|status| match &status.kind {
+            StatusKind::Live(live_status) => StatusState::Live(live_status.clone()),
+            StatusKind::Posts(posts) => StatusState::Posts {
+                seen: posts
+                    .0
+                    .iter()
+                    .map(|post| post.platform_unique_id())
+                    .collect(),
+            },
+        })
+    }
+
     // Sometimes the data source API glitches and returns empty items without
     // producing any errors. If we simply replace the stored value of `Status`, when
     // the API comes back to normal, we will incorrectly generate notifications with
     // all the items as a new update. To solve this issue, call this function, which
-    // will always incrementally store the new items and never delete the old items.
+    // will always incrementally store the new items, and only delete the old items
+    // that are missing from two fetches in a row (see `missing_posts`).
     pub fn update_incrementally(&mut self, new: Status) {
         match (&mut self.0, new.0) {
             (None, None) => {}
@@ -102,7 +143,25 @@ impl Status {
             (Some(stored), Some(new)) => {
                 match (&mut stored.kind, new.kind) {
                     (StatusKind::Live(stored), StatusKind::Live(new)) => *stored = new,
-                    (StatusKind::Posts(stored), StatusKind::Posts(new)) => {
+                    (StatusKind::Posts(stored_posts), StatusKind::Posts(new)) => {
+                        let (deleted, missing) = missing_posts(&new, stored_posts)
+                            .map(|post| post.platform_unique_id())
+                            .partition::<Vec<_>, _>(|id| stored.missing.contains(id));
+                        stored_posts
+                            .0
+                            .retain(|post| !deleted.contains(&post.platform_unique_id()));
+                        stored.missing = missing;
+                        let stored = stored_posts;
+
+                        // Keep the latest version of the edited items
+                        for stored in &mut stored.0 {
+                            if let Some(post) = new.0.iter().find(|post| {
+                                post.platform_unique_id() == stored.platform_unique_id()
+                            }) {
+                                *stored = post.clone();
+                            }
+                        }
+
                         let mut new = diff::diff_by(&stored.0, new.0, |l, r| {
                             l.platform_unique_id() == r.platform_unique_id()
                         })
@@ -121,6 +180,124 @@ impl Status {
     }
 }
 
+impl StatusInner {
+    fn live_notifications<'a>(
+        &'a self,
+        live_status: &'a LiveStatus,
+        last_live_status: &'a LiveStatus,
+    ) -> Vec<Notification<'a>> {
+        live_notifications(&self.source, live_status, last_live_status)
+    }
+
+    fn posts_notifications<'a>(
+        &'a self,
+        posts: &'a Posts,
+        is_seen: impl Fn(&Post) -> bool,
+    ) -> Vec<Notification<'a>> {
+        let new_posts = posts
+            .0
+            .iter()
+            .filter(|post| !is_seen(post))
+            .collect::<Vec<_>>();
+        if !new_posts.is_empty() {
+            vec![Notification {
+                kind: NotificationKind::Posts(PostsRef(new_posts)),
+                source: &self.source,
+            }]
+        } else {
+            vec![]
+        }
+    }
+
+    fn changed_posts_notifications<'a>(
+        &'a self,
+        posts: &'a Posts,
+        last_posts: &'a Posts,
+        last_missing: &'a [PostPlatformUniqueId],
+    ) -> Vec<Notification<'a>> {
+        let edited = edited_posts(posts, last_posts).map(|(post, old)| Notification {
+            kind: NotificationKind::PostEdited(post, old),
+            source: &self.source,
+        });
+        let deleted = missing_posts(posts, last_posts)
+            .filter(|post| last_missing.contains(&post.platform_unique_id()))
+            .map(|post| Notification {
+                kind: NotificationKind::PostDeleted(post),
+                source: &self.source,
+            });
+        edited.chain(deleted).collect()
+    }
+}
+
+// Posts that are still fetched but with a different content
+fn edited_posts<'a>(
+    posts: &'a Posts,
+    last_posts: &'a Posts,
+) -> impl Iterator<Item = (&'a Post, &'a Post /* old */)> {
+    posts.0.iter().filter_map(|post| {
+        last_posts
+            .0
+            .iter()
+            .find(|last| last.platform_unique_id() == post.platform_unique_id())
+            .filter(|last| last.content != post.content)
+            .map(|last| (post, last))
+    })
+}
+
+// Only the latest items are fetched, so a post absent from the fetched items is
+// considered missing only if it's newer than the oldest fetched one. Pinned
+// posts are excluded, as they are not ordered by time, and so are posts without
+// time (`UNIX_EPOCH`), which would move the cut-off to the beginning.
+fn missing_posts<'a>(posts: &'a Posts, last_posts: &'a Posts) -> impl Iterator<Item = &'a Post> {
+    let oldest = posts
+        .0
+        .iter()
+        .filter(|post| !post.is_pinned && post.time != DateTime::UNIX_EPOCH)
+        .map(|post| post.time)
+        .min();
+    last_posts.0.iter().filter(move |last| {
+        oldest.is_some_and(|oldest| !last.is_pinned && last.time > oldest)
+            && !posts
+                .0
+                .iter()
+                .any(|post| post.platform_unique_id() == last.platform_unique_id())
+    })
+}
+
+pub(super) fn live_notifications<'a>(
+    source: &'a StatusSource,
+    live_status: &'a LiveStatus,
+    last_live_status: &'a LiveStatus,
+) -> Vec<Notification<'a>> {
+    let mut notifications = vec![];
+    // Sources may leave the title empty if it's unknown, e.g. no stream has been
+    // seen yet, that's not a change of the title
+    if !live_status.title.is_empty()
+        && !last_live_status.title.is_empty()
+        && live_status.title != last_live_status.title
+    {
+        notifications.push(Notification {
+            kind: NotificationKind::LiveTitle(live_status, &last_live_status.title),
+            source,
+        })
+    }
+    if let (Some(area), Some(last_area)) = (&live_status.area, &last_live_status.area) {
+        if area != last_area {
+            notifications.push(Notification {
+                kind: NotificationKind::LiveArea(live_status, last_area),
+                source,
+            })
+        }
+    }
+    if live_status.kind != last_live_status.kind {
+        notifications.push(Notification {
+            kind: NotificationKind::LiveOnline(live_status),
+            source,
+        })
+    }
+    notifications
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum StatusKind {
     Live(LiveStatus),
@@ -136,6 +313,82 @@ impl fmt::Display for StatusKind {
     }
 }
 
+// The minimal data of a `Status` that needs to be persisted across restarts, so
+// that we are able to generate notifications for updates that happened while we
+// were down
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StatusState {
+    Live(LiveStatus),
+    Posts { seen: Vec<PostPlatformUniqueId> },
+}
+
+// Persists the `StatusState` of a source under a key shared by all its
+// subscribers, along with the subscribers who have seen it, so that adding or
+// removing a subscriber doesn't lose the state for the others
+#[derive(Clone, Debug)]
+pub struct StatusStore {
+    key: String,
+    subscribers: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedStatusState {
+    // `None` for states saved before the subscribers were tracked, they are
+    // considered seen by all subscribers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subscribers: Option<Vec<String>>,
+    #[serde(flatten)]
+    state: StatusState,
+}
+
+impl StatusStore {
+    pub fn new(source: impl fmt::Display, subscribers: Vec<String>) -> Self {
+        Self {
+            key: format!("status.{source}"),
+            subscribers,
+        }
+    }
+
+    // Returns the state and the current subscribers who have seen it
+    pub async fn load(&self) -> Option<(StatusState, Vec<String>)> {
+        let store = state::store()?;
+        let saved = store
+            .load::<SavedStatusState>(&self.key)
+            .await
+            .inspect_err(|err| error!("failed to load state '{}': {err}", self.key))
+            .ok()
+            .flatten()?;
+        trace!("state '{}' restored: '{saved:?}'", self.key);
+
+        let seen_by = self
+            .subscribers
+            .iter()
+            .filter(|subscriber| {
+                saved
+                    .subscribers
+                    .as_ref()
+                    .is_none_or(|seen_by| seen_by.contains(subscriber))
+            })
+            .cloned()
+            .collect();
+        Some((saved.state, seen_by))
+    }
+
+    pub async fn save(&self, state: &StatusState) {
+        let Some(store) = state::store() else {
+            return;
+        };
+        let saved = SavedStatusState {
+            subscribers: Some(self.subscribers.clone()),
+            state: state.clone(),
+        };
+        if let Err(err) = store.save(&self.key, &saved).await {
+            error!("failed to save state '{}': {err}", self.key);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::DateTime;
@@ -160,6 +413,7 @@ mod tests {
             StatusKind::Live(LiveStatus {
                 kind: LiveStatusKind::Online { start_time: None },
                 title: "title1".into(),
+                area: None,
                 streamer_name: "streamer1".into(),
                 cover_image_url: "cover1".into(),
                 live_url: "live1".into(),
@@ -181,6 +435,7 @@ mod tests {
             StatusKind::Live(LiveStatus {
                 kind: LiveStatusKind::Online { start_time: None },
                 title: "title2".into(),
+                area: None,
                 streamer_name: "streamer2".into(),
                 cover_image_url: "cover2".into(),
                 live_url: "live2".into(),
@@ -365,6 +620,283 @@ mod tests {
         assert_eq!(status, last);
     }
 
+    #[test]
+    fn status_state() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = |id: &str| Post {
+            user: mock_user(),
+            content: PostContent::plain(format!("content of {id}")),
+            urls: PostUrls::new(PostUrl::Identity(id.into())),
+            time: DateTime::UNIX_EPOCH.into(),
+            is_pinned: false,
+            repost_from: None,
+            attachments: vec![],
+        };
+
+        assert_eq!(Status::empty().state(), None);
+
+        let status = Status::new(
+            StatusKind::Posts(Posts(vec![post("id1"), post("id2")])),
+            source.clone(),
+        );
+        let state = status.state().unwrap();
+        assert_eq!(
+            state,
+            StatusState::Posts {
+                seen: vec![
+                    post("id1").platform_unique_id(),
+                    post("id2").platform_unique_id()
+                ]
+            }
+        );
+
+        // Roundtrip through the persistent form
+        let state: StatusState =
+            serde_json::from_value(serde_json::to_value(&state).unwrap()).unwrap();
+
+        let status = Status::new(
+            StatusKind::Posts(Posts(vec![post("id3"), post("id1"), post("id2")])),
+            source.clone(),
+        );
+        let notifications = status.generate_notifications_since(&state);
+        assert_eq!(notifications.len(), 1);
+        match &notifications[0].kind {
+            NotificationKind::Posts(posts) => {
+                assert_eq!(posts.0.len(), 1);
+                assert_eq!(posts.0[0].urls.major().unique_id(), "id3");
+            }
+            _ => panic!("unexpected notification kind"),
+        }
+
+        let live_status = |kind, title: &str, area: &str| LiveStatus {
+            kind,
+            title: title.into(),
+            area: Some(LiveArea {
+                parent: "parent".into(),
+                child: area.into(),
+            }),
+            streamer_name: "streamer1".into(),
+            cover_image_url: "cover1".into(),
+            live_url: "live1".into(),
+        };
+        let state = Status::new(
+            StatusKind::Live(live_status(LiveStatusKind::Offline, "title1", "area1")),
+            source.clone(),
+        )
+        .state()
+        .unwrap();
+        let state: StatusState =
+            serde_json::from_value(serde_json::to_value(&state).unwrap()).unwrap();
+
+        let status = Status::new(
+            StatusKind::Live(live_status(
+                LiveStatusKind::Online { start_time: None },
+                "title1",
+                "area1",
+            )),
+            source.clone(),
+        );
+        let notifications = status.generate_notifications_since(&state);
+        assert_eq!(notifications.len(), 1);
+        assert!(matches!(
+            notifications[0].kind,
+            NotificationKind::LiveOnline(_)
+        ));
+
+        let status = Status::new(
+            StatusKind::Live(live_status(LiveStatusKind::Offline, "title1", "area2")),
+            source.clone(),
+        );
+        let notifications = status.generate_notifications_since(&state);
+        assert_eq!(notifications.len(), 1);
+        match notifications[0].kind {
+            NotificationKind::LiveArea(live_status, old_area) => {
+                assert_eq!(live_status.area.as_ref().unwrap().child, "area2");
+                assert_eq!(old_area.child, "area1");
+            }
+            _ => panic!("unexpected notification kind"),
+        }
+
+        // Unknown titles are not compared
+        let state = Status::new(
+            StatusKind::Live(live_status(LiveStatusKind::Offline, "", "area1")),
+            source.clone(),
+        )
+        .state()
+        .unwrap();
+        let status = Status::new(
+            StatusKind::Live(live_status(LiveStatusKind::Offline, "title1", "area1")),
+            source.clone(),
+        );
+        assert!(status.generate_notifications_since(&state).is_empty());
+
+        // Mismatched state is ignored
+        let status = Status::new(StatusKind::Posts(Posts(vec![post("id1")])), source);
+        assert!(status.generate_notifications_since(&state).is_empty());
+    }
+
+    #[test]
+    fn saved_status_state() {
+        let saved = SavedStatusState {
+            subscribers: Some(vec!["meow".into()]),
+            state: StatusState::Posts {
+                seen: serde_json::from_str(r#"["id1"]"#).unwrap(),
+            },
+        };
+        let json = r#"{"subscribers":["meow"],"type":"posts","seen":["id1"]}"#;
+        assert_eq!(serde_json::to_string(&saved).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<SavedStatusState>(json).unwrap(),
+            saved
+        );
+
+        // Saved before the subscribers were tracked
+        assert_eq!(
+            serde_json::from_str::<SavedStatusState>(r#"{"type":"posts","seen":["id1"]}"#).unwrap(),
+            SavedStatusState {
+                subscribers: None,
+                ..saved
+            }
+        );
+    }
+
+    #[test]
+    fn status_changed_posts() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = |id: u64, content: &str| Post {
+            user: mock_user(),
+            content: PostContent::plain(content),
+            urls: PostUrls::new(PostUrl::Identity(id.to_string())),
+            time: DateTime::from_timestamp(id as i64, 0).unwrap().into(),
+            is_pinned: false,
+            repost_from: None,
+            attachments: vec![],
+        };
+
+        let mut last = Status::new(
+            StatusKind::Posts(Posts(vec![
+                post(4, "4"),
+                post(3, "3"),
+                post(2, "2"),
+                post(1, "1"),
+            ])),
+            source.clone(),
+        );
+
+        // 4 is edited, 3 is missing, 1 is out of the fetched window
+        let status = Status::new(
+            StatusKind::Posts(Posts(vec![post(5, "5"), post(4, "4 edited"), post(2, "2")])),
+            source.clone(),
+        );
+        let notifications = status.generate_notifications(&last);
+        assert_eq!(notifications.len(), 2);
+        match &notifications[0].kind {
+            NotificationKind::Posts(posts) => assert_eq!(posts.0, [&post(5, "5")]),
+            _ => panic!("unexpected notification kind"),
+        }
+        match &notifications[1].kind {
+            NotificationKind::PostEdited(post, old) => {
+                assert_eq!(post.content, PostContent::plain("4 edited"));
+                assert_eq!(old.content, PostContent::plain("4"));
+            }
+            _ => panic!("unexpected notification kind"),
+        }
+
+        // Missing once is not deleted yet
+        last.update_incrementally(status.clone());
+        let posts = |last: &Status| match &last.0.as_ref().unwrap().kind {
+            StatusKind::Posts(posts) => posts.0.clone(),
+            _ => panic!("unexpected status kind"),
+        };
+        assert_eq!(
+            posts(&last),
+            [
+                post(4, "4 edited"),
+                post(3, "3"),
+                post(2, "2"),
+                post(1, "1"),
+                post(5, "5"),
+            ]
+        );
+
+        // Missing twice in a row is deleted
+        let notifications = status.generate_notifications(&last);
+        assert_eq!(notifications.len(), 1);
+        match &notifications[0].kind {
+            NotificationKind::PostDeleted(deleted) => assert_eq!(*deleted, &post(3, "3")),
+            _ => panic!("unexpected notification kind"),
+        }
+        last.update_incrementally(status.clone());
+        let expected = [
+            post(4, "4 edited"),
+            post(2, "2"),
+            post(1, "1"),
+            post(5, "5"),
+        ];
+        assert_eq!(posts(&last), expected);
+        assert!(status.generate_notifications(&last).is_empty());
+
+        // Empty results from glitches don't delete anything
+        let empty = Status::new(StatusKind::Posts(Posts(vec![])), source.clone());
+        assert!(empty.generate_notifications(&last).is_empty());
+        last.update_incrementally(empty);
+        assert_eq!(posts(&last), expected);
+
+        // A post reappearing is no longer missing
+        let without_2 = Status::new(
+            StatusKind::Posts(Posts(vec![post(5, "5"), post(4, "4 edited")])),
+            source.clone(),
+        );
+        last.update_incrementally(without_2.clone());
+        last.update_incrementally(status.clone());
+        assert!(without_2.generate_notifications(&last).is_empty());
+    }
+
+    #[test]
+    fn status_undated_posts() {
+        let source = StatusSource {
+            platform: PlatformMetadata {
+                display_name: "test",
+            },
+            user: None,
+        };
+        let post = |id: u64, time: i64| Post {
+            user: mock_user(),
+            content: PostContent::plain(id.to_string()),
+            urls: PostUrls::new(PostUrl::Identity(id.to_string())),
+            time: DateTime::from_timestamp(time, 0).unwrap().into(),
+            is_pinned: false,
+            repost_from: None,
+            attachments: vec![],
+        };
+
+        let mut last = Status::new(
+            StatusKind::Posts(Posts(vec![post(3, 3), post(2, 2), post(1, 0)])),
+            source.clone(),
+        );
+
+        // 2 is out of the fetched window, the undated 1 is not the cut-off
+        let status = Status::new(
+            StatusKind::Posts(Posts(vec![post(4, 4), post(3, 3), post(1, 0)])),
+            source,
+        );
+        last.update_incrementally(status.clone());
+        assert!(status
+            .generate_notifications(&last)
+            .iter()
+            .all(|notification| !matches!(notification.kind, NotificationKind::PostDeleted(_))));
+    }
+
     #[test]
     #[should_panic]
     fn status_incremental_update_mismatch() {
@@ -375,6 +907,7 @@ mod tests {
             StatusKind::Live(LiveStatus {
                 kind: LiveStatusKind::Online { start_time: None },
                 title: "title1".into(),
+                area: None,
                 streamer_name: "streamer1".into(),
                 cover_image_url: "cover1".into(),
                 live_url: "live1".into(),
@@ -4,8 +4,8 @@ use spdlog::prelude::*;
 use tokio::sync::Mutex;
 
 use super::{
-    Document, DocumentRef, FileRef, Notification, NotificationKind, Playback, PlaybackRef,
-    StatusSource,
+    status::live_notifications, Document, DocumentRef, FileRef, LiveStatus, Notification,
+    NotificationKind, Playback, PlaybackRef, StatusSource,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -21,6 +21,7 @@ impl Update {
 
     pub async fn generate_notifications(&self) -> Vec<Notification<'_>> {
         match &self.kind {
+            UpdateKind::Live(live) => live_notifications(&self.source, &live.current, &live.last),
             UpdateKind::Playback(playback) => {
                 vec![Notification {
                     kind: NotificationKind::Playback(PlaybackRef {
@@ -52,13 +53,22 @@ impl Update {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum UpdateKind {
+    Live(Box<LiveUpdate>),
     Playback(Playback),
     Document(Document),
 }
 
+// For listen-based live sources, which track the live status by themselves
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiveUpdate {
+    pub current: LiveStatus,
+    pub last: LiveStatus,
+}
+
 impl fmt::Display for UpdateKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::Live(live) => write!(f, "{}", live.current),
             Self::Playback(playback) => write!(f, "{playback}"),
             Self::Document(document) => write!(f, "{document}"),
         }
@@ -0,0 +1,137 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    hash::Hash,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use spdlog::prelude::*;
+use tokio::{sync::Mutex, time::Instant};
+
+const BATCH_REUSE_WINDOW: Duration = Duration::from_secs(10);
+
+// For APIs accepting multiple keys, so instead of requesting for each fetcher,
+// a request is made for all registered keys and the result is shared with the
+// fetchers ticking around the same time
+pub struct Batcher<K, V> {
+    name: &'static str,
+    keys: std::sync::Mutex<HashMap<K, usize /* number of fetchers */>>,
+    last: Mutex<Option<Batch<K, V>>>,
+}
+
+struct Batch<K, V> {
+    time: Instant,
+    keys: BTreeSet<K>,
+    result: Result<HashMap<K, V>, String>,
+}
+
+impl<K: Clone + Ord + Hash, V: Clone> Batcher<K, V> {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            keys: std::sync::Mutex::new(HashMap::new()),
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn register(&self, key: K) {
+        *self.keys.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    pub fn unregister(&self, key: &K) {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(count) = keys.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                keys.remove(key);
+            }
+        }
+    }
+
+    // Returns `None` if the key is absent from the result of the batch
+    pub async fn fetch<F, Fut>(&self, key: &K, fetch_batch: F) -> anyhow::Result<Option<V>>
+    where
+        F: FnOnce(Vec<K>) -> Fut,
+        Fut: Future<Output = anyhow::Result<HashMap<K, V>>>,
+    {
+        let mut last = self.last.lock().await;
+
+        let reusable = last.as_ref().is_some_and(|batch| {
+            batch.time.elapsed() < BATCH_REUSE_WINDOW && batch.keys.contains(key)
+        });
+        if !reusable {
+            let mut keys = self
+                .keys
+                .lock()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            keys.insert(key.clone());
+
+            trace!("{} fetching a batch of {} keys", self.name, keys.len());
+            let result = fetch_batch(keys.iter().cloned().collect())
+                .await
+                .map_err(|err| err.to_string());
+            *last = Some(Batch {
+                time: Instant::now(),
+                keys,
+                result,
+            });
+        }
+
+        match &last.as_ref().unwrap().result {
+            Ok(values) => Ok(values.get(key).cloned()),
+            Err(err) => Err(anyhow!("{err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn reuse() {
+        let batcher = Batcher::<u64, u64>::new("test");
+        batcher.register(1);
+        batcher.register(2);
+        batcher.register(2);
+        batcher.unregister(&2);
+
+        let requests = AtomicUsize::new(0);
+        let fetch_batch = |keys: Vec<u64>| {
+            requests.fetch_add(1, Ordering::Relaxed);
+            // Key 0 is absent from the response
+            async move {
+                Ok(keys
+                    .into_iter()
+                    .filter(|key| *key != 0)
+                    .map(|key| (key, key * 10))
+                    .collect())
+            }
+        };
+
+        assert_eq!(batcher.fetch(&1, fetch_batch).await.unwrap(), Some(10));
+        assert_eq!(batcher.fetch(&2, fetch_batch).await.unwrap(), Some(20));
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+
+        // Not in the last batch
+        assert_eq!(batcher.fetch(&3, fetch_batch).await.unwrap(), Some(30));
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+
+        // Expired
+        tokio::time::advance(BATCH_REUSE_WINDOW).await;
+        assert_eq!(batcher.fetch(&1, fetch_batch).await.unwrap(), Some(10));
+        assert_eq!(requests.load(Ordering::Relaxed), 3);
+
+        batcher.register(0);
+        tokio::time::advance(BATCH_REUSE_WINDOW).await;
+        assert_eq!(batcher.fetch(&0, fetch_batch).await.unwrap(), None);
+        assert_eq!(batcher.fetch(&1, fetch_batch).await.unwrap(), Some(10));
+        assert_eq!(requests.load(Ordering::Relaxed), 4);
+    }
+}
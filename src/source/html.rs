@@ -0,0 +1,192 @@
+// Converts HTML fragments of post contents (e.g. feed summaries) into
+// `PostContent`. Only links and line breaks are preserved, the other tags are
+// stripped
+
+use super::PostContent;
+
+pub fn to_post_content(html: &str) -> PostContent {
+    let mut content = PostContent::from_parts([]);
+    let mut plain = String::new();
+    let mut link: Option<(String /* url */, String /* display */)> = None;
+    let mut skipping: Option<String> = None;
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut plain, &mut link, skipping.is_none(), rest);
+            break;
+        };
+        push_text(&mut plain, &mut link, skipping.is_none(), &rest[..start]);
+
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            // Not a tag, keep it as is
+            push_text(&mut plain, &mut link, skipping.is_none(), &rest[start..]);
+            break;
+        };
+        let tag = Tag::parse(&rest[start + 1..end]);
+        rest = &rest[end + 1..];
+
+        if let Some(skipped) = &skipping {
+            if tag.closing && tag.name == *skipped {
+                skipping = None;
+            }
+            continue;
+        }
+
+        match (tag.name.as_str(), tag.closing) {
+            ("script" | "style", false) => skipping = Some(tag.name),
+            ("a", false) => {
+                if let Some(url) = tag.href {
+                    link = Some((url, String::new()));
+                }
+            }
+            ("a", true) => {
+                if let Some((url, display)) = link.take() {
+                    flush_plain(&mut content, &mut plain);
+                    let display = display.trim();
+                    content.push_link(
+                        if display.is_empty() { &url } else { display },
+                        url.as_str(),
+                    );
+                }
+            }
+            ("br", _) => push_text(&mut plain, &mut link, true, "\n"),
+            ("p" | "div" | "blockquote" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => {
+                push_text(&mut plain, &mut link, true, "\n\n")
+            }
+            _ => {}
+        }
+    }
+    if let Some((url, display)) = link {
+        plain.push_str(if display.is_empty() { &url } else { &display });
+    }
+
+    plain.truncate(plain.trim_end().len());
+    flush_plain(&mut content, &mut plain);
+    content
+}
+
+fn flush_plain(content: &mut PostContent, plain: &mut String) {
+    // Leading line breaks of the content are meaningless
+    let text = if content.is_empty() {
+        plain.trim_start()
+    } else {
+        plain.as_str()
+    };
+    if !text.is_empty() {
+        content.push_plain(text);
+    }
+    plain.clear();
+}
+
+fn push_text(plain: &mut String, link: &mut Option<(String, String)>, visible: bool, text: &str) {
+    if !visible {
+        return;
+    }
+    let text = html_escape::decode_html_entities(text);
+    match link {
+        Some((_, display)) => display.push_str(&text),
+        None => plain.push_str(&text),
+    }
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    href: Option<String>,
+}
+
+impl Tag {
+    fn parse(inner: &str) -> Self {
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+        let name_end = inner
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(inner.len());
+        let name = inner[..name_end].to_ascii_lowercase();
+        let href = attribute(&inner[name_end..], "href")
+            .map(|href| html_escape::decode_html_entities(&href).into_owned());
+        Self {
+            name,
+            closing,
+            href,
+        }
+    }
+}
+
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+                        rest = value.get(end + 1..).unwrap_or("");
+                        &value[1..end]
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        rest = &value[end..];
+                        &value[..end]
+                    }
+                }
+            }
+            None => "",
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert() {
+        assert_eq!(
+            to_post_content("<p>Hello &amp; <b>welcome</b>!</p><p>line1<br/>line2</p>"),
+            PostContent::plain("Hello & welcome!\n\nline1\nline2")
+        );
+        assert_eq!(
+            to_post_content(
+                r#"<p>Read <a href="https://example.com/?a=1&amp;b=2" rel="nofollow">this post</a> by <a href='https://example.com/@meow' class="mention">@<span>meow</span></a></p>"#
+            ),
+            PostContent::plain("Read ")
+                .with_link("this post", "https://example.com/?a=1&b=2")
+                .with_plain(" by ")
+                .with_link("@meow", "https://example.com/@meow")
+        );
+        assert_eq!(
+            to_post_content(
+                r#"<a href="https://example.com/"></a><script>alert(1)</script> 1 &lt; 2 &#x1F431;"#
+            ),
+            PostContent::from_parts([])
+                .with_link("https://example.com/", "https://example.com/")
+                .with_plain(" 1 < 2 🐱")
+        );
+        assert_eq!(
+            to_post_content("caf&eacute; &mdash; &hellip; &unknown;"),
+            PostContent::plain("café — … &unknown;")
+        );
+        assert_eq!(
+            to_post_content("plain text"),
+            PostContent::plain("plain text")
+        );
+    }
+}
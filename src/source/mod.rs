@@ -1,8 +1,10 @@
 mod abstruct;
+mod batcher;
 pub mod diff;
+mod html;
 pub mod platform;
 
-use std::{fmt, future::Future, pin::Pin};
+use std::{fmt, future::Future, pin::Pin, time::Duration};
 
 pub use abstruct::*;
 use tokio::sync::mpsc;
@@ -38,8 +40,18 @@ impl Sourcer {
     }
 }
 
-pub fn sourcer(platform: &config::Accessor<platform::Config>) -> Sourcer {
+// `interval` is the polling interval of the subscription, listeners may use it
+// for the fallback polling. Listeners tracking the status themselves persist it
+// in `status_store`, the same as the subscription does for fetchers.
+pub fn sourcer(
+    platform: &config::Accessor<platform::Config>,
+    interval: Duration,
+    status_store: StatusStore,
+) -> Sourcer {
     match &**platform {
+        platform::Config::BilibiliLive(p) if p.realtime => Sourcer::new_listener(
+            platform::bilibili::live::Listener::new(p.clone(), interval, status_store),
+        ),
         platform::Config::BilibiliLive(p) => {
             Sourcer::new_fetcher(platform::bilibili::live::Fetcher::new(p.clone()))
         }
@@ -55,5 +67,21 @@ pub fn sourcer(platform: &config::Accessor<platform::Config>) -> Sourcer {
         platform::Config::Twitter(p) => {
             Sourcer::new_fetcher(platform::twitter::Fetcher::new(p.clone()))
         }
+        platform::Config::Rss(p) => Sourcer::new_fetcher(platform::rss::Fetcher::new(p.clone())),
+        platform::Config::YouTubeLive(p) => {
+            Sourcer::new_fetcher(platform::youtube::live::Fetcher::new(p.clone()))
+        }
+        platform::Config::YouTubeVideo(p) => {
+            Sourcer::new_fetcher(platform::youtube::video::Fetcher::new(p.clone()))
+        }
+        platform::Config::Twitch(p) => {
+            Sourcer::new_fetcher(platform::twitch::Fetcher::new(p.clone()))
+        }
+        platform::Config::Mastodon(p) => {
+            Sourcer::new_fetcher(platform::mastodon::Fetcher::new(p.clone()))
+        }
+        platform::Config::Bluesky(p) => {
+            Sourcer::new_fetcher(platform::bluesky::Fetcher::new(p.clone()))
+        }
     }
 }
@@ -0,0 +1,350 @@
+// Client of bilibili live danmaku (broadcast) WebSocket, only the events we
+// are interested in are handled
+//
+// Protocol reference: https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/live/message_stream.md
+
+use std::{collections::VecDeque, io::Read, time::Duration};
+
+use anyhow::{anyhow, bail, ensure};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::read::ZlibDecoder;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{self as json, json};
+use spdlog::prelude::*;
+use tokio::{net::TcpStream, time::MissedTickBehavior};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{bilibili_request_builder, Response};
+use crate::source::LiveArea;
+
+const DANMU_INFO_API: &str = "https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo";
+const FALLBACK_HOST: &str = "broadcastlv.chat.bilibili.com";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+const HEADER_LEN: usize = 16;
+
+const PROTOVER_JSON: u16 = 0;
+const PROTOVER_INT: u16 = 1;
+const PROTOVER_ZLIB: u16 = 2;
+
+const OP_HEARTBEAT: u32 = 2;
+const OP_HEARTBEAT_REPLY: u32 = 3;
+const OP_MESSAGE: u32 = 5;
+const OP_AUTH: u32 = 7;
+const OP_AUTH_REPLY: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Live,
+    Preparing,
+    RoomChange { title: String, area: LiveArea },
+    CutOff,
+    RoomLock,
+}
+
+pub struct Connection {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    heartbeat: tokio::time::Interval,
+    events: VecDeque<Event>,
+}
+
+impl Connection {
+    pub async fn connect(room_id: u64) -> anyhow::Result<Self> {
+        let (host, key) = match fetch_danmu_info(room_id).await {
+            Ok(info) => info,
+            Err(err) => {
+                // The connection without a key still receives broadcast events
+                warn!("failed to fetch danmaku server info for room '{room_id}', fallback to the default server: {err}");
+                (FALLBACK_HOST.into(), String::new())
+            }
+        };
+
+        let url = format!("wss://{host}/sub");
+        let (mut stream, _) = tokio_tungstenite::connect_async(&url)
+            .await
+            .map_err(|err| anyhow!("failed to connect to danmaku server '{url}': {err}"))?;
+
+        let auth = json!({
+            "uid": 0,
+            "roomid": room_id,
+            "protover": PROTOVER_ZLIB,
+            "platform": "web",
+            "type": 2,
+            "key": key,
+        });
+        stream
+            .send(Message::binary(encode_packet(
+                PROTOVER_INT,
+                OP_AUTH,
+                auth.to_string().as_bytes(),
+            )))
+            .await
+            .map_err(|err| anyhow!("failed to send auth packet: {err}"))?;
+
+        let reply = match stream.next().await {
+            Some(Ok(Message::Binary(data))) => decode_packets(data)?,
+            Some(Ok(message)) => bail!("unexpected auth reply '{message:?}'"),
+            Some(Err(err)) => bail!("failed to receive auth reply: {err}"),
+            None => bail!("connection closed before auth reply"),
+        };
+        let reply = reply
+            .into_iter()
+            .find(|packet| packet.op == OP_AUTH_REPLY)
+            .ok_or_else(|| anyhow!("auth reply not found"))?;
+        let code = json::from_slice::<json::Value>(&reply.body)
+            .ok()
+            .and_then(|body| body.get("code").and_then(|code| code.as_i64()));
+        ensure!(
+            code == Some(0),
+            "auth failed, reply '{}'",
+            String::from_utf8_lossy(&reply.body)
+        );
+
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Ok(Self {
+            stream,
+            heartbeat,
+            events: VecDeque::new(),
+        })
+    }
+
+    // Returns an error when disconnected
+    pub async fn next_event(&mut self) -> anyhow::Result<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            tokio::select! {
+                _ = self.heartbeat.tick() => {
+                    self.stream
+                        .send(Message::binary(encode_packet(PROTOVER_INT, OP_HEARTBEAT, b"")))
+                        .await
+                        .map_err(|err| anyhow!("failed to send heartbeat: {err}"))?;
+                }
+                message = self.stream.next() => {
+                    let data = match message {
+                        Some(Ok(Message::Binary(data))) => data,
+                        Some(Ok(Message::Close(frame))) => bail!("connection closed by server: {frame:?}"),
+                        Some(Ok(_)) => continue,
+                        Some(Err(err)) => bail!("failed to receive message: {err}"),
+                        None => bail!("connection closed"),
+                    };
+                    for packet in decode_packets(data)? {
+                        match packet.op {
+                            OP_MESSAGE => match parse_event(&packet.body) {
+                                Ok(Some(event)) => self.events.push_back(event),
+                                Ok(None) => {}
+                                Err(err) => warn!("failed to parse danmaku message: {err}"),
+                            },
+                            OP_HEARTBEAT_REPLY => trace!("danmaku heartbeat replied"),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn fetch_danmu_info(room_id: u64) -> anyhow::Result<(String, String)> {
+    #[derive(Deserialize)]
+    struct Data {
+        token: String,
+        host_list: Vec<Host>,
+    }
+
+    #[derive(Deserialize)]
+    struct Host {
+        host: String,
+    }
+
+    let resp: Response<Data> = bilibili_request_builder()?
+        .get(DANMU_INFO_API)
+        .query(&[("id", room_id)])
+        .send()
+        .await
+        .map_err(|err| anyhow!("failed to send request: {err}"))?
+        .error_for_status()
+        .map_err(|err| anyhow!("response status is not success: {err}"))?
+        .json()
+        .await
+        .map_err(|err| anyhow!("failed to deserialize response: {err}"))?;
+    ensure!(
+        resp.code == 0,
+        "response contains error, code '{}'",
+        resp.code
+    );
+
+    let data = resp.data.ok_or_else(|| anyhow!("response without data"))?;
+    let host = data
+        .host_list
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("empty host list"))?
+        .host;
+    Ok((host, data.token))
+}
+
+#[derive(Debug, PartialEq)]
+struct Packet {
+    op: u32,
+    body: Bytes,
+}
+
+fn encode_packet(protover: u16, op: u32, body: &[u8]) -> Vec<u8> {
+    let mut buf = BytesMut::with_capacity(HEADER_LEN + body.len());
+    buf.put_u32((HEADER_LEN + body.len()) as u32);
+    buf.put_u16(HEADER_LEN as u16);
+    buf.put_u16(protover);
+    buf.put_u32(op);
+    buf.put_u32(1); // Sequence, unused
+    buf.put_slice(body);
+    buf.into()
+}
+
+// A message may contain multiple packets, and compressed packets contain more
+// packets inside
+fn decode_packets(mut data: impl Buf) -> anyhow::Result<Vec<Packet>> {
+    let mut packets = vec![];
+    while data.has_remaining() {
+        ensure!(data.remaining() >= HEADER_LEN, "incomplete packet header");
+        let packet_len = data.get_u32() as usize;
+        let header_len = data.get_u16() as usize;
+        let protover = data.get_u16();
+        let op = data.get_u32();
+        let _seq = data.get_u32();
+        ensure!(
+            header_len >= HEADER_LEN && packet_len >= header_len,
+            "invalid packet length {packet_len}, header length {header_len}"
+        );
+        ensure!(
+            data.remaining() >= packet_len - HEADER_LEN,
+            "incomplete packet body"
+        );
+        data.advance(header_len - HEADER_LEN);
+        let body = data.copy_to_bytes(packet_len - header_len);
+
+        match protover {
+            PROTOVER_ZLIB => {
+                let mut decompressed = vec![];
+                ZlibDecoder::new(&body[..])
+                    .read_to_end(&mut decompressed)
+                    .map_err(|err| anyhow!("failed to decompress packet: {err}"))?;
+                packets.extend(decode_packets(&decompressed[..])?);
+            }
+            PROTOVER_JSON | PROTOVER_INT => packets.push(Packet { op, body }),
+            _ => trace!("ignored a packet with unsupported protover {protover}"),
+        }
+    }
+    Ok(packets)
+}
+
+fn parse_event(body: &[u8]) -> anyhow::Result<Option<Event>> {
+    let message: json::Value =
+        json::from_slice(body).map_err(|err| anyhow!("invalid json: {err}"))?;
+    let cmd = message
+        .get("cmd")
+        .and_then(|cmd| cmd.as_str())
+        .ok_or_else(|| anyhow!("missing 'cmd'"))?;
+
+    // Some commands come with suffixes, e.g. `DANMU_MSG:4:0:2:2:2:0`
+    let event = match cmd.split(':').next().unwrap() {
+        "LIVE" => Event::Live,
+        "PREPARING" => Event::Preparing,
+        "ROOM_CHANGE" => {
+            let field = |name| {
+                message
+                    .pointer(&format!("/data/{name}"))
+                    .and_then(|value| value.as_str())
+                    .map(String::from)
+                    .ok_or_else(|| anyhow!("missing {name} in ROOM_CHANGE"))
+            };
+            Event::RoomChange {
+                title: field("title")?,
+                area: LiveArea {
+                    parent: field("parent_area_name")?,
+                    child: field("area_name")?,
+                },
+            }
+        }
+        "CUT_OFF" => Event::CutOff,
+        "ROOM_LOCK" => Event::RoomLock,
+        _ => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn packets() {
+        let auth = encode_packet(PROTOVER_INT, OP_AUTH, br#"{"code":0}"#);
+        assert_eq!(
+            decode_packets(&auth[..]).unwrap(),
+            vec![Packet {
+                op: OP_AUTH,
+                body: Bytes::from_static(br#"{"code":0}"#)
+            }]
+        );
+
+        let inner = [
+            encode_packet(PROTOVER_JSON, OP_MESSAGE, br#"{"cmd":"LIVE"}"#),
+            encode_packet(
+                PROTOVER_JSON,
+                OP_MESSAGE,
+                br#"{"cmd":"DANMU_MSG:4:0:2:2:2:0"}"#,
+            ),
+        ]
+        .concat();
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&inner).unwrap();
+        let compressed = encode_packet(PROTOVER_ZLIB, OP_MESSAGE, &encoder.finish().unwrap());
+        let message = [
+            compressed,
+            encode_packet(PROTOVER_INT, OP_HEARTBEAT_REPLY, &[0, 0, 0, 1]),
+        ]
+        .concat();
+
+        let packets = decode_packets(&message[..]).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].op, OP_MESSAGE);
+        assert_eq!(parse_event(&packets[0].body).unwrap(), Some(Event::Live));
+        assert_eq!(parse_event(&packets[1].body).unwrap(), None);
+        assert_eq!(packets[2].op, OP_HEARTBEAT_REPLY);
+
+        assert!(decode_packets(&message[..message.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            parse_event(r#"{"cmd":"ROOM_CHANGE","data":{"title":"meow","area_id":744,"parent_area_id":9,"area_name":"虚拟Singer","parent_area_name":"虚拟主播"}}"#.as_bytes()).unwrap(),
+            Some(Event::RoomChange {
+                title: "meow".into(),
+                area: LiveArea {
+                    parent: "虚拟主播".into(),
+                    child: "虚拟Singer".into(),
+                }
+            })
+        );
+        assert_eq!(
+            parse_event(br#"{"cmd":"PREPARING","roomid":"14172231"}"#).unwrap(),
+            Some(Event::Preparing)
+        );
+        assert_eq!(
+            parse_event(br#"{"cmd":"CUT_OFF","msg":"meow","roomid":14172231}"#).unwrap(),
+            Some(Event::CutOff)
+        );
+        assert!(parse_event(br#"{"cmd":"ROOM_CHANGE","data":{}}"#).is_err());
+    }
+}
@@ -7,24 +7,32 @@ use std::{
 };
 
 use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde_json::{self as json, json};
-use spdlog::critical;
-use tokio::sync::Mutex;
+use spdlog::{critical, prelude::*};
+use tokio::{
+    sync::{mpsc, Mutex},
+    time::Instant,
+};
 
 use super::*;
 use crate::{
-    config,
+    config, helper,
     platform::{PlatformMetadata, PlatformTrait},
     source::{
-        FetcherTrait, LiveStatus, LiveStatusKind, Status, StatusKind, StatusSource,
-        StatusSourceUser,
+        batcher::Batcher, FetcherTrait, ListenerTrait, LiveArea, LiveStatus, LiveStatusKind,
+        LiveUpdate, Status, StatusKind, StatusSource, StatusSourceUser, StatusState, StatusStore,
+        Update, UpdateKind,
     },
 };
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct ConfigParams {
     pub user_id: u64,
+    // Listen to the danmaku WebSocket for live status changes, instead of polling
+    #[serde(default)]
+    pub realtime: bool,
 }
 
 impl config::Validator for ConfigParams {
@@ -42,20 +50,16 @@ impl fmt::Display for ConfigParams {
 const BILIBILI_LIVE_API: &str =
     "https://api.live.bilibili.com/room/v1/Room/get_status_info_by_uids";
 
-enum RoomData {
-    Normal(ResponseDataRoom),
-    Banned,
-}
-
 #[derive(Clone, Debug, Deserialize)]
 struct ResponseDataRoom {
     title: String,
     room_id: u64,
-    #[allow(dead_code)]
     uid: u64,
     live_time: u64,   // Unix timestamp
     live_status: u64, // 0: offline, 1: online, 2: replay
     uname: String,
+    area_v2_name: String,
+    area_v2_parent_name: String,
     cover_from_user: String, // Empty for no cover (not yet updated)
 }
 
@@ -84,8 +88,15 @@ impl fmt::Display for Fetcher {
     }
 }
 
+impl Drop for Fetcher {
+    fn drop(&mut self) {
+        BATCHER.unregister(&self.params.user_id);
+    }
+}
+
 impl Fetcher {
     pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        BATCHER.register(params.user_id);
         Self {
             params,
             room_data_cache: Mutex::new(None),
@@ -93,28 +104,43 @@ impl Fetcher {
     }
 
     async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
-        let data = fetch_live_info(self.params.user_id).await?;
+        let status = match self.fetch_room().await? {
+            Some((data, is_banned)) => {
+                let (live_status, source) = self.room_data_into_live_status(data, is_banned);
+                Status::new(StatusKind::Live(live_status), source)
+            }
+            None => Status::empty(),
+        };
+        Ok(status)
+    }
+
+    // Returns `None` if the room is banned and we have never seen its data
+    async fn fetch_room(&self) -> anyhow::Result<Option<(ResponseDataRoom, bool /* is_banned */)>> {
+        let data = BATCHER
+            .fetch(&self.params.user_id, |uids| async move {
+                fetch_live_info(&uids).await
+            })
+            .await?;
 
         let mut cache = self.room_data_cache.lock().await;
-        let status = match data {
-            RoomData::Normal(data) => {
+        let room = match data {
+            Some(data) => {
                 *cache = Some(data.clone());
-                self.room_data_into_status(data, false)
-            }
-            RoomData::Banned => {
-                if let Some(data) = &*cache {
-                    self.room_data_into_status(data.clone(), true)
-                } else {
-                    Status::empty()
-                }
+                Some((data, false))
             }
+            // Banned rooms are absent from the response
+            None => cache.clone().map(|data| (data, true)),
         };
-        Ok(status)
+        Ok(room)
     }
 
-    fn room_data_into_status(&self, data: ResponseDataRoom, is_banned: bool) -> Status {
-        Status::new(
-            StatusKind::Live(LiveStatus {
+    fn room_data_into_live_status(
+        &self,
+        data: ResponseDataRoom,
+        is_banned: bool,
+    ) -> (LiveStatus, StatusSource) {
+        (
+            LiveStatus {
                 kind: match (is_banned, data.live_status) {
                     (true, _) => LiveStatusKind::Banned,
                     (false, 0 | 2) => LiveStatusKind::Offline,
@@ -128,6 +154,10 @@ impl Fetcher {
                     }
                 },
                 title: data.title,
+                area: Some(LiveArea {
+                    parent: data.area_v2_parent_name,
+                    child: data.area_v2_name,
+                }),
                 streamer_name: data.uname.clone(),
                 cover_image_url: if !data.cover_from_user.is_empty() {
                     upgrade_to_https(&data.cover_from_user)
@@ -136,7 +166,7 @@ impl Fetcher {
                         .into()
                 },
                 live_url: format!("https://live.bilibili.com/{}", data.room_id),
-            }),
+            },
             StatusSource {
                 platform: self.metadata(),
                 user: Some(StatusSourceUser {
@@ -148,8 +178,193 @@ impl Fetcher {
     }
 }
 
-async fn fetch_live_info(user_id: u64) -> anyhow::Result<RoomData> {
-    let body = json!({ "uids": [user_id] });
+// Reconnecting to the danmaku server is backed off from this delay. While
+// disconnected, the live status is polled on the subscription interval as the
+// fallback, and before each attempt
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+pub struct Listener {
+    fetcher: Fetcher,
+    interval: Duration,
+    // Shared with the poll-based fetcher, so switching between them doesn't produce
+    // duplicate notifications
+    status_store: StatusStore,
+    last: Option<LiveStatus>,
+    source: Option<StatusSource>,
+}
+
+impl PlatformTrait for Listener {
+    fn metadata(&self) -> PlatformMetadata {
+        self.fetcher.metadata()
+    }
+}
+
+impl ListenerTrait for Listener {
+    fn listen(
+        &mut self,
+        sender: mpsc::Sender<Update>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(self.listen_impl(sender))
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fetcher)
+    }
+}
+
+impl Listener {
+    pub fn new(
+        params: config::Accessor<ConfigParams>,
+        interval: Duration,
+        status_store: StatusStore,
+    ) -> Self {
+        Self {
+            fetcher: Fetcher::new(params),
+            interval,
+            status_store,
+            last: None,
+            source: None,
+        }
+    }
+
+    async fn listen_impl(&mut self, sender: mpsc::Sender<Update>) {
+        self.last = match self.status_store.load().await {
+            Some((StatusState::Live(live_status), _)) => Some(live_status),
+            _ => None,
+        };
+
+        let mut room_id = None;
+        let mut failures = 0;
+        loop {
+            // Poll for the initial status, and for the changes missed while disconnected
+            if !self.poll(&mut room_id, &sender).await {
+                return;
+            }
+
+            if let Some(room_id) = room_id {
+                match danmaku::Connection::connect(room_id).await {
+                    Ok(mut connection) => {
+                        info!("'{self}' connected to danmaku server");
+                        failures = 0;
+                        loop {
+                            match connection.next_event().await {
+                                Ok(event) => {
+                                    if !self.handle_event(event, &sender).await {
+                                        return;
+                                    }
+                                }
+                                Err(err) => {
+                                    info!("'{self}' disconnected from danmaku server: {err}");
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => info!("'{self}' failed to connect to danmaku server: {err}"),
+                }
+            }
+
+            let reconnect_at = Instant::now()
+                + helper::exponential_backoff(RECONNECT_BASE_DELAY, failures, RECONNECT_MAX_DELAY);
+            failures += 1;
+
+            // Keep polling on the subscription interval until reconnecting
+            loop {
+                let poll_at = Instant::now() + self.interval;
+                if poll_at >= reconnect_at {
+                    tokio::time::sleep_until(reconnect_at).await;
+                    break;
+                }
+                tokio::time::sleep_until(poll_at).await;
+                if !self.poll(&mut room_id, &sender).await {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Returns `false` if the receiver is gone
+    async fn poll(&mut self, room_id: &mut Option<u64>, sender: &mpsc::Sender<Update>) -> bool {
+        match self.fetcher.fetch_room().await {
+            Ok(Some((data, is_banned))) => {
+                *room_id = Some(data.room_id);
+                let (live_status, source) =
+                    self.fetcher.room_data_into_live_status(data, is_banned);
+                self.source = Some(source);
+                self.update(live_status, sender).await
+            }
+            Ok(None) => true,
+            Err(err) => {
+                info!("failed to fetch status for '{self}': {err}");
+                true
+            }
+        }
+    }
+
+    // Returns `false` if the receiver is gone
+    async fn handle_event(&mut self, event: danmaku::Event, sender: &mpsc::Sender<Update>) -> bool {
+        trace!("'{self}' received danmaku event '{event:?}'");
+
+        let Some(mut live_status) = self.last.clone() else {
+            return true;
+        };
+        match event {
+            danmaku::Event::Live => {
+                // The event may be sent multiple times, keep the first start time
+                if !matches!(live_status.kind, LiveStatusKind::Online { .. }) {
+                    live_status.kind = LiveStatusKind::Online {
+                        start_time: Some(SystemTime::now()),
+                    };
+                }
+            }
+            danmaku::Event::Preparing | danmaku::Event::CutOff => {
+                live_status.kind = LiveStatusKind::Offline
+            }
+            danmaku::Event::RoomChange { title, area } => {
+                live_status.title = title;
+                live_status.area = Some(area);
+            }
+            danmaku::Event::RoomLock => live_status.kind = LiveStatusKind::Banned,
+        }
+        self.update(live_status, sender).await
+    }
+
+    // Returns `false` if the receiver is gone
+    async fn update(&mut self, live_status: LiveStatus, sender: &mpsc::Sender<Update>) -> bool {
+        let Some(last) = self.last.replace(live_status.clone()) else {
+            self.status_store
+                .save(&StatusState::Live(live_status.clone()))
+                .await;
+            return true;
+        };
+        if last == live_status {
+            return true;
+        }
+        self.status_store
+            .save(&StatusState::Live(live_status.clone()))
+            .await;
+
+        let update = Update::new(
+            UpdateKind::Live(Box::new(LiveUpdate {
+                current: live_status,
+                last,
+            })),
+            self.source.clone().unwrap(),
+        );
+        sender.send(update).await.is_ok()
+    }
+}
+
+// The API accepts multiple uids
+static BATCHER: Lazy<Batcher<u64, ResponseDataRoom>> = Lazy::new(|| Batcher::new("bilibili.live"));
+
+async fn fetch_live_info(
+    uids: impl IntoIterator<Item = &u64>,
+) -> anyhow::Result<HashMap<u64, ResponseDataRoom>> {
+    let body = json!({ "uids": uids.into_iter().collect::<Vec<_>>() });
 
     let resp = bilibili_request_builder()?
         .post(BILIBILI_LIVE_API)
@@ -167,33 +382,35 @@ async fn fetch_live_info(user_id: u64) -> anyhow::Result<RoomData> {
         .text()
         .await
         .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
-    let json: json::Value = json::from_str(&text)
+    parse_live_info(&text)
+}
+
+fn parse_live_info(text: &str) -> anyhow::Result<HashMap<u64, ResponseDataRoom>> {
+    let json: json::Value = json::from_str(text)
         .map_err(|err| anyhow!("failed to deserialize response to json value: {err}"))?;
 
-    // If the room is banned, the `data` field will be an empty array instead of an
-    // object
-    let is_banned = json
+    // If all rooms are banned, the `data` field will be an empty array instead of
+    // an object
+    let is_all_banned = json
         .get("data")
         .and_then(|data| data.as_array())
         .is_some_and(|data| data.is_empty());
-    if is_banned {
-        return Ok(RoomData::Banned);
+    if is_all_banned {
+        return Ok(HashMap::new());
     }
 
     let resp: Response<HashMap<String, ResponseDataRoom>> =
-        json::from_str(&text).map_err(|err| anyhow!("failed to deserialize response: {err}"))?;
+        json::from_value(json).map_err(|err| anyhow!("failed to deserialize response: {err}"))?;
     if resp.code != 0 {
         bail!("response contains error, response '{text}'");
     }
 
-    resp.data
-        .unwrap()
+    Ok(resp
+        .data
+        .ok_or_else(|| anyhow!("UNEXPECTED! response without data, response '{text}'"))?
         .into_values()
-        .next()
-        .ok_or_else(|| {
-            anyhow!("UNEXPECTED! response with unexpected data array, response '{text}'")
-        })
-        .map(RoomData::Normal)
+        .map(|room| (room.uid, room))
+        .collect())
 }
 
 #[cfg(test)]
@@ -202,6 +419,27 @@ mod tests {
 
     #[tokio::test]
     async fn deser() {
-        fetch_live_info(9617619).await.unwrap();
+        fetch_live_info(&[9617619]).await.unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        let rooms = parse_live_info(
+            r#"{"code":0,"msg":"success","message":"success","data":{"9617619":{"title":"meow","room_id":14172231,"uid":9617619,"online":0,"live_time":0,"live_status":0,"short_id":0,"area":6,"area_name":"生活娱乐","area_v2_id":744,"area_v2_name":"虚拟Singer","area_v2_parent_name":"虚拟主播","area_v2_parent_id":9,"uname":"Shiro","face":"","tag_name":"","tags":"","cover_from_user":"","keyframe":"","lock_till":"0000-00-00 00:00:00","hidden_till":"0000-00-00 00:00:00","broadcast_type":0}}}"#,
+        )
+        .unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[&9617619].room_id, 14172231);
+        assert_eq!(rooms[&9617619].area_v2_parent_name, "虚拟主播");
+        assert_eq!(rooms[&9617619].area_v2_name, "虚拟Singer");
+
+        let rooms =
+            parse_live_info(r#"{"code":0,"msg":"success","message":"success","data":[]}"#).unwrap();
+        assert!(rooms.is_empty());
+
+        assert!(
+            parse_live_info(r#"{"code":-400,"msg":"error","message":"error","data":null}"#)
+                .is_err()
+        );
     }
 }
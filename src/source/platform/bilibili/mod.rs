@@ -1,3 +1,4 @@
+mod danmaku;
 pub mod live;
 pub mod playback;
 pub mod space;
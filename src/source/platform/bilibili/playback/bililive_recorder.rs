@@ -66,10 +66,18 @@ impl BililiveRecorder {
     }
 
     pub async fn add_listener(&self, room_id: u64, sender: mpsc::Sender<Update>) {
-        assert!(self.senders.lock().await.insert(room_id, sender).is_none());
+        // The previous listener may be left by a subscription stopped on reloading,
+        // which is aborted asynchronously and may be still alive here, the new one
+        // replaces it anyway
+        if let Some(prev) = self.senders.lock().await.insert(room_id, sender) {
+            if !prev.is_closed() {
+                debug!("bililive-recorder listener for room {room_id} is replaced before closed");
+            }
+        }
     }
 
-    // Only block on the first call
+    // The server is spawned in background on the first call, so that it keeps
+    // serving when the subscription task that started it is stopped
     pub async fn listen(&self) -> anyhow::Result<()> {
         if mem::replace(&mut *self.is_listening.lock().await, true) {
             return Ok(());
@@ -87,9 +95,7 @@ impl BililiveRecorder {
             .and(with_context(ctx))
             .and_then(webhook_handler);
 
-        warp::serve(routes)
-            .run(self.config.listen_webhook.to_addr().unwrap())
-            .await;
+        tokio::spawn(warp::serve(routes).run(self.config.listen_webhook.to_addr().unwrap()));
         Ok(())
     }
 }
@@ -0,0 +1,739 @@
+use std::{collections::HashSet, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use chrono::{DateTime, Local, Utc};
+use once_cell::sync::Lazy;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use spdlog::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        FetcherTrait, Post, PostAttachment, PostAttachmentImage, PostContent, PostUrl, Posts,
+        RepostFrom, Status, StatusKind, StatusSource, StatusSourceUser, User,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigGlobal {
+    // Handle, DID or email of the account
+    pub identifier: String,
+    #[serde(flatten)]
+    pub app_password: ConfigAppPassword,
+    // PDS of the account, only needed for self-hosted ones
+    #[serde(default)]
+    pub service: Option<String>,
+}
+
+impl config::Validator for ConfigGlobal {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.identifier.is_empty(), "identifier is empty");
+        self.app_password.validate()?;
+        Ok(())
+    }
+}
+
+impl ConfigGlobal {
+    fn service(&self) -> &str {
+        self.service
+            .as_deref()
+            .unwrap_or(BLUESKY_DEFAULT_SERVICE)
+            .trim_end_matches('/')
+    }
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigAppPassword {
+        AppPassword(String),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    // Handle (e.g. `bsky.app`) or DID
+    pub handle: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.handle.is_empty() && !self.handle.starts_with('@'),
+            "invalid handle '{}', it should be without the leading '@'",
+            self.handle
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bluesky:{}", self.handle)
+    }
+}
+
+const BLUESKY_PUBLIC_API: &str = "https://public.api.bsky.app";
+const BLUESKY_DEFAULT_SERVICE: &str = "https://bsky.social";
+
+// Labels hiding media behind a warning on bsky.app
+const SPOILER_LABELS: [&str; 4] = ["porn", "sexual", "nudity", "graphic-media"];
+
+mod data {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Session {
+        pub access_jwt: String,
+        pub refresh_jwt: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Error {
+        pub error: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct AuthorFeed {
+        pub feed: Vec<FeedViewPost>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct FeedViewPost {
+        pub post: PostView,
+        pub reason: Option<Reason>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(tag = "$type")]
+    pub enum Reason {
+        #[serde(rename = "app.bsky.feed.defs#reasonRepost", rename_all = "camelCase")]
+        Repost {
+            by: Author,
+            uri: Option<String>, // The repost record, absent from older AppViews
+            indexed_at: DateTime<Utc>,
+        },
+        #[serde(rename = "app.bsky.feed.defs#reasonPin")]
+        Pin,
+        #[serde(other)]
+        Unknown,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PostView {
+        pub uri: String, // at://<did>/app.bsky.feed.post/<rkey>
+        pub author: Author,
+        pub record: PostRecord,
+        pub embed: Option<EmbedView>,
+        #[serde(default)]
+        pub labels: Vec<Label>,
+        pub indexed_at: DateTime<Utc>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ViewRecord {
+        pub uri: String,
+        pub author: Author,
+        pub value: PostRecord,
+        #[serde(default)]
+        pub embeds: Vec<EmbedView>,
+        #[serde(default)]
+        pub labels: Vec<Label>,
+        pub indexed_at: DateTime<Utc>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Author {
+        pub did: String,
+        pub handle: String,
+        pub display_name: Option<String>,
+        pub avatar: Option<String>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct Label {
+        pub val: String,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct PostRecord {
+        #[serde(default)]
+        pub text: String,
+        #[serde(default)]
+        pub facets: Vec<Facet>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct Facet {
+        pub index: FacetIndex,
+        pub features: Vec<FacetFeature>,
+    }
+
+    // UTF-8 byte offsets into the text
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct FacetIndex {
+        pub byte_start: usize,
+        pub byte_end: usize,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(tag = "$type")]
+    pub enum FacetFeature {
+        #[serde(rename = "app.bsky.richtext.facet#link")]
+        Link { uri: String },
+        #[serde(rename = "app.bsky.richtext.facet#mention")]
+        Mention { did: String },
+        #[serde(other)]
+        Unknown,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(tag = "$type")]
+    pub enum EmbedView {
+        #[serde(rename = "app.bsky.embed.images#view")]
+        Images { images: Vec<EmbedImage> },
+        // The video itself is only available as an HLS playlist
+        #[serde(rename = "app.bsky.embed.video#view")]
+        Video { thumbnail: Option<String> },
+        #[serde(rename = "app.bsky.embed.record#view")]
+        Record { record: EmbedRecord },
+        #[serde(rename = "app.bsky.embed.recordWithMedia#view")]
+        RecordWithMedia {
+            record: EmbedRecordView,
+            media: Box<EmbedView>,
+        },
+        #[serde(other)]
+        Unknown,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct EmbedImage {
+        pub fullsize: String,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct EmbedRecordView {
+        pub record: EmbedRecord,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(tag = "$type")]
+    pub enum EmbedRecord {
+        #[serde(rename = "app.bsky.embed.record#viewRecord")]
+        Post(Box<ViewRecord>),
+        // Not found, blocked, detached, or records other than posts (e.g. feeds)
+        #[serde(other)]
+        Unknown,
+    }
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Bluesky",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let feed = fetch_author_feed(&self.params.handle).await?;
+
+        let author = feed
+            .feed
+            .iter()
+            .find(|item| !matches!(item.reason, Some(data::Reason::Repost { .. })))
+            .map(|item| &item.post.author)
+            .or_else(|| {
+                feed.feed.iter().find_map(|item| match &item.reason {
+                    Some(data::Reason::Repost { by, .. }) => Some(by),
+                    _ => None,
+                })
+            });
+        let source_user = author.map(|author| {
+            let user = author_into_user(author);
+            StatusSourceUser {
+                display_name: user.nickname,
+                profile_url: user.profile_url,
+            }
+        });
+
+        Ok(Status::new(
+            StatusKind::Posts(parse_feed(feed.feed)),
+            StatusSource {
+                platform: self.metadata(),
+                user: source_user,
+            },
+        ))
+    }
+}
+
+async fn fetch_author_feed(actor: &str) -> anyhow::Result<data::AuthorFeed> {
+    let query = [
+        ("actor", actor),
+        ("filter", "posts_no_replies"),
+        ("includePins", "true"),
+        ("limit", "30"),
+    ];
+
+    let global = (*config::Config::global().platform().bluesky).clone();
+    let Some(global) = global else {
+        let req = helper::reqwest_client()?
+            .get(format!(
+                "{BLUESKY_PUBLIC_API}/xrpc/app.bsky.feed.getAuthorFeed"
+            ))
+            .query(&query);
+        return match request_xrpc(req).await? {
+            Some(feed) => Ok(feed),
+            None => bail!("the feed requires authentication, configure an app password in global"),
+        };
+    };
+
+    // Requests through the PDS are proxied to the AppView with the session
+    let mut session = SESSION.lock().await;
+    for retry in [false, true] {
+        let access_jwt = session.access_jwt(&global, retry).await?;
+        let req = helper::reqwest_client()?
+            .get(format!(
+                "{}/xrpc/app.bsky.feed.getAuthorFeed",
+                global.service()
+            ))
+            .query(&query)
+            .bearer_auth(access_jwt);
+        if let Some(feed) = request_xrpc(req).await? {
+            return Ok(feed);
+        }
+    }
+    bail!("session is rejected even after renewed")
+}
+
+// Returns `None` if the request is rejected for authentication, including the
+// expiration of the access token
+async fn request_xrpc<T: DeserializeOwned>(req: RequestBuilder) -> anyhow::Result<Option<T>> {
+    let resp = req
+        .send()
+        .await
+        .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+
+    if !status.is_success() {
+        let error = serde_json::from_str::<data::Error>(&text).map(|resp| resp.error);
+        let is_auth_error = status == StatusCode::UNAUTHORIZED
+            || error.as_deref().is_ok_and(|error| {
+                matches!(
+                    error,
+                    "ExpiredToken" | "InvalidToken" | "AuthenticationRequired"
+                )
+            });
+        if is_auth_error {
+            return Ok(None);
+        }
+        bail!("response status '{status}' is not success, response '{text}'");
+    }
+
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|err| anyhow!("failed to deserialize response: {err}, text: {text}"))
+}
+
+// Shared by all fetchers, so that the session is not created for each of them,
+// as `createSession` is heavily rate limited
+static SESSION: Lazy<Mutex<Session>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Session {
+    // The identity of the account the session belongs to
+    account: Option<(String /* identifier */, String /* service */)>,
+    session: Option<data::Session>,
+}
+
+impl Session {
+    async fn access_jwt(&mut self, global: &ConfigGlobal, renew: bool) -> anyhow::Result<String> {
+        let account = (global.identifier.clone(), global.service().to_string());
+        if self.account.as_ref() != Some(&account) {
+            self.session = None;
+        }
+
+        if renew {
+            if let Some(session) = self.session.take() {
+                let refreshed =
+                    xrpc_procedure(global, "com.atproto.server.refreshSession", None, |req| {
+                        req.bearer_auth(&session.refresh_jwt)
+                    })
+                    .await;
+                match refreshed {
+                    Ok(session) => self.session = Some(session),
+                    Err(err) => {
+                        info!("failed to refresh Bluesky session, creating a new one: {err}")
+                    }
+                }
+            }
+        }
+
+        if self.session.is_none() {
+            let body = json!({
+                "identifier": global.identifier,
+                "password": global.app_password.as_secret_ref().get_str()?,
+            });
+            let session = xrpc_procedure(
+                global,
+                "com.atproto.server.createSession",
+                Some(body),
+                |req| req,
+            )
+            .await
+            .map_err(|err| anyhow!("failed to create session: {err}"))?;
+            self.session = Some(session);
+            self.account = Some(account);
+        }
+        Ok(self.session.as_ref().unwrap().access_jwt.clone())
+    }
+}
+
+async fn xrpc_procedure(
+    global: &ConfigGlobal,
+    nsid: &str,
+    body: Option<serde_json::Value>,
+    configure: impl FnOnce(RequestBuilder) -> RequestBuilder,
+) -> anyhow::Result<data::Session> {
+    let mut req = helper::reqwest_client()?.post(format!("{}/xrpc/{nsid}", global.service()));
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    request_xrpc(configure(req))
+        .await?
+        .ok_or_else(|| anyhow!("authentication failed"))
+}
+
+fn parse_feed(items: Vec<data::FeedViewPost>) -> Posts {
+    let pinned_uris = items
+        .iter()
+        .filter(|item| matches!(item.reason, Some(data::Reason::Pin)))
+        .map(|item| item.post.uri.clone())
+        .collect::<HashSet<_>>();
+
+    // The pinned post is placed at the top, and also returned at its original
+    // position, keep the pinned one only
+    let posts = items
+        .into_iter()
+        .filter(|item| {
+            !matches!(item.reason, None | Some(data::Reason::Unknown))
+                || !pinned_uris.contains(&item.post.uri)
+        })
+        .map(parse_feed_item)
+        .collect();
+    Posts(posts)
+}
+
+fn parse_feed_item(item: data::FeedViewPost) -> Post {
+    let post = parse_post_view(item.post);
+    match item.reason {
+        Some(data::Reason::Repost {
+            by,
+            uri,
+            indexed_at,
+        }) => {
+            let major = post.urls.major();
+            // Fallback to an ID unique for each reposter
+            let id = uri.unwrap_or_else(|| format!("{}#repost:{}", major.unique_id(), by.did));
+            let url = match major.as_clickable() {
+                Some(clickable) => PostUrl::new_clickable_with_id(&clickable.url, "View Post", id),
+                None => PostUrl::Identity(id),
+            };
+            Post {
+                user: author_into_user(&by),
+                content: PostContent::plain("Repost"),
+                urls: url.into(),
+                time: DateTime::<Local>::from(indexed_at),
+                is_pinned: false,
+                repost_from: Some(RepostFrom::Recursion(Box::new(post))),
+                attachments: vec![],
+            }
+        }
+        Some(data::Reason::Pin) => Post {
+            is_pinned: true,
+            ..post
+        },
+        Some(data::Reason::Unknown) | None => post,
+    }
+}
+
+fn parse_post_view(post: data::PostView) -> Post {
+    build_post(
+        post.uri,
+        &post.author,
+        post.record,
+        post.embed,
+        &post.labels,
+        post.indexed_at,
+    )
+}
+
+fn parse_view_record(record: data::ViewRecord) -> Post {
+    build_post(
+        record.uri,
+        &record.author,
+        record.value,
+        record.embeds.into_iter().next(),
+        &record.labels,
+        record.indexed_at,
+    )
+}
+
+fn build_post(
+    uri: String,
+    author: &data::Author,
+    record: data::PostRecord,
+    embed: Option<data::EmbedView>,
+    labels: &[data::Label],
+    time: DateTime<Utc>,
+) -> Post {
+    let has_spoiler = labels
+        .iter()
+        .any(|label| SPOILER_LABELS.contains(&label.val.as_str()));
+
+    let (attachments, quoted) = match embed {
+        Some(data::EmbedView::Record { record }) => (vec![], Some(record)),
+        Some(data::EmbedView::RecordWithMedia { record, media }) => {
+            (parse_media(*media, has_spoiler), Some(record.record))
+        }
+        Some(embed) => (parse_media(embed, has_spoiler), None),
+        None => (vec![], None),
+    };
+    let repost_from = quoted.and_then(|quoted| match quoted {
+        data::EmbedRecord::Post(record) => {
+            Some(RepostFrom::Recursion(Box::new(parse_view_record(*record))))
+        }
+        data::EmbedRecord::Unknown => None,
+    });
+
+    let url = post_uri_to_url(&uri, &author.handle);
+    Post {
+        user: author_into_user(author),
+        content: replace_facets(record.text, &record.facets),
+        urls: match url {
+            Some(url) => PostUrl::new_clickable_with_id(url, "View Post", uri),
+            None => PostUrl::Identity(uri),
+        }
+        .into(),
+        time: DateTime::<Local>::from(time),
+        is_pinned: false,
+        repost_from,
+        attachments,
+    }
+}
+
+fn parse_media(embed: data::EmbedView, has_spoiler: bool) -> Vec<PostAttachment> {
+    match embed {
+        data::EmbedView::Images { images } => images
+            .into_iter()
+            .map(|image| {
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: image.fullsize,
+                    has_spoiler,
+                })
+            })
+            .collect(),
+        // Notifiers cannot send HLS, fallback to the thumbnail
+        data::EmbedView::Video { thumbnail } => thumbnail
+            .into_iter()
+            .map(|thumbnail| {
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: thumbnail,
+                    has_spoiler,
+                })
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// at://<did>/app.bsky.feed.post/<rkey> -> https://bsky.app/profile/<handle>/post/<rkey>
+fn post_uri_to_url(uri: &str, handle: &str) -> Option<String> {
+    let (_, rkey) = uri
+        .strip_prefix("at://")?
+        .split_once("/app.bsky.feed.post/")?;
+    Some(format!("https://bsky.app/profile/{handle}/post/{rkey}"))
+}
+
+fn author_into_user(author: &data::Author) -> User {
+    User {
+        nickname: author
+            .display_name
+            .clone()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| author.handle.clone()),
+        profile_url: format!("https://bsky.app/profile/{}", author.handle),
+        avatar_url: author.avatar.clone(),
+    }
+}
+
+fn replace_facets(text: String, facets: &[data::Facet]) -> PostContent {
+    let mut facets = facets
+        .iter()
+        .filter_map(|facet| {
+            let url = facet.features.iter().find_map(|feature| match feature {
+                data::FacetFeature::Link { uri } => Some(uri.clone()),
+                data::FacetFeature::Mention { did } => {
+                    Some(format!("https://bsky.app/profile/{did}"))
+                }
+                data::FacetFeature::Unknown => None,
+            })?;
+            Some((facet.index.byte_start, facet.index.byte_end, url))
+        })
+        .collect::<Vec<_>>();
+    facets.sort_by_key(|(start, _, _)| *start);
+
+    let mut content = PostContent::from_parts([]);
+    let mut cursor = 0;
+    for (start, end, url) in facets {
+        // Skip the malformed ones, overlapping or not on char boundaries
+        let Some(display) = (start >= cursor && start < end)
+            .then(|| text.get(start..end))
+            .flatten()
+        else {
+            warn!("Bluesky post contains an invalid facet {start}..{end}, text: '{text}'");
+            continue;
+        };
+        if start > cursor {
+            content.push_plain(&text[cursor..start]);
+        }
+        content.push_link(display, url);
+        cursor = end;
+    }
+    if cursor < text.len() || content.is_empty() {
+        content.push_plain(&text[cursor..]);
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facets() {
+        let facets: Vec<data::Facet> = serde_json::from_str(
+            r#"[
+                {"index":{"byteStart":10,"byteEnd":19},"features":[{"$type":"app.bsky.richtext.facet#mention","did":"did:plc:z72i7hdynmk6r22z27h6tvur"}]},
+                {"index":{"byteStart":24,"byteEnd":35},"features":[{"$type":"app.bsky.richtext.facet#link","uri":"https://example.com/"}]},
+                {"index":{"byteStart":36,"byteEnd":40},"features":[{"$type":"app.bsky.richtext.facet#tag","tag":"cat"}]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            replace_facets("喵喵 hi @bsky.app see example.com #cat".into(), &facets),
+            PostContent::plain("喵喵 hi ")
+                .with_link(
+                    "@bsky.app",
+                    "https://bsky.app/profile/did:plc:z72i7hdynmk6r22z27h6tvur"
+                )
+                .with_plain(" see ")
+                .with_link("example.com", "https://example.com/")
+                .with_plain(" #cat")
+        );
+        assert_eq!(replace_facets("".into(), &[]), PostContent::plain(""));
+    }
+
+    #[test]
+    fn parse() {
+        let author = r#"{"did":"did:plc:meow","handle":"meow.bsky.social","displayName":"Meow","avatar":"https://cdn.bsky.app/avatar.jpg"}"#;
+        let feed: data::AuthorFeed = serde_json::from_str(&format!(
+            r#"{{"feed":[
+                {{"post":{{"uri":"at://did:plc:meow/app.bsky.feed.post/1","cid":"c1","author":{author},"record":{{"$type":"app.bsky.feed.post","text":"pinned","createdAt":"2024-01-01T00:00:00.000Z"}},"labels":[],"indexedAt":"2024-01-01T00:00:00.000Z"}},"reason":{{"$type":"app.bsky.feed.defs#reasonPin"}}}},
+                {{"post":{{"uri":"at://did:plc:woof/app.bsky.feed.post/2","cid":"c2","author":{{"did":"did:plc:woof","handle":"woof.bsky.social","displayName":""}},"record":{{"$type":"app.bsky.feed.post","text":"woof","createdAt":"2025-01-01T00:00:00.000Z"}},"embed":{{"$type":"app.bsky.embed.video#view","cid":"v","playlist":"https://video.bsky.app/watch/did%3Aplc%3Awoof/v/playlist.m3u8","thumbnail":"https://video.bsky.app/watch/did%3Aplc%3Awoof/v/thumbnail.jpg"}},"labels":[{{"val":"graphic-media"}}],"indexedAt":"2025-01-01T00:00:00.000Z"}},"reason":{{"$type":"app.bsky.feed.defs#reasonRepost","by":{author},"uri":"at://did:plc:meow/app.bsky.feed.repost/3","indexedAt":"2025-01-02T00:00:00.000Z"}}}},
+                {{"post":{{"uri":"at://did:plc:meow/app.bsky.feed.post/4","cid":"c4","author":{author},"record":{{"$type":"app.bsky.feed.post","text":"quote","createdAt":"2025-01-03T00:00:00.000Z"}},"embed":{{"$type":"app.bsky.embed.recordWithMedia#view","record":{{"record":{{"$type":"app.bsky.embed.record#viewRecord","uri":"at://did:plc:woof/app.bsky.feed.post/5","cid":"c5","author":{{"did":"did:plc:woof","handle":"woof.bsky.social"}},"value":{{"$type":"app.bsky.feed.post","text":"quoted","createdAt":"2024-12-01T00:00:00.000Z"}},"embeds":[{{"$type":"app.bsky.embed.external#view","external":{{"uri":"https://example.com/","title":"","description":""}}}}],"indexedAt":"2024-12-01T00:00:00.000Z"}}}},"media":{{"$type":"app.bsky.embed.images#view","images":[{{"thumb":"https://cdn.bsky.app/thumb.jpg","fullsize":"https://cdn.bsky.app/full.jpg","alt":""}}]}}}},"indexedAt":"2025-01-03T00:00:00.000Z"}}}}
+            ],"cursor":"x"}}"#
+        ))
+        .unwrap();
+        let posts = parse_feed(feed.feed).0;
+        assert_eq!(posts.len(), 3);
+
+        assert!(posts[0].is_pinned);
+        assert_eq!(
+            posts[0].urls.major().as_clickable().unwrap().url,
+            "https://bsky.app/profile/meow.bsky.social/post/1"
+        );
+        assert_eq!(
+            posts[0].urls.major().unique_id(),
+            "at://did:plc:meow/app.bsky.feed.post/1"
+        );
+
+        let repost = &posts[1];
+        assert_eq!(repost.user.nickname, "Meow");
+        assert_eq!(
+            repost.urls.major().unique_id(),
+            "at://did:plc:meow/app.bsky.feed.repost/3"
+        );
+        assert_eq!(
+            repost.urls.major().as_clickable().unwrap().url,
+            "https://bsky.app/profile/woof.bsky.social/post/2"
+        );
+        let Some(RepostFrom::Recursion(reposted)) = &repost.repost_from else {
+            panic!()
+        };
+        assert_eq!(reposted.user.nickname, "woof.bsky.social");
+        assert_eq!(
+            reposted.attachments(false).cloned().collect::<Vec<_>>(),
+            [PostAttachment::Image(PostAttachmentImage {
+                media_url: "https://video.bsky.app/watch/did%3Aplc%3Awoof/v/thumbnail.jpg".into(),
+                has_spoiler: true
+            })]
+        );
+
+        let quote = &posts[2];
+        assert_eq!(quote.content, PostContent::plain("quote"));
+        assert_eq!(
+            quote.attachments(false).cloned().collect::<Vec<_>>(),
+            [PostAttachment::Image(PostAttachmentImage {
+                media_url: "https://cdn.bsky.app/full.jpg".into(),
+                has_spoiler: false
+            })]
+        );
+        let Some(RepostFrom::Recursion(quoted)) = &quote.repost_from else {
+            panic!()
+        };
+        assert_eq!(quoted.content, PostContent::plain("quoted"));
+        assert_eq!(
+            quoted.urls.major().unique_id(),
+            "at://did:plc:woof/app.bsky.feed.post/5"
+        );
+        assert!(quoted.attachments(false).next().is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::HashSet, fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, ensure};
+use chrono::{DateTime, Local, Utc};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    config, helper,
+    platform::{PlatformMetadata, PlatformTrait},
+    source::{
+        html, FetcherTrait, Post, PostAttachment, PostAttachmentImage, PostAttachmentVideo,
+        PostContent, PostUrl, Posts, RepostFrom, Status, StatusKind, StatusSource,
+        StatusSourceUser, User,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    pub instance: String,
+    pub username: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.instance.is_empty() && !self.instance.contains(['/', ':', '@']),
+            "invalid instance '{}', it should be a domain like 'mastodon.social'",
+            self.instance
+        );
+        ensure!(
+            !self.username.is_empty() && !self.username.contains(['/', '@']),
+            "invalid username '{}', it should be without the leading '@' and the instance",
+            self.username
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mastodon:@{}@{}", self.username, self.instance)
+    }
+}
+
+// Number of the latest statuses to request, same as the API default
+const STATUSES_LIMIT: &str = "20";
+
+mod data {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Account {
+        pub id: String,
+        pub username: String,
+        pub display_name: String, // Empty if not set
+        pub url: String,
+        pub avatar: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Status {
+        pub uri: String,         // ActivityPub ID, unique across instances
+        pub url: Option<String>, // `None` for boosts
+        pub created_at: DateTime<Utc>,
+        pub account: Account,
+        pub content: String, // HTML
+        pub spoiler_text: String,
+        pub sensitive: bool,
+        pub media_attachments: Vec<MediaAttachment>,
+        pub reblog: Option<Box<Status>>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct MediaAttachment {
+        #[serde(rename = "type")]
+        pub kind: String, // "image", "gifv", "video", "audio" or "unknown"
+        pub url: String,
+    }
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+    // Looked up once, statuses are requested by the account ID
+    account_cache: Mutex<Option<data::Account>>,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Mastodon",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            account_cache: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let mut account = self.account_cache.lock().await;
+        if account.is_none() {
+            *account = Some(
+                self.request(
+                    "/api/v1/accounts/lookup",
+                    &[("acct", &self.params.username)],
+                )
+                .await?,
+            );
+        }
+        let account = account.as_mut().unwrap();
+
+        let path = format!("/api/v1/accounts/{}/statuses", account.id);
+        // The `pinned` field of statuses is only present for the authenticated user,
+        // so pinned statuses are requested separately
+        let pinned: Vec<data::Status> = self.request(&path, &[("pinned", "true")]).await?;
+        let statuses: Vec<data::Status> = self
+            .request(
+                &path,
+                &[("exclude_replies", "true"), ("limit", STATUSES_LIMIT)],
+            )
+            .await?;
+
+        // Keep the profile up to date without an extra request
+        if let Some(status) = statuses.first() {
+            *account = status.account.clone();
+        }
+
+        let user = account_into_user(account.clone());
+        let posts = parse_statuses(pinned, statuses);
+        Ok(Status::new(
+            StatusKind::Posts(posts),
+            StatusSource {
+                platform: self.metadata(),
+                user: Some(StatusSourceUser {
+                    display_name: user.nickname,
+                    profile_url: user.profile_url,
+                }),
+            },
+        ))
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> anyhow::Result<T> {
+        let resp = helper::reqwest_client()?
+            .get(format!("https://{}{path}", self.params.instance))
+            .query(query)
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+        let status = resp.status();
+        ensure!(
+            status.is_success(),
+            "response status is not success: {resp:?}"
+        );
+
+        let text = resp
+            .text()
+            .await
+            .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+        serde_json::from_str(&text)
+            .map_err(|err| anyhow!("failed to deserialize response: {err}, text: {text}"))
+    }
+}
+
+fn parse_statuses(pinned: Vec<data::Status>, statuses: Vec<data::Status>) -> Posts {
+    let pinned_uris = pinned
+        .iter()
+        .map(|status| status.uri.clone())
+        .collect::<HashSet<_>>();
+
+    // Pinned statuses are placed at the top, like what the profile page does
+    let posts = pinned
+        .into_iter()
+        .chain(
+            statuses
+                .into_iter()
+                .filter(|status| !pinned_uris.contains(&status.uri)),
+        )
+        .map(|status| {
+            let is_pinned = pinned_uris.contains(&status.uri);
+            Post {
+                is_pinned,
+                ..parse_status(status)
+            }
+        })
+        .collect();
+    Posts(posts)
+}
+
+fn parse_status(status: data::Status) -> Post {
+    let repost_from = status
+        .reblog
+        .map(|reblog| RepostFrom::Recursion(Box::new(parse_status(*reblog))));
+
+    // Boosts link to the boosted status, but are still identified by their own URI
+    let url = status
+        .url
+        .or_else(|| match &repost_from {
+            Some(RepostFrom::Recursion(post)) => post
+                .urls
+                .major()
+                .as_clickable()
+                .map(|clickable| clickable.url.clone()),
+            None => None,
+        })
+        .unwrap_or_else(|| status.uri.clone());
+
+    let content = if repost_from.is_some() {
+        PostContent::plain("Boost")
+    } else {
+        let content = html::to_post_content(&status.content);
+        // Content warning
+        if !status.spoiler_text.is_empty() {
+            PostContent::plain(format!("⚠️ {}", status.spoiler_text))
+                .with_plain("\n\n")
+                .with_content(content)
+        } else {
+            content
+        }
+    };
+
+    let attachments = status
+        .media_attachments
+        .into_iter()
+        .filter_map(|media| match media.kind.as_str() {
+            "image" => Some(PostAttachment::Image(PostAttachmentImage {
+                media_url: media.url,
+                has_spoiler: status.sensitive,
+            })),
+            "gifv" | "video" => Some(PostAttachment::Video(PostAttachmentVideo {
+                media_url: media.url,
+                has_spoiler: status.sensitive,
+            })),
+            _ => None,
+        })
+        .collect();
+
+    Post {
+        user: account_into_user(status.account),
+        content,
+        urls: PostUrl::new_clickable_with_id(url, "View Post", status.uri).into(),
+        time: DateTime::<Local>::from(status.created_at),
+        is_pinned: false,
+        repost_from,
+        attachments,
+    }
+}
+
+fn account_into_user(account: data::Account) -> User {
+    User {
+        nickname: if !account.display_name.is_empty() {
+            account.display_name
+        } else {
+            account.username
+        },
+        profile_url: account.url,
+        avatar_url: Some(account.avatar),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let account = r#"{"id":"1","username":"meow","acct":"meow","display_name":"Meow","url":"https://example.social/@meow","avatar":"https://example.social/avatar.png"}"#;
+        let pinned: Vec<data::Status> = serde_json::from_str(&format!(
+            r#"[{{"id":"100","uri":"https://example.social/users/meow/statuses/100","url":"https://example.social/@meow/100","created_at":"2024-01-01T00:00:00.000Z","account":{account},"content":"<p>Pinned</p>","spoiler_text":"","sensitive":false,"media_attachments":[],"reblog":null,"pinned":true}}]"#
+        ))
+        .unwrap();
+        let statuses: Vec<data::Status> = serde_json::from_str(&format!(
+            r#"[
+                {{"id":"102","uri":"https://example.social/users/meow/statuses/102/activity","url":null,"created_at":"2025-01-02T00:00:00.000Z","account":{account},"content":"","spoiler_text":"","sensitive":false,"media_attachments":[],"reblog":{{"id":"200","uri":"https://other.social/users/woof/statuses/200","url":"https://other.social/@woof/200","created_at":"2025-01-01T12:00:00.000Z","account":{{"id":"2","username":"woof","acct":"woof@other.social","display_name":"","url":"https://other.social/@woof","avatar":"https://other.social/avatar.png"}},"content":"<p>Woof</p>","spoiler_text":"","sensitive":false,"media_attachments":[],"reblog":null}}}},
+                {{"id":"101","uri":"https://example.social/users/meow/statuses/101","url":"https://example.social/@meow/101","created_at":"2025-01-01T00:00:00.000Z","account":{account},"content":"<p>Hello <span class=\"h-card\"><a href=\"https://other.social/@woof\" class=\"u-url mention\">@<span>woof</span></a></span></p>","spoiler_text":"cat","sensitive":true,"media_attachments":[{{"id":"1","type":"image","url":"https://example.social/cat.png","preview_url":"https://example.social/cat_small.png"}},{{"id":"2","type":"gifv","url":"https://example.social/cat.mp4","preview_url":"https://example.social/cat_small.png"}},{{"id":"3","type":"audio","url":"https://example.social/meow.mp3","preview_url":null}}],"reblog":null}},
+                {{"id":"100","uri":"https://example.social/users/meow/statuses/100","url":"https://example.social/@meow/100","created_at":"2024-01-01T00:00:00.000Z","account":{account},"content":"<p>Pinned</p>","spoiler_text":"","sensitive":false,"media_attachments":[],"reblog":null}}
+            ]"#
+        ))
+        .unwrap();
+        let posts = parse_statuses(pinned, statuses).0;
+        assert_eq!(posts.len(), 3);
+
+        assert!(posts[0].is_pinned);
+        assert_eq!(posts[0].content, PostContent::plain("Pinned"));
+
+        let boost = &posts[1];
+        assert!(!boost.is_pinned);
+        assert_eq!(
+            boost.urls.major().unique_id(),
+            "https://example.social/users/meow/statuses/102/activity"
+        );
+        assert_eq!(
+            boost.urls.major().as_clickable().unwrap().url,
+            "https://other.social/@woof/200"
+        );
+        let Some(RepostFrom::Recursion(reblog)) = &boost.repost_from else {
+            panic!()
+        };
+        assert_eq!(reblog.user.nickname, "woof");
+        assert_eq!(reblog.content, PostContent::plain("Woof"));
+
+        let post = &posts[2];
+        assert_eq!(post.user.nickname, "Meow");
+        assert_eq!(
+            post.content,
+            PostContent::plain("⚠️ cat")
+                .with_plain("\n\n")
+                .with_plain("Hello ")
+                .with_link("@woof", "https://other.social/@woof")
+        );
+        assert_eq!(
+            post.attachments(false).cloned().collect::<Vec<_>>(),
+            [
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: "https://example.social/cat.png".into(),
+                    has_spoiler: true
+                }),
+                PostAttachment::Video(PostAttachmentVideo {
+                    media_url: "https://example.social/cat.mp4".into(),
+                    has_spoiler: true
+                })
+            ]
+        );
+    }
+}
@@ -1,5 +1,10 @@
 pub mod bilibili;
+pub mod bluesky;
+pub mod mastodon;
+pub mod rss;
+pub mod twitch;
 pub mod twitter;
+pub mod youtube;
 
 use std::fmt;
 
@@ -21,6 +26,18 @@ pub enum Config {
     BilibiliPlayback(config::Accessor<bilibili::playback::ConfigParams>),
     #[serde(rename = "Twitter")]
     Twitter(config::Accessor<twitter::ConfigParams>),
+    #[serde(rename = "RSS")]
+    Rss(config::Accessor<rss::ConfigParams>),
+    #[serde(rename = "YouTube.live")]
+    YouTubeLive(config::Accessor<youtube::live::ConfigParams>),
+    #[serde(rename = "YouTube.video")]
+    YouTubeVideo(config::Accessor<youtube::video::ConfigParams>),
+    #[serde(rename = "Twitch")]
+    Twitch(config::Accessor<twitch::ConfigParams>),
+    #[serde(rename = "Mastodon")]
+    Mastodon(config::Accessor<mastodon::ConfigParams>),
+    #[serde(rename = "Bluesky")]
+    Bluesky(config::Accessor<bluesky::ConfigParams>),
 }
 
 impl config::Validator for Config {
@@ -31,6 +48,12 @@ impl config::Validator for Config {
             Self::BilibiliVideo(p) => p.validate(),
             Self::BilibiliPlayback(p) => p.validate(),
             Self::Twitter(p) => p.validate(),
+            Self::Rss(p) => p.validate(),
+            Self::YouTubeLive(p) => p.validate(),
+            Self::YouTubeVideo(p) => p.validate(),
+            Self::Twitch(p) => p.validate(),
+            Self::Mastodon(p) => p.validate(),
+            Self::Bluesky(p) => p.validate(),
         }
     }
 }
@@ -43,6 +66,12 @@ impl fmt::Display for Config {
             Self::BilibiliVideo(p) => write!(f, "{p}"),
             Self::BilibiliPlayback(p) => write!(f, "{p}"),
             Self::Twitter(p) => write!(f, "{p}"),
+            Self::Rss(p) => write!(f, "{p}"),
+            Self::YouTubeLive(p) => write!(f, "{p}"),
+            Self::YouTubeVideo(p) => write!(f, "{p}"),
+            Self::Twitch(p) => write!(f, "{p}"),
+            Self::Mastodon(p) => write!(f, "{p}"),
+            Self::Bluesky(p) => write!(f, "{p}"),
         }
     }
 }
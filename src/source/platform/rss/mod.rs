@@ -0,0 +1,326 @@
+use std::{fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, bail, ensure};
+use chrono::{DateTime, Local};
+use feed_rs::model::{Entry, Feed, MediaObject, Text};
+use reqwest::{
+    header::{self, HeaderValue},
+    StatusCode, Url,
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    config, helper,
+    platform::{PlatformMetadata, PlatformTrait},
+    source::{
+        html, FetcherTrait, Post, PostAttachment, PostAttachmentImage, PostAttachmentVideo,
+        PostContent, PostUrl, Posts, Status, StatusKind, StatusSource, StatusSourceUser, User,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    pub url: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        Url::parse(&self.url).map_err(|err| anyhow!("invalid feed url '{}': {err}", self.url))?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RSS:{}", self.url)
+    }
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+    cache: Mutex<Option<Cache>>,
+}
+
+// The last fetched feed, reused if the server responds `304 Not Modified`
+struct Cache {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    status: Status,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "RSS",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            cache: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let mut cache = self.cache.lock().await;
+
+        let mut req = helper::reqwest_client()?.get(&self.params.url);
+        if let Some(cache) = &*cache {
+            if let Some(etag) = &cache.etag {
+                req = req.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cache.last_modified {
+                req = req.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return match &*cache {
+                Some(cache) => Ok(cache.status.clone()),
+                None => bail!("response is not modified, but nothing was cached"),
+            };
+        }
+        ensure!(
+            status.is_success(),
+            "response status is not success: {resp:?}"
+        );
+
+        let etag = resp.headers().get(header::ETAG).cloned();
+        let last_modified = resp.headers().get(header::LAST_MODIFIED).cloned();
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|err| anyhow!("failed to obtain body from response: {err}"))?;
+        let feed = feed_rs::parser::parse(&body[..])
+            .map_err(|err| anyhow!("failed to parse feed: {err}"))?;
+
+        let (user, posts) = parse_feed(feed, &self.params.url);
+        let status = Status::new(
+            StatusKind::Posts(posts),
+            StatusSource {
+                platform: self.metadata(),
+                user: Some(StatusSourceUser {
+                    display_name: user.nickname,
+                    profile_url: user.profile_url,
+                }),
+            },
+        );
+        *cache = Some(Cache {
+            etag,
+            last_modified,
+            status: status.clone(),
+        });
+        Ok(status)
+    }
+}
+
+fn parse_feed(feed: Feed, url: &str) -> (User, Posts) {
+    let user = User {
+        nickname: feed
+            .title
+            .as_ref()
+            .map(text_to_plain)
+            .unwrap_or_else(|| url.into()),
+        profile_url: alternate_link(&feed.links).unwrap_or(url).into(),
+        avatar_url: feed
+            .icon
+            .as_ref()
+            .or(feed.logo.as_ref())
+            .map(|image| image.uri.clone()),
+    };
+
+    let posts = feed
+        .entries
+        .into_iter()
+        .map(|entry| parse_entry(entry, &user))
+        .collect();
+    (user, Posts(posts))
+}
+
+fn parse_entry(entry: Entry, user: &User) -> Post {
+    let title = entry.title.as_ref().map(text_to_plain);
+    let summary = entry.summary.as_ref().map(text_to_content);
+    let content = match (title, summary) {
+        (Some(title), Some(summary)) if !summary.is_empty() => PostContent::plain(title)
+            .with_plain("\n\n")
+            .with_content(summary),
+        (Some(title), _) => PostContent::plain(title),
+        (None, Some(summary)) => summary,
+        (None, None) => PostContent::from_parts([]),
+    };
+
+    let urls = match alternate_link(&entry.links) {
+        Some(link) => PostUrl::new_clickable_with_id(link, "View Post", &entry.id),
+        None => PostUrl::Identity(entry.id.clone()),
+    };
+
+    Post {
+        user: user.clone(),
+        content,
+        urls: urls.into(),
+        // Entries without time are never considered deleted, nor used as the cut-off
+        // of deletions (see `missing_posts`)
+        time: entry
+            .published
+            .or(entry.updated)
+            .map(Into::into)
+            .unwrap_or_else(|| DateTime::<Local>::from(DateTime::UNIX_EPOCH)),
+        is_pinned: false,
+        repost_from: None,
+        attachments: entry.media.iter().flat_map(parse_media).collect(),
+    }
+}
+
+// Both enclosures and `media:content` are parsed into media objects
+fn parse_media(media: &MediaObject) -> Vec<PostAttachment> {
+    let attachments = media
+        .content
+        .iter()
+        .filter_map(|content| {
+            let url = content.url.as_ref()?.to_string();
+            let content_type = content.content_type.as_ref()?.to_string();
+            if content_type.starts_with("image/") {
+                Some(PostAttachment::Image(PostAttachmentImage {
+                    media_url: url,
+                    has_spoiler: false,
+                }))
+            } else if content_type.starts_with("video/") {
+                Some(PostAttachment::Video(PostAttachmentVideo {
+                    media_url: url,
+                    has_spoiler: false,
+                }))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if attachments.is_empty() {
+        media
+            .thumbnails
+            .iter()
+            .take(1)
+            .map(|thumbnail| {
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: thumbnail.image.uri.clone(),
+                    has_spoiler: false,
+                })
+            })
+            .collect()
+    } else {
+        attachments
+    }
+}
+
+fn alternate_link(links: &[feed_rs::model::Link]) -> Option<&str> {
+    links
+        .iter()
+        .find(|link| link.rel.as_deref().is_none_or(|rel| rel == "alternate"))
+        .map(|link| link.href.as_str())
+}
+
+fn is_html(text: &Text) -> bool {
+    text.content_type.to_string().contains("html")
+}
+
+fn text_to_content(text: &Text) -> PostContent {
+    if is_html(text) {
+        html::to_post_content(&text.content)
+    } else {
+        PostContent::plain(text.content.trim())
+    }
+}
+
+fn text_to_plain(text: &Text) -> String {
+    text_to_content(text).fallback()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rss() {
+        let feed = feed_rs::parser::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Meow Blog</title>
+    <link>https://example.com/</link>
+    <item>
+      <title>Hello</title>
+      <link>https://example.com/hello?utm=rss</link>
+      <guid isPermaLink="false">post-1</guid>
+      <description><![CDATA[<p>See <a href="https://example.com/more">more</a></p>]]></description>
+      <pubDate>Mon, 06 Jan 2025 12:00:00 GMT</pubDate>
+      <enclosure url="https://example.com/cat.jpg" length="1" type="image/jpeg"/>
+      <media:content url="https://example.com/cat.mp4" type="video/mp4"/>
+    </item>
+    <item>
+      <title>No link</title>
+      <guid isPermaLink="false">post-2</guid>
+    </item>
+  </channel>
+</rss>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let (_, posts) = parse_feed(feed, "https://example.com/feed.xml");
+
+        assert_eq!(posts.0.len(), 2);
+        let post = &posts.0[0];
+        assert_eq!(post.user.nickname, "Meow Blog");
+        assert_eq!(post.user.profile_url, "https://example.com/");
+        assert_eq!(post.urls.major().unique_id(), "post-1");
+        assert_eq!(
+            post.urls.major().as_clickable().unwrap().url,
+            "https://example.com/hello?utm=rss"
+        );
+        assert_eq!(
+            post.content,
+            PostContent::plain("Hello")
+                .with_plain("\n\n")
+                .with_plain("See ")
+                .with_link("more", "https://example.com/more")
+        );
+        assert_eq!(
+            post.attachments(false).cloned().collect::<Vec<_>>(),
+            [
+                PostAttachment::Image(PostAttachmentImage {
+                    media_url: "https://example.com/cat.jpg".into(),
+                    has_spoiler: false
+                }),
+                PostAttachment::Video(PostAttachmentVideo {
+                    media_url: "https://example.com/cat.mp4".into(),
+                    has_spoiler: false
+                })
+            ]
+        );
+
+        let post = &posts.0[1];
+        assert_eq!(post.urls.major().unique_id(), "post-2");
+        assert!(post.urls.major().as_clickable().is_none());
+        assert_eq!(post.content, PostContent::plain("No link"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, ensure};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    config::{self, AsSecretRef},
+    helper,
+    platform::{PlatformMetadata, PlatformTrait},
+    secret_enum,
+    source::{
+        batcher::Batcher, FetcherTrait, LiveArea, LiveStatus, LiveStatusKind, Status, StatusKind,
+        StatusSource, StatusSourceUser,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigGlobal {
+    pub client_id: String,
+    #[serde(flatten)]
+    pub client_secret: ConfigClientSecret,
+}
+
+impl config::Validator for ConfigGlobal {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.client_id.is_empty(), "client_id is empty");
+        self.client_secret.validate()?;
+        Ok(())
+    }
+}
+
+secret_enum! {
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ConfigClientSecret {
+        ClientSecret(String),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    pub login: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.login.is_empty()
+                && self.login.len() <= 25
+                && self
+                    .login
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "invalid Twitch login '{}', it should be the username in the channel URL",
+            self.login
+        );
+        match &*config::Config::global().platform().twitch {
+            Some(_) => Ok(()),
+            None => bail!("client credentials in global are missing"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Twitch:{}", self.login)
+    }
+}
+
+const TWITCH_TOKEN_API: &str = "https://id.twitch.tv/oauth2/token";
+const TWITCH_HELIX_API: &str = "https://api.twitch.tv/helix";
+
+// Helix accepts at most 100 logins in a single request
+const HELIX_MAX_LOGINS: usize = 100;
+
+mod data {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    pub struct AppAccessToken {
+        pub access_token: String,
+        pub expires_in: u64, // Seconds
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Helix<T> {
+        pub data: Vec<T>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct User {
+        pub login: String,
+        pub display_name: String,
+        pub offline_image_url: String, // Empty for no image
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Stream {
+        pub user_login: String,
+        #[serde(rename = "type")]
+        pub kind: String, // "live", or empty on error
+        pub title: String,
+        pub game_name: String, // Empty for no category
+        pub started_at: DateTime<Utc>,
+        pub thumbnail_url: String, // Contains `{width}` and `{height}` placeholders
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Channel {
+    user: data::User,
+    stream: Option<data::Stream>,
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+    // The last seen status, its title, category and cover are kept after the
+    // stream ends, as they are not available from Helix `streams` while offline
+    status_cache: Mutex<Option<LiveStatus>>,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "Twitch",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Drop for Fetcher {
+    fn drop(&mut self) {
+        BATCHER.unregister(&self.params.login.to_ascii_lowercase());
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        // Logins are case-insensitive
+        BATCHER.register(params.login.to_ascii_lowercase());
+        Self {
+            params,
+            status_cache: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let channel = BATCHER
+            .fetch(&self.params.login.to_ascii_lowercase(), fetch_channels)
+            .await?;
+
+        let mut cache = self.status_cache.lock().await;
+        let live_status = match channel {
+            Some(channel) => channel_into_live_status(channel, cache.as_ref()),
+            // Suspended or deleted users are absent from the response
+            None => match &*cache {
+                Some(last) => LiveStatus {
+                    kind: LiveStatusKind::Banned,
+                    ..last.clone()
+                },
+                None => return Ok(Status::empty()),
+            },
+        };
+        *cache = Some(live_status.clone());
+
+        let source = StatusSource {
+            platform: self.metadata(),
+            user: Some(StatusSourceUser {
+                display_name: live_status.streamer_name.clone(),
+                profile_url: live_status.live_url.clone(),
+            }),
+        };
+        Ok(Status::new(StatusKind::Live(live_status), source))
+    }
+}
+
+fn channel_into_live_status(channel: Channel, last: Option<&LiveStatus>) -> LiveStatus {
+    let Channel { user, stream } = channel;
+    let live_url = format!("https://www.twitch.tv/{}", user.login);
+
+    match stream.filter(|stream| stream.kind == "live") {
+        Some(stream) => LiveStatus {
+            kind: LiveStatusKind::Online {
+                start_time: Some(stream.started_at.into()),
+            },
+            title: stream.title,
+            area: (!stream.game_name.is_empty()).then(|| LiveArea {
+                parent: String::new(),
+                child: stream.game_name,
+            }),
+            streamer_name: user.display_name,
+            cover_image_url: stream
+                .thumbnail_url
+                .replace("{width}", "1280")
+                .replace("{height}", "720"),
+            live_url,
+        },
+        None => LiveStatus {
+            kind: LiveStatusKind::Offline,
+            // Unknown if never seen online, not treated as a title change
+            title: last.map(|last| last.title.clone()).unwrap_or_default(),
+            area: last.and_then(|last| last.area.clone()),
+            streamer_name: user.display_name,
+            cover_image_url: if !user.offline_image_url.is_empty() {
+                user.offline_image_url
+            } else {
+                last.map(|last| last.cover_image_url.clone())
+                    .unwrap_or_default()
+            },
+            live_url,
+        },
+    }
+}
+
+// Helix accepts multiple logins
+static BATCHER: Lazy<Batcher<String, Channel>> = Lazy::new(|| Batcher::new("Twitch"));
+
+// Shared by all fetchers, as it's obtained with the global client credentials
+static APP_TOKEN: Lazy<Mutex<Option<AppToken>>> = Lazy::new(|| Mutex::new(None));
+
+struct AppToken {
+    client_id: String,
+    access_token: String,
+    expires_at: Instant,
+}
+
+async fn fetch_channels(logins: Vec<String>) -> anyhow::Result<HashMap<String, Channel>> {
+    let logins = logins.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut users = Vec::new();
+    let mut streams = Vec::new();
+    for chunk in logins.chunks(HELIX_MAX_LOGINS) {
+        let query = chunk
+            .iter()
+            .map(|login| ("login", *login))
+            .collect::<Vec<_>>();
+        users.extend(request_helix::<data::User>("users", &query).await?);
+
+        let query = chunk
+            .iter()
+            .map(|login| ("user_login", *login))
+            .chain([("first", "100")])
+            .collect::<Vec<_>>();
+        streams.extend(request_helix::<data::Stream>("streams", &query).await?);
+    }
+    Ok(join_channels(users, streams))
+}
+
+async fn request_helix<T: DeserializeOwned>(
+    endpoint: &str,
+    query: &[(&str, &str)],
+) -> anyhow::Result<Vec<T>> {
+    let mut token = APP_TOKEN.lock().await;
+    // Retry once if the token is revoked before its expiration
+    for retry in [false, true] {
+        let (client_id, access_token) = app_token(&mut token).await?;
+        let resp = helper::reqwest_client()?
+            .get(format!("{TWITCH_HELIX_API}/{endpoint}"))
+            .query(query)
+            .header("Client-Id", client_id)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+        let status = resp.status();
+        if status == StatusCode::UNAUTHORIZED && !retry {
+            *token = None;
+            continue;
+        }
+        ensure!(
+            status.is_success(),
+            "response status is not success: {resp:?}"
+        );
+
+        let text = resp
+            .text()
+            .await
+            .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+        let resp: data::Helix<T> = serde_json::from_str(&text)
+            .map_err(|err| anyhow!("failed to deserialize response: {err}, text: {text}"))?;
+        return Ok(resp.data);
+    }
+    unreachable!()
+}
+
+// The app access token is requested with the client credentials grant flow,
+// and cached until it expires or the credentials are changed
+async fn app_token(token: &mut Option<AppToken>) -> anyhow::Result<(String, String)> {
+    let global = (*config::Config::global().platform().twitch).clone();
+    let Some(global) = global else {
+        bail!("client credentials in global are missing");
+    };
+
+    let valid = token.as_ref().is_some_and(|token| {
+        token.client_id == global.client_id && token.expires_at > Instant::now()
+    });
+    if !valid {
+        let client_secret = global.client_secret.as_secret_ref().get_str()?;
+        let resp = helper::reqwest_client()?
+            .post(TWITCH_TOKEN_API)
+            .form(&[
+                ("client_id", global.client_id.as_str()),
+                ("client_secret", &client_secret),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await
+            .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+        let status = resp.status();
+        ensure!(
+            status.is_success(),
+            "failed to obtain app access token, response status is not success: {resp:?}"
+        );
+        let resp: data::AppAccessToken = resp
+            .json()
+            .await
+            .map_err(|err| anyhow!("failed to deserialize app access token: {err}"))?;
+
+        // Refresh a bit earlier, avoid expiring during requests
+        let expires_in =
+            Duration::from_secs(resp.expires_in).saturating_sub(Duration::from_secs(60));
+        *token = Some(AppToken {
+            client_id: global.client_id.clone(),
+            access_token: resp.access_token,
+            expires_at: Instant::now() + expires_in,
+        });
+    }
+
+    let token = token.as_ref().unwrap();
+    Ok((token.client_id.clone(), token.access_token.clone()))
+}
+
+fn join_channels(users: Vec<data::User>, streams: Vec<data::Stream>) -> HashMap<String, Channel> {
+    let mut streams = streams
+        .into_iter()
+        .map(|stream| (stream.user_login.to_ascii_lowercase(), stream))
+        .collect::<HashMap<_, _>>();
+    users
+        .into_iter()
+        .map(|user| {
+            let login = user.login.to_ascii_lowercase();
+            let stream = streams.remove(&login);
+            (login, Channel { user, stream })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let users: data::Helix<data::User> = serde_json::from_str(
+            r#"{"data":[{"id":"141981764","login":"twitchdev","display_name":"TwitchDev","type":"","broadcaster_type":"partner","description":"Supporting third-party developers building Twitch integrations from chatbots to game integrations.","profile_image_url":"https://static-cdn.jtvnw.net/jtv_user_pictures/8a6381c7-d0c0-4576-b179-38bd5ce1d6af-profile_image-300x300.png","offline_image_url":"https://static-cdn.jtvnw.net/jtv_user_pictures/3f13ab61-ec78-4fe6-8481-8682cb3b0ac2-channel_offline_image-1920x1080.png","view_count":5980557,"created_at":"2016-12-14T20:32:28Z"},{"id":"12826","login":"meow","display_name":"Meow","type":"","broadcaster_type":"","description":"","profile_image_url":"","offline_image_url":"","view_count":0,"created_at":"2007-05-22T10:39:54Z"}]}"#,
+        )
+        .unwrap();
+        let streams: data::Helix<data::Stream> = serde_json::from_str(
+            r#"{"data":[{"id":"40952121085","user_id":"141981764","user_login":"twitchdev","user_name":"TwitchDev","game_id":"509658","game_name":"Just Chatting","type":"live","title":"Building extensions","tags":["English"],"viewer_count":78365,"started_at":"2021-03-10T15:04:21Z","language":"en","thumbnail_url":"https://static-cdn.jtvnw.net/previews-ttv/live_user_twitchdev-{width}x{height}.jpg","tag_ids":[],"is_mature":false}],"pagination":{}}"#,
+        )
+        .unwrap();
+        let channels = join_channels(users.data, streams.data);
+        assert_eq!(channels.len(), 2);
+
+        let live_status = channel_into_live_status(channels["twitchdev"].clone(), None);
+        assert_eq!(
+            live_status,
+            LiveStatus {
+                kind: LiveStatusKind::Online {
+                    start_time: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1615388661))
+                },
+                title: "Building extensions".into(),
+                area: Some(LiveArea {
+                    parent: "".into(),
+                    child: "Just Chatting".into()
+                }),
+                streamer_name: "TwitchDev".into(),
+                cover_image_url:
+                    "https://static-cdn.jtvnw.net/previews-ttv/live_user_twitchdev-1280x720.jpg"
+                        .into(),
+                live_url: "https://www.twitch.tv/twitchdev".into(),
+            }
+        );
+        assert_eq!(
+            live_status.area.as_ref().unwrap().to_string(),
+            "Just Chatting"
+        );
+
+        // Details of the last stream are kept while offline
+        let offline = channel_into_live_status(
+            Channel {
+                user: channels["twitchdev"].user.clone(),
+                stream: None,
+            },
+            Some(&live_status),
+        );
+        assert_eq!(offline.kind, LiveStatusKind::Offline);
+        assert_eq!(offline.title, "Building extensions");
+        assert_eq!(offline.area, live_status.area);
+        assert!(offline.cover_image_url.contains("channel_offline_image"));
+
+        let offline = channel_into_live_status(channels["meow"].clone(), None);
+        assert_eq!(offline.kind, LiveStatusKind::Offline);
+        assert_eq!(offline.title, "");
+        assert_eq!(offline.live_url, "https://www.twitch.tv/meow");
+    }
+}
@@ -375,7 +375,8 @@ impl fmt::Display for Fetcher {
 
 impl Fetcher {
     pub fn new(params: config::Accessor<ConfigParams>) -> Self {
-        let cookies = Config::global()
+        let config = Config::global();
+        let cookies = config
             .platform()
             .twitter
             .as_ref()
@@ -495,6 +496,7 @@ fn parse_tweet(tweet: data::Tweet) -> anyhow::Result<Post> {
             tweet.core.user_results.result.legacy.screen_name, tweet.rest_id
         ),
         display: "View Tweet".into(),
+        id: None,
     }));
 
     let repost_from = if !tweet.legacy.is_quote_status {
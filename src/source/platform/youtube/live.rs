@@ -0,0 +1,305 @@
+use std::{fmt, future::Future, pin::Pin, time::SystemTime};
+
+use anyhow::{anyhow, ensure};
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use serde_json as json;
+use tokio::sync::Mutex;
+
+use super::*;
+use crate::{
+    config,
+    platform::{PlatformMetadata, PlatformTrait},
+    source::{
+        FetcherTrait, LiveStatus, LiveStatusKind, Status, StatusKind, StatusSource,
+        StatusSourceUser,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    pub channel_id: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        validate_channel_id(&self.channel_id)
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "YouTube.live:{}", self.channel_id)
+    }
+}
+
+mod data {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PlayerResponse {
+        pub video_details: Option<VideoDetails>,
+        pub microformat: Option<Microformat>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct VideoDetails {
+        pub video_id: String,
+        pub channel_id: String,
+        pub title: String,
+        pub author: String,
+        #[serde(default)]
+        pub is_live_content: bool,
+        #[serde(default)]
+        pub is_upcoming: bool,
+        pub thumbnail: Thumbnails,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Thumbnails {
+        pub thumbnails: Vec<Thumbnail>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    pub struct Thumbnail {
+        pub url: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Microformat {
+        pub player_microformat_renderer: PlayerMicroformatRenderer,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PlayerMicroformatRenderer {
+        pub live_broadcast_details: Option<LiveBroadcastDetails>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct LiveBroadcastDetails {
+        pub is_live_now: bool,
+        pub start_timestamp: Option<DateTime<FixedOffset>>,
+        pub end_timestamp: Option<DateTime<FixedOffset>>,
+    }
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+    // The last seen live or upcoming stream, its details are kept after it ends
+    stream_cache: Mutex<Option<LiveStatus>>,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "YouTube Live",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self {
+            params,
+            stream_cache: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let channel_id = &self.params.channel_id;
+        let player = fetch_live_player(channel_id).await?;
+
+        let mut cache = self.stream_cache.lock().await;
+        let live_status = match parse_stream(channel_id, player) {
+            Some(live_status) => {
+                *cache = Some(live_status.clone());
+                live_status
+            }
+            None => match &*cache {
+                Some(last) => LiveStatus {
+                    kind: LiveStatusKind::Offline,
+                    ..last.clone()
+                },
+                // Never seen a stream, the channel name is only available in the feed
+                None => {
+                    let feed = fetch_feed(channel_id).await?;
+                    LiveStatus {
+                        kind: LiveStatusKind::Offline,
+                        title: String::new(), // Unknown, not treated as a title change
+                        area: None,
+                        streamer_name: feed
+                            .authors
+                            .into_iter()
+                            .next()
+                            .map(|author| author.name)
+                            .unwrap_or_else(|| channel_id.clone()),
+                        cover_image_url: String::new(),
+                        live_url: format!("https://www.youtube.com/channel/{channel_id}/live"),
+                    }
+                }
+            },
+        };
+
+        let source = StatusSource {
+            platform: self.metadata(),
+            user: Some(StatusSourceUser {
+                display_name: live_status.streamer_name.clone(),
+                profile_url: format!("https://www.youtube.com/channel/{channel_id}"),
+            }),
+        };
+        Ok(Status::new(StatusKind::Live(live_status), source))
+    }
+}
+
+// The `/live` page of a channel redirects to the current live stream, or the
+// nearest upcoming one, otherwise it's the channel home page
+async fn fetch_live_player(channel_id: &str) -> anyhow::Result<data::PlayerResponse> {
+    let resp = youtube_request_builder()?
+        .get(format!("https://www.youtube.com/channel/{channel_id}/live"))
+        .send()
+        .await
+        .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+    let status = resp.status();
+    ensure!(
+        status.is_success(),
+        "response status is not success: {resp:?}"
+    );
+
+    let html = resp
+        .text()
+        .await
+        .map_err(|err| anyhow!("failed to obtain text from response: {err}"))?;
+    extract_player_response(&html)
+}
+
+fn extract_player_response(html: &str) -> anyhow::Result<data::PlayerResponse> {
+    const MARKER: &str = "ytInitialPlayerResponse = ";
+
+    let Some(start) = html.find(MARKER) else {
+        // Channel home pages have no player
+        return Ok(data::PlayerResponse {
+            video_details: None,
+            microformat: None,
+        });
+    };
+    // The JSON is followed by other scripts, only deserialize the first value
+    json::Deserializer::from_str(&html[start + MARKER.len()..])
+        .into_iter::<data::PlayerResponse>()
+        .next()
+        .ok_or_else(|| anyhow!("player response is empty"))?
+        .map_err(|err| anyhow!("failed to deserialize player response: {err}"))
+}
+
+fn parse_stream(channel_id: &str, player: data::PlayerResponse) -> Option<LiveStatus> {
+    let details = player.video_details?;
+    let broadcast = player
+        .microformat?
+        .player_microformat_renderer
+        .live_broadcast_details?;
+    if details.channel_id != channel_id
+        || !details.is_live_content
+        || broadcast.end_timestamp.is_some()
+        || !(broadcast.is_live_now || details.is_upcoming)
+    {
+        return None;
+    }
+
+    Some(LiveStatus {
+        kind: if broadcast.is_live_now {
+            LiveStatusKind::Online {
+                start_time: broadcast.start_timestamp.map(SystemTime::from),
+            }
+        } else {
+            LiveStatusKind::Offline
+        },
+        title: details.title,
+        area: None,
+        streamer_name: details.author,
+        cover_image_url: details
+            .thumbnail
+            .thumbnails
+            .into_iter()
+            .next_back()
+            .map(|thumbnail| thumbnail.url)
+            .unwrap_or_else(|| {
+                format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", details.video_id)
+            }),
+        live_url: format!("https://www.youtube.com/watch?v={}", details.video_id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL_ID: &str = "UC1opHUrw8rvnsadT-iGp7Cg";
+
+    fn page(is_live_now: bool, is_upcoming: bool) -> String {
+        format!(
+            r#"<script>var ytInitialPlayerResponse = {{"videoDetails":{{"videoId":"abc","channelId":"{CHANNEL_ID}","title":"Singing","author":"Meow Ch.","isLiveContent":true,"isUpcoming":{is_upcoming},"thumbnail":{{"thumbnails":[{{"url":"https://i.ytimg.com/vi/abc/default.jpg"}},{{"url":"https://i.ytimg.com/vi/abc/maxresdefault_live.jpg"}}]}}}},"microformat":{{"playerMicroformatRenderer":{{"liveBroadcastDetails":{{"isLiveNow":{is_live_now},"startTimestamp":"2025-01-06T12:00:00+00:00"}}}}}}}};var meta = {{}};</script>"#
+        )
+    }
+
+    #[test]
+    fn parse() {
+        let live = parse_stream(
+            CHANNEL_ID,
+            extract_player_response(&page(true, false)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            live.kind,
+            LiveStatusKind::Online {
+                start_time: Some(
+                    DateTime::parse_from_rfc3339("2025-01-06T12:00:00+00:00")
+                        .unwrap()
+                        .into()
+                )
+            }
+        );
+        assert_eq!(live.title, "Singing");
+        assert_eq!(live.streamer_name, "Meow Ch.");
+        assert_eq!(
+            live.cover_image_url,
+            "https://i.ytimg.com/vi/abc/maxresdefault_live.jpg"
+        );
+        assert_eq!(live.live_url, "https://www.youtube.com/watch?v=abc");
+
+        let upcoming = parse_stream(
+            CHANNEL_ID,
+            extract_player_response(&page(false, true)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(upcoming.kind, LiveStatusKind::Offline);
+        assert_eq!(upcoming.title, "Singing");
+
+        assert!(parse_stream(
+            "UCxxxxxxxxxxxxxxxxxxxxxx",
+            extract_player_response(&page(true, false)).unwrap()
+        )
+        .is_none());
+        assert!(parse_stream(
+            CHANNEL_ID,
+            extract_player_response("<html>channel home</html>").unwrap()
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,60 @@
+pub mod live;
+pub mod video;
+
+use anyhow::{anyhow, ensure};
+use feed_rs::model::Feed;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+
+use crate::helper;
+
+fn validate_channel_id(channel_id: &str) -> anyhow::Result<()> {
+    ensure!(
+        channel_id.len() == 24 && channel_id.starts_with("UC"),
+        "invalid YouTube channel ID '{channel_id}', it should look like 'UCxxxxxxxxxxxxxxxxxxxxxx'"
+    );
+    Ok(())
+}
+
+fn youtube_request_builder() -> anyhow::Result<reqwest::Client> {
+    helper::reqwest_client_with(|builder| {
+        builder.default_headers(HeaderMap::from_iter([
+            (header::ACCEPT_LANGUAGE, HeaderValue::from_static("en")),
+            // Skip the consent page for requests from the EU
+            (header::COOKIE, HeaderValue::from_static("CONSENT=YES+")),
+        ]))
+    })
+}
+
+// The public per-channel feed of the latest 15 uploads, no API key is needed
+async fn fetch_feed(channel_id: &str) -> anyhow::Result<Feed> {
+    let resp = youtube_request_builder()?
+        .get("https://www.youtube.com/feeds/videos.xml")
+        .query(&[("channel_id", channel_id)])
+        .send()
+        .await
+        .map_err(|err| anyhow!("failed to send request: {err}"))?;
+
+    let status = resp.status();
+    ensure!(
+        status.is_success(),
+        "response status is not success: {resp:?}"
+    );
+
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|err| anyhow!("failed to obtain body from response: {err}"))?;
+    feed_rs::parser::parse(&body[..]).map_err(|err| anyhow!("failed to parse feed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_id() {
+        assert!(validate_channel_id("UC1opHUrw8rvnsadT-iGp7Cg").is_ok());
+        assert!(validate_channel_id("@meow").is_err());
+        assert!(validate_channel_id("UC1opHUrw8rvnsadT").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::{fmt, future::Future, pin::Pin};
+
+use anyhow::anyhow;
+use feed_rs::model::Feed;
+use serde::Deserialize;
+
+use super::*;
+use crate::{
+    config,
+    platform::{PlatformMetadata, PlatformTrait},
+    source::{
+        FetcherTrait, Post, PostAttachment, PostAttachmentImage, PostContent, PostUrl, Posts,
+        Status, StatusKind, StatusSource, StatusSourceUser, User,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConfigParams {
+    pub channel_id: String,
+}
+
+impl config::Validator for ConfigParams {
+    fn validate(&self) -> anyhow::Result<()> {
+        validate_channel_id(&self.channel_id)
+    }
+}
+
+impl fmt::Display for ConfigParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "YouTube.video:{}", self.channel_id)
+    }
+}
+
+pub struct Fetcher {
+    params: config::Accessor<ConfigParams>,
+}
+
+impl PlatformTrait for Fetcher {
+    fn metadata(&self) -> PlatformMetadata {
+        PlatformMetadata {
+            display_name: "YouTube",
+        }
+    }
+}
+
+impl FetcherTrait for Fetcher {
+    fn fetch_status(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<Status>> + Send + '_>> {
+        Box::pin(self.fetch_status_impl())
+    }
+}
+
+impl fmt::Display for Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.params)
+    }
+}
+
+impl Fetcher {
+    pub fn new(params: config::Accessor<ConfigParams>) -> Self {
+        Self { params }
+    }
+
+    async fn fetch_status_impl(&self) -> anyhow::Result<Status> {
+        let feed = fetch_feed(&self.params.channel_id).await?;
+        let (user, posts) = parse_feed(&self.params.channel_id, feed)?;
+
+        Ok(Status::new(
+            StatusKind::Posts(posts),
+            StatusSource {
+                platform: self.metadata(),
+                user: Some(StatusSourceUser {
+                    display_name: user.nickname,
+                    profile_url: user.profile_url,
+                }),
+            },
+        ))
+    }
+}
+
+fn parse_feed(channel_id: &str, feed: Feed) -> anyhow::Result<(User, Posts)> {
+    let author = feed.authors.into_iter().next();
+    let user = User {
+        nickname: author
+            .as_ref()
+            .map(|author| author.name.clone())
+            .unwrap_or_else(|| channel_id.into()),
+        profile_url: author
+            .and_then(|author| author.uri)
+            .unwrap_or_else(|| format!("https://www.youtube.com/channel/{channel_id}")),
+        avatar_url: None,
+    };
+
+    let videos = feed
+        .entries
+        .into_iter()
+        .map(|entry| -> anyhow::Result<Post> {
+            let url = entry
+                .links
+                .first()
+                .map(|link| link.href.clone())
+                .ok_or_else(|| anyhow!("video without link, id={}", entry.id))?;
+            let time = entry
+                .published
+                .or(entry.updated)
+                .ok_or_else(|| anyhow!("video without time, id={}", entry.id))?
+                .into();
+            Ok(Post {
+                user: user.clone(),
+                content: PostContent::plain(
+                    entry.title.map(|title| title.content).unwrap_or_default(),
+                ),
+                urls: PostUrl::new_clickable(url, "View Video").into(),
+                time,
+                is_pinned: false,
+                repost_from: None,
+                attachments: entry
+                    .media
+                    .iter()
+                    .flat_map(|media| media.thumbnails.first())
+                    .map(|thumbnail| {
+                        PostAttachment::Image(PostAttachmentImage {
+                            media_url: thumbnail.image.uri.clone(),
+                            has_spoiler: false,
+                        })
+                    })
+                    .collect(),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((user, Posts(videos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let feed = feed_rs::parser::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns:media="http://search.yahoo.com/mrss/" xmlns="http://www.w3.org/2005/Atom">
+ <link rel="self" href="http://www.youtube.com/feeds/videos.xml?channel_id=UC1opHUrw8rvnsadT-iGp7Cg"/>
+ <id>yt:channel:1opHUrw8rvnsadT-iGp7Cg</id>
+ <yt:channelId>1opHUrw8rvnsadT-iGp7Cg</yt:channelId>
+ <title>Meow Ch.</title>
+ <link rel="alternate" href="https://www.youtube.com/channel/UC1opHUrw8rvnsadT-iGp7Cg"/>
+ <author>
+  <name>Meow Ch.</name>
+  <uri>https://www.youtube.com/channel/UC1opHUrw8rvnsadT-iGp7Cg</uri>
+ </author>
+ <published>2018-08-01T08:00:00+00:00</published>
+ <entry>
+  <id>yt:video:dQw4w9WgXcQ</id>
+  <yt:videoId>dQw4w9WgXcQ</yt:videoId>
+  <yt:channelId>UC1opHUrw8rvnsadT-iGp7Cg</yt:channelId>
+  <title>Hello YouTube</title>
+  <link rel="alternate" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+  <author>
+   <name>Meow Ch.</name>
+   <uri>https://www.youtube.com/channel/UC1opHUrw8rvnsadT-iGp7Cg</uri>
+  </author>
+  <published>2025-01-06T12:00:00+00:00</published>
+  <updated>2025-01-07T12:00:00+00:00</updated>
+  <media:group>
+   <media:title>Hello YouTube</media:title>
+   <media:content url="https://www.youtube.com/v/dQw4w9WgXcQ?version=3" type="application/x-shockwave-flash" width="640" height="390"/>
+   <media:thumbnail url="https://i1.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg" width="480" height="360"/>
+   <media:description>meow</media:description>
+  </media:group>
+ </entry>
+</feed>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let (user, posts) = parse_feed("UC1opHUrw8rvnsadT-iGp7Cg", feed).unwrap();
+
+        assert_eq!(user.nickname, "Meow Ch.");
+        assert_eq!(posts.0.len(), 1);
+        let post = &posts.0[0];
+        assert_eq!(post.content, PostContent::plain("Hello YouTube"));
+        assert_eq!(
+            post.urls.major().unique_id(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            post.attachments(false).cloned().collect::<Vec<_>>(),
+            [PostAttachment::Image(PostAttachmentImage {
+                media_url: "https://i1.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg".into(),
+                has_spoiler: false
+            })]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::anyhow;
+use serde_json as json;
+use tokio::fs;
+
+use super::StoreTrait;
+
+// Stores each key as a JSON file under a directory
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| anyhow!("failed to create state directory '{dir:?}': {err}"))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", sanitize_file_name(key)))
+    }
+
+    async fn load_impl(&self, key: &str) -> anyhow::Result<Option<json::Value>> {
+        let path = self.path(key);
+        let text = match fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(anyhow!("failed to read state file '{path:?}': {err}")),
+        };
+        json::from_str(&text)
+            .map(Some)
+            .map_err(|err| anyhow!("failed to parse state file '{path:?}': {err}"))
+    }
+
+    async fn save_impl(&self, key: &str, value: json::Value) -> anyhow::Result<()> {
+        let path = self.path(key);
+        let text = json::to_string_pretty(&value)?;
+        write_atomically(&path, text).await
+    }
+}
+
+impl StoreTrait for FileStore {
+    fn load<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<Option<json::Value>>> + Send + 'a>> {
+        Box::pin(self.load_impl(key))
+    }
+
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        value: json::Value,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(self.save_impl(key, value))
+    }
+}
+
+// Write to a temporary file and then rename it, so that the state file will not
+// be corrupted if we are killed while writing. The temporary name is unique, as
+// the same key may be written concurrently.
+async fn write_atomically(path: &Path, content: String) -> anyhow::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let tmp_path = path.with_extension(format!(
+        "json.{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp_path, content)
+        .await
+        .map_err(|err| anyhow!("failed to write state file '{tmp_path:?}': {err}"))?;
+    fs::rename(&tmp_path, path)
+        .await
+        .map_err(|err| anyhow!("failed to rename state file to '{path:?}': {err}"))
+}
+
+fn sanitize_file_name(key: &str) -> String {
+    key.chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn file_name() {
+        assert_eq!(
+            sanitize_file_name("status.bilibili.live:123"),
+            "status.bilibili.live_123"
+        );
+        assert_eq!(sanitize_file_name("Twitter:a/b\\c"), "Twitter_a_b_c");
+    }
+
+    #[tokio::test]
+    async fn load_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().join("nested")).unwrap();
+
+        assert_eq!(store.load_impl("meow").await.unwrap(), None);
+
+        store
+            .save_impl("meow", json!({ "a": 1, "b": ["c"] }))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.load_impl("meow").await.unwrap(),
+            Some(json!({ "a": 1, "b": ["c"] }))
+        );
+
+        store.save_impl("meow", json!(null)).await.unwrap();
+        assert_eq!(store.load_impl("meow").await.unwrap(), Some(json!(null)));
+    }
+
+    #[tokio::test]
+    async fn concurrent_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+
+        let results = futures_util::future::join_all(
+            (0..10).map(|i| store.save_impl("meow", json!({ "i": i }))),
+        )
+        .await;
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert!(store.load_impl("meow").await.unwrap().is_some());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+mod file;
+
+use std::{future::Future, path::PathBuf, pin::Pin};
+
+use anyhow::anyhow;
+pub use file::FileStore;
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json as json;
+
+pub trait StoreTrait: Send + Sync {
+    fn load<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<Option<json::Value>>> + Send + 'a>>;
+
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        value: json::Value,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+}
+
+pub struct Store(Box<dyn StoreTrait>);
+
+impl Store {
+    pub fn new(store: impl StoreTrait + 'static) -> Self {
+        Self(Box::new(store))
+    }
+
+    pub async fn load<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.0
+            .load(key)
+            .await?
+            .map(|value| {
+                json::from_value(value)
+                    .map_err(|err| anyhow!("failed to deserialize state '{key}': {err}"))
+            })
+            .transpose()
+    }
+
+    pub async fn save<T: Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
+        let value = json::to_value(value)
+            .map_err(|err| anyhow!("failed to serialize state '{key}': {err}"))?;
+        self.0.save(key, value).await
+    }
+}
+
+static STORE: OnceCell<Option<Store>> = OnceCell::new();
+
+pub fn init(state_dir: Option<PathBuf>) -> anyhow::Result<()> {
+    let store = state_dir
+        .map(|dir| FileStore::new(dir).map(Store::new))
+        .transpose()?;
+    STORE
+        .set(store)
+        .map_err(|_| anyhow!("state store was initialized before"))
+}
+
+// Returns `None` if persistence is not enabled
+pub fn store() -> Option<&'static Store> {
+    STORE.get().and_then(|store| store.as_ref())
+}
@@ -1,37 +1,229 @@
 mod reporter;
 mod subscription;
 
-use std::{future::Future, pin::Pin};
+use std::{future::Future, pin::Pin, time::Duration};
 
 pub use reporter::TaskReporter;
 use spdlog::prelude::*;
-pub use subscription::TaskSubscription;
+pub use subscription::{Subscriber, TaskSubscription};
+use tokio::{sync::watch, task::JoinHandle};
 
 pub trait Task: Send {
-    fn run(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+    // The task should return once `stop` is signaled, after finishing the work in
+    // progress (e.g. sending notifications)
+    fn run(&mut self, stop: StopSignal) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
 }
 
-pub struct Runner {
-    join_handles: Vec<tokio::task::JoinHandle<()>>,
+#[derive(Clone)]
+pub struct StopSignal(watch::Receiver<bool>);
+
+impl StopSignal {
+    // Cancel safe, so it can be used in `tokio::select!` loops
+    pub async fn stopped(&mut self) {
+        // The runner is gone if it fails, also treat it as stopped
+        _ = self.0.wait_for(|stop| *stop).await;
+    }
+}
+
+// Running tasks keyed by what they are spawned from, so that they can be
+// reconciled with a new set of keys on reloading
+pub struct Runner<K> {
+    tasks: Vec<(K, RunningTask)>,
+}
+
+// Each task has its own stop signal, so that it can be stopped alone on
+// reloading
+struct RunningTask {
+    join_handle: JoinHandle<()>,
+    stop: watch::Sender<bool>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ReconcileStats {
+    pub stopped: usize,
+    pub started: usize,
+    pub kept: usize,
+}
+
+impl<K: PartialEq> Runner<K> {
+    pub fn new() -> Self {
+        Self { tasks: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn spawn(&mut self, key: K, mut task: Box<dyn Task>) {
+        let (stop, receiver) = watch::channel(false);
+        let join_handle = tokio::spawn(async move { task.run(StopSignal(receiver)).await });
+        self.tasks.push((key, RunningTask { join_handle, stop }));
+    }
+
+    // Stops tasks whose key is not in `keys`, and starts tasks for keys that are
+    // not running yet. Tasks with an unchanged key are kept running. Stopped tasks
+    // are given `timeout` to finish, the same as `shutdown`.
+    pub async fn reconcile(
+        &mut self,
+        keys: impl IntoIterator<Item = K>,
+        make_task: impl Fn(&K) -> Box<dyn Task>,
+        timeout: Duration,
+    ) -> ReconcileStats {
+        let mut stats = ReconcileStats::default();
+
+        let mut unmatched = std::mem::take(&mut self.tasks);
+        let mut to_start = vec![];
+        for key in keys {
+            match unmatched.iter().position(|(running, _)| *running == key) {
+                Some(index) => {
+                    self.tasks.push(unmatched.swap_remove(index));
+                    stats.kept += 1;
+                }
+                None => to_start.push(key),
+            }
+        }
+
+        // Stop first, the new ones may take over resources held by the old ones
+        stats.stopped = unmatched.len();
+        stop_tasks(unmatched, timeout).await;
+        for key in to_start {
+            let task = make_task(&key);
+            self.spawn(key, task);
+            stats.started += 1;
+        }
+
+        stats
+    }
+
+    // Signals all tasks to stop and waits for them, tasks still running after
+    // `timeout` are aborted
+    pub async fn shutdown(self, timeout: Duration) {
+        stop_tasks(self.tasks, timeout).await;
+    }
 }
 
-impl Runner {
-    pub async fn join_all(self) {
-        for join_handle in self.join_handles {
-            if let Err(err) = join_handle.await {
+async fn stop_tasks<K>(mut tasks: Vec<(K, RunningTask)>, timeout: Duration) {
+    for (_, task) in &tasks {
+        task.stop.send_replace(true);
+    }
+
+    let join_all = async {
+        for (_, task) in &mut tasks {
+            if let Err(err) = (&mut task.join_handle).await {
                 error!("failed to join task: {err}");
             }
         }
+    };
+    if tokio::time::timeout(timeout, join_all).await.is_err() {
+        let running = tasks
+            .iter()
+            .filter(|(_, task)| !task.join_handle.is_finished())
+            .count();
+        warn!("{running} tasks are still running after {timeout:?}, aborting them");
+        for (_, task) in &tasks {
+            task.join_handle.abort();
+        }
     }
 }
 
-pub async fn run_tasks(tasks: impl IntoIterator<Item = Box<dyn Task>>) -> anyhow::Result<Runner> {
-    let join_handles = tasks
-        .into_iter()
-        .map(|mut task| tokio::spawn(async move { task.run().await }))
-        .collect::<Vec<_>>();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Pending;
+
+    impl Task for Pending {
+        fn run(&mut self, _stop: StopSignal) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    struct Graceful(Duration);
+
+    impl Task for Graceful {
+        fn run(&mut self, mut stop: StopSignal) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+            Box::pin(async move {
+                stop.stopped().await;
+                tokio::time::sleep(self.0).await; // Simulate in-flight work
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconcile() {
+        let make_task = |_: &&str| Box::new(Pending) as Box<dyn Task>;
+        let timeout = Duration::from_secs(5);
+
+        let mut runner = Runner::new();
+        assert_eq!(
+            runner.reconcile(["a", "b", "b"], make_task, timeout).await,
+            ReconcileStats {
+                stopped: 0,
+                started: 3,
+                kept: 0
+            }
+        );
+        assert_eq!(runner.len(), 3);
+
+        assert_eq!(
+            runner.reconcile(["b", "c", "a"], make_task, timeout).await,
+            ReconcileStats {
+                stopped: 1,
+                started: 1,
+                kept: 2
+            }
+        );
+        assert_eq!(runner.len(), 3);
+
+        assert_eq!(
+            runner.reconcile([], make_task, timeout).await,
+            ReconcileStats {
+                stopped: 3,
+                started: 0,
+                kept: 0
+            }
+        );
+        assert_eq!(runner.len(), 0);
+
+        // Stopped tasks are signaled and waited for before the new ones start
+        let make_task = |_: &&str| Box::new(Graceful(Duration::from_secs(1))) as Box<dyn Task>;
+        runner.reconcile(["a"], make_task, timeout).await;
+        let handle = runner.tasks[0].1.join_handle.abort_handle();
+        let start = tokio::time::Instant::now();
+        runner.reconcile(["b"], make_task, timeout).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(handle.is_finished());
+    }
 
-    info!("{} tasks are running", join_handles.len());
+    #[tokio::test(start_paused = true)]
+    async fn shutdown() {
+        let mut runner = Runner::new();
+        runner.spawn(0, Box::new(Graceful(Duration::from_secs(1))));
+        runner.spawn(1, Box::new(Graceful(Duration::from_secs(3))));
+        let mut handles = runner
+            .tasks
+            .iter()
+            .map(|(_, task)| task.join_handle.abort_handle())
+            .collect::<Vec<_>>();
 
-    Ok(Runner { join_handles })
+        let start = tokio::time::Instant::now();
+        runner.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert!(handles.iter().all(|handle| handle.is_finished()));
+
+        let mut runner = Runner::new();
+        runner.spawn(0, Box::new(Graceful(Duration::from_secs(1))));
+        runner.spawn(1, Box::new(Pending));
+        handles = runner
+            .tasks
+            .iter()
+            .map(|(_, task)| task.join_handle.abort_handle())
+            .collect();
+
+        let start = tokio::time::Instant::now();
+        runner.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        tokio::task::yield_now().await;
+        assert!(handles.iter().all(|handle| handle.is_finished()));
+    }
 }
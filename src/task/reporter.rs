@@ -4,7 +4,7 @@ use anyhow::ensure;
 use spdlog::prelude::*;
 use tokio::time::MissedTickBehavior;
 
-use super::Task;
+use super::{StopSignal, Task};
 use crate::{
     helper,
     reporter::{ConfigHeartbeat, ConfigHeartbeatKind, ReporterParams},
@@ -19,14 +19,17 @@ impl TaskReporter {
         Self { params }
     }
 
-    async fn run_impl(&self) {
+    async fn run_impl(&self, mut stop: StopSignal) {
         let heartbeat = self.params.heartbeat.as_ref().unwrap();
 
         let mut interval = tokio::time::interval(heartbeat.interval);
         interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.stopped() => break,
+            }
             if let Err(err) = Self::run_once(heartbeat).await {
                 error!("error occurred while sending heartbeat: {err}");
             } else {
@@ -51,9 +54,9 @@ impl TaskReporter {
 }
 
 impl Task for TaskReporter {
-    fn run(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+    fn run(&mut self, stop: StopSignal) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
         match *self.params.heartbeat {
-            Some(_) => Box::pin(self.run_impl()),
+            Some(_) => Box::pin(self.run_impl(stop)),
             None => Box::pin(async {}),
         }
     }
@@ -3,52 +3,154 @@ use std::{fmt::Display, future::Future, pin::Pin, time::Duration};
 use spdlog::prelude::*;
 use tokio::{sync::mpsc, time::MissedTickBehavior};
 
-use super::Task;
+use super::{StopSignal, Task};
 use crate::{
-    config, notify,
-    source::{self, sourcer, FetcherTrait, Sourcer, Status, Update},
+    config, helper, notify,
+    source::{self, sourcer, FetcherTrait, Sourcer, Status, StatusStore, Update},
 };
 
+// How often to check if there are failed notifications due to retry
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Number of consecutive fetch failures before reporting the subscription as
+// degraded
+const DEGRADED_THRESHOLD: u32 = 3;
+
+// A subscription to a source, multiple subscriptions to the same source share
+// one task, so that the source is only fetched once
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subscriber {
+    pub name: String,
+    pub notify: Vec<config::NotifyResolved>,
+}
+
 pub struct TaskSubscription {
-    name: String,
+    name: String, // names of all subscribers, for logging
     interval: Duration,
-    notifiers: Vec<Box<dyn notify::NotifierTrait>>,
-    sourcer: Option<Sourcer>, // took when the task is running
+    max_backoff: Duration,
+    subscribers: Vec<Subscriber>,
+    source: String,
+    replay_dead_letters: bool,
+    status_store: StatusStore,
+    outboxes: Vec<(String /* subscriber */, notify::Outbox)>, // opened when the task is running
+    sourcer: Option<Sourcer>,                                 // took when the task is running
 }
 
 impl TaskSubscription {
     pub fn new(
-        name: String,
         interval: Duration,
-        notify: Vec<config::Accessor<notify::platform::Config>>,
+        max_backoff: Duration,
+        subscribers: Vec<Subscriber>,
         source_platform: &config::Accessor<source::platform::Config>,
+        replay_dead_letters: bool,
     ) -> Self {
+        let status_store = StatusStore::new(
+            source_platform,
+            subscribers
+                .iter()
+                .map(|subscriber| subscriber.name.clone())
+                .collect(),
+        );
         Self {
-            name,
+            name: subscribers
+                .iter()
+                .map(|subscriber| subscriber.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
             interval,
-            notifiers: notify.into_iter().map(notify::notifier).collect(),
-            sourcer: Some(sourcer(source_platform)),
+            max_backoff,
+            subscribers,
+            source: source_platform.to_string(),
+            replay_dead_letters,
+            outboxes: vec![],
+            sourcer: Some(sourcer(source_platform, interval, status_store.clone())),
+            status_store,
+        }
+    }
+
+    async fn open_outboxes(&mut self) {
+        for subscriber in &self.subscribers {
+            for notify in &subscriber.notify {
+                let outbox = notify::Outbox::new(
+                    format!("{}.{}", subscriber.name, self.source),
+                    notify.clone(),
+                    self.replay_dead_letters,
+                )
+                .await;
+                self.outboxes.push((subscriber.name.clone(), outbox));
+            }
+        }
+    }
+
+    async fn retry_outboxes(&mut self) {
+        for (_, outbox) in &mut self.outboxes {
+            outbox.retry_due().await;
+        }
+    }
+
+    async fn close_outboxes(&mut self) {
+        for (_, outbox) in &mut self.outboxes {
+            outbox.close().await;
         }
     }
 
     // Handler for poll-based subscription
-    async fn continuous_fetch(&mut self, fetcher: Box<dyn FetcherTrait>) {
+    async fn continuous_fetch(&mut self, fetcher: Box<dyn FetcherTrait>, mut stop: StopSignal) {
         let mut interval = tokio::time::interval(self.interval);
         interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
+        let mut retry_interval = tokio::time::interval(RETRY_CHECK_INTERVAL);
+        retry_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         let mut last_status = Status::empty();
+        let (mut last_state, mut seen_by) = match self.status_store.load().await {
+            Some((state, seen_by)) => (Some(state), Some(seen_by)),
+            None => (None, None),
+        };
+        let mut failures = 0_u32;
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = retry_interval.tick() => {
+                    self.retry_outboxes().await;
+                    continue;
+                }
+                _ = stop.stopped() => break,
+            }
 
-            let Ok(mut status) = fetcher.fetch_status().await.inspect_err(|err| {
-                error!(
-                    "failed to fetch status for '{}' on '{}': {err}",
-                    self.name, fetcher
-                )
-            }) else {
-                continue;
+            let mut status = match fetcher.fetch_status().await {
+                Ok(status) => status,
+                Err(err) => {
+                    failures += 1;
+                    // Back off instead of retrying on every tick, the normal interval resumes
+                    // after the delayed tick
+                    let delay =
+                        helper::exponential_backoff(self.interval, failures, self.max_backoff)
+                            .max(self.interval);
+                    interval.reset_after(delay);
+
+                    info!(
+                        "failed to fetch status for '{}' on '{fetcher}' ({failures} in a row), retry in {}: {err}",
+                        self.name,
+                        helper::format_duration_in_sec(delay)
+                    );
+                    if failures == DEGRADED_THRESHOLD {
+                        error!(
+                            "subscription '{}' on '{fetcher}' is degraded, failed to fetch status {failures} times in a row: {err}",
+                            self.name
+                        );
+                    }
+                    continue;
+                }
             };
+            if failures >= DEGRADED_THRESHOLD {
+                warn!(
+                    "subscription '{}' on '{fetcher}' is recovered after {failures} failures in a row",
+                    self.name
+                );
+            }
+            failures = 0;
 
             status.sort();
 
@@ -57,11 +159,33 @@ impl TaskSubscription {
                 self.name
             );
 
-            let notifications = status.generate_notifications(&last_status);
-            self.notify(notifications, &fetcher).await;
+            let notifications = match (&last_state, last_status.is_empty()) {
+                // The first fetch after startup, compare with the restored state so that
+                // updates happened while we were down are not lost
+                (Some(state), true) => status.generate_notifications_since(state),
+                _ => status.generate_notifications(&last_status),
+            };
+            // Until the first update, only the subscribers who have seen the restored
+            // state are notified, the others start from the current status
+            let recipients = if last_status.is_empty() {
+                seen_by.as_deref()
+            } else {
+                None
+            };
+            self.notify(notifications, &fetcher, recipients).await;
 
             last_status.update_incrementally(status);
             trace!("subscription '{}' updated once", self.name);
+
+            if let Some(state) = last_status.state() {
+                let unseen = seen_by
+                    .take()
+                    .is_some_and(|seen_by| seen_by.len() < self.subscribers.len());
+                if unseen || last_state.as_ref() != Some(&state) {
+                    self.status_store.save(&state).await;
+                    last_state = Some(state);
+                }
+            }
         }
     }
 
@@ -70,47 +194,81 @@ impl TaskSubscription {
         &mut self,
         mut receiver: mpsc::Receiver<Update>,
         platform: impl Display,
+        mut stop: StopSignal,
     ) {
-        while let Some(update) = receiver.recv().await {
+        let mut retry_interval = tokio::time::interval(RETRY_CHECK_INTERVAL);
+        retry_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let update = tokio::select! {
+                update = receiver.recv() => match update {
+                    Some(update) => update,
+                    None => break,
+                },
+                _ = retry_interval.tick() => {
+                    self.retry_outboxes().await;
+                    continue;
+                }
+                _ = stop.stopped() => break,
+            };
+
             trace!(
                 "event of '{}' on '{platform}' received an update '{update:?}'",
                 self.name
             );
 
             let notifications = update.generate_notifications().await;
-            self.notify(notifications, &platform).await;
+            self.notify(notifications, &platform, None).await;
         }
     }
 
-    async fn notify(&self, notifications: Vec<source::Notification<'_>>, platform: &impl Display) {
+    // `recipients` are the names of the subscribers to notify, `None` for all
+    async fn notify(
+        &mut self,
+        notifications: Vec<source::Notification<'_>>,
+        platform: &impl Display,
+        recipients: Option<&[String]>,
+    ) {
         for notification in notifications {
             info!(
                 "'{}' needs to send a notification for '{platform}': '{notification}'",
                 self.name
             );
 
-            for notifier in &self.notifiers {
-                notify::notify(&**notifier, &notification).await;
+            for (subscriber, outbox) in &mut self.outboxes {
+                if recipients.is_none_or(|recipients| recipients.contains(subscriber)) {
+                    outbox.deliver(&notification).await;
+                }
             }
         }
     }
 }
 
 impl Task for TaskSubscription {
-    fn run(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
-        match self.sourcer.take().unwrap() {
-            Sourcer::Fetcher(fetcher) => Box::pin(self.continuous_fetch(fetcher)),
-            Sourcer::Listener(mut listener) => {
-                let (sender, receiver) = mpsc::channel(10);
-                // TODO: A bit hacky, improve it?
-                let platform = listener.to_string();
-                Box::pin(async move {
+    fn run(&mut self, stop: StopSignal) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            self.open_outboxes().await;
+
+            match self.sourcer.take().unwrap() {
+                Sourcer::Fetcher(fetcher) => self.continuous_fetch(fetcher, stop).await,
+                Sourcer::Listener(mut listener) => {
+                    let (sender, receiver) = mpsc::channel(10);
+                    // TODO: A bit hacky, improve it?
+                    let platform = listener.to_string();
+                    let mut listener_stop = stop.clone();
                     tokio::join!(
-                        listener.listen(sender),
-                        self.continuous_wait(receiver, platform)
+                        async {
+                            tokio::select! {
+                                _ = listener.listen(sender) => {}
+                                _ = listener_stop.stopped() => {}
+                            }
+                        },
+                        self.continuous_wait(receiver, platform, stop)
                     );
-                })
+                }
             }
-        }
+
+            self.close_outboxes().await;
+        })
     }
 }